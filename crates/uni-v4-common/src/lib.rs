@@ -6,8 +6,11 @@ pub mod pool_updates;
 pub mod traits;
 
 // Re-export commonly used types
-pub use pools::{PoolError, SwapSimulationError, UniswapPools};
-pub use traits::{PoolUpdateDelivery, PoolUpdateDeliveryExt};
+pub use pools::{
+    FrozenUniswapPools, PoolError, PoolHealth, SwapSimulationError, TickStats, UniswapPools,
+    UnknownPoolPolicy
+};
+pub use traits::{PoolUpdateDelivery, PoolUpdateDeliveryExt, UpdateOrder};
 pub use uni_v4_structure::pool_updates::{ModifyLiquidityEventData, PoolUpdate, SwapEventData};
 
 /// Configuration for what types of pool updates should be streamed
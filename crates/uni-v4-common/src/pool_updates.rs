@@ -1,4 +1,7 @@
-use std::collections::{HashMap, VecDeque};
+use std::{
+    collections::{HashMap, VecDeque},
+    task::{Context, Poll, Waker}
+};
 
 use alloy_primitives::U256;
 use uni_v4_structure::{
@@ -13,23 +16,29 @@ use crate::{V4Network, traits::PoolUpdateDelivery};
 /// A queue-based implementation of PoolUpdateDelivery that allows feeding
 /// PoolUpdate instances
 pub struct PoolUpdateQueue<T: V4Network> {
-    updates: VecDeque<PoolUpdate<T>>
+    updates: VecDeque<PoolUpdate<T>>,
+    // woken by the next push after `poll_updates` found the queue empty
+    waker:   Option<Waker>
 }
 
 impl<T: V4Network> PoolUpdateQueue<T> {
     /// Create a new empty PoolUpdateQueue
     pub fn new() -> Self {
-        Self { updates: VecDeque::new() }
+        Self { updates: VecDeque::new(), waker: None }
     }
 
     /// Add a single update to the queue
     pub fn push(&mut self, update: PoolUpdate<T>) {
         self.updates.push_back(update);
+        self.wake();
     }
 
     /// Add multiple updates to the queue
     pub fn extend(&mut self, updates: impl IntoIterator<Item = PoolUpdate<T>>) {
         self.updates.extend(updates);
+        if !self.updates.is_empty() {
+            self.wake();
+        }
     }
 
     /// Get the number of pending updates
@@ -46,6 +55,12 @@ impl<T: V4Network> PoolUpdateQueue<T> {
     pub fn clear(&mut self) {
         self.updates.clear();
     }
+
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
 }
 
 impl<T: V4Network> Default for PoolUpdateQueue<T> {
@@ -107,10 +122,12 @@ impl<T: V4Network> PoolUpdateDelivery<T> for PoolUpdateQueue<T> {
         }
     }
 
-    fn get_fee_update(&mut self) -> Option<(PoolId, u64, <T::FeeConfig as FeeConfig>::Update)> {
+    fn get_fee_update(
+        &mut self
+    ) -> Option<(PoolId, u64, u64, u64, <T::FeeConfig as FeeConfig>::Update)> {
         match self.updates.front() {
-            Some(PoolUpdate::FeeUpdate { pool_id, block, update }) => {
-                let result = (*pool_id, *block, *update);
+            Some(PoolUpdate::FeeUpdate { pool_id, block, tx_index, log_index, update }) => {
+                let result = (*pool_id, *block, *tx_index, *log_index, *update);
                 self.updates.pop_front();
                 Some(result)
             }
@@ -166,4 +183,60 @@ impl<T: V4Network> PoolUpdateDelivery<T> for PoolUpdateQueue<T> {
             _ => None
         }
     }
+
+    fn poll_updates(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if !self.updates.is_empty() {
+            return Poll::Ready(());
+        }
+        self.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        pin::pin,
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering}
+        },
+        task::Wake
+    };
+
+    use alloy_network::Ethereum;
+
+    use super::*;
+    use crate::traits::{PoolUpdateDeliveryExt, UpdateOrder};
+
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn empty_queues_wait_for_a_push() {
+        let wakes = Arc::new(CountingWaker::default());
+        let waker = Waker::from(wakes.clone());
+        let mut cx = Context::from_waker(&waker);
+        let mut queue = PoolUpdateQueue::<Ethereum>::new();
+        let mut order = UpdateOrder::priority();
+
+        {
+            let mut next = pin!(queue.next_update_async(&mut order));
+            assert!(next.as_mut().poll(&mut cx).is_pending());
+            assert!(next.as_mut().poll(&mut cx).is_pending());
+        }
+        // parked rather than waking itself to poll again
+        assert_eq!(wakes.0.load(Ordering::SeqCst), 0);
+
+        queue.push(PoolUpdate::NewBlock(5));
+        assert_eq!(wakes.0.load(Ordering::SeqCst), 1);
+        let mut next = pin!(queue.next_update_async(&mut order));
+        assert!(matches!(next.as_mut().poll(&mut cx), Poll::Ready(PoolUpdate::NewBlock(5))));
+    }
 }
@@ -1,32 +1,88 @@
 use std::{
+    collections::HashMap,
     ops::Deref,
-    sync::{Arc, atomic::AtomicU64}
+    sync::{
+        Arc,
+        atomic::{AtomicU64, AtomicUsize}
+    }
 };
 
-use alloy_primitives::B256;
+use alloy_primitives::{Address, B256, U256};
 use dashmap::{DashMap, mapref::one::Ref};
 use thiserror::Error;
 use tokio::sync::{
-    Notify,
+    Notify, broadcast,
     futures::{Notified, OwnedNotified}
 };
 use uni_v4_structure::{
-    BaselinePoolState, PoolId, UpdatePool, V4Network, fee_config::FeeConfig,
-    pool_updates::PoolUpdate
+    BaselinePoolState, ConversionError, PoolId, UpdatePool, V4Network, fee_config::FeeConfig,
+    pool_key::MAX_LP_FEE, pool_registry::PoolRegistry, pool_swap::Quote, pool_updates::PoolUpdate,
+    sqrt_pricex96::SqrtPriceX96
 };
 use uniswap_v3_math::error::UniswapV3MathError;
 
 use crate::traits::{PoolUpdateDelivery, PoolUpdateDeliveryExt};
 
+/// One-call summary of a pool's state and how recently it was updated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolHealth {
+    pub tick:              i32,
+    pub sqrt_price:        SqrtPriceX96,
+    pub liquidity:         u128,
+    /// lowest and highest initialized tick loaded, `None` when no ticks are
+    /// loaded
+    pub loaded_ticks:      Option<(i32, i32)>,
+    pub last_update_block: u64,
+    /// fee charged in the pool's current mode
+    pub fee:               u32,
+    /// block slot0 was last set from a swap or slot0 refresh
+    pub slot0_block:       u64,
+    /// whether slot0 was set at the block the pools are up to date for
+    pub slot0_fresh:       bool
+}
+
+/// What [`UniswapPools::update_pools`] does with an update for a pool it
+/// doesn't hold. Such updates are always skipped, usually it means a pool is
+/// being tracked that was never loaded.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum UnknownPoolPolicy {
+    Ignore,
+    #[default]
+    Warn,
+    /// [`UniswapPools::try_update_pools`] fails with [`PoolError::UnknownPool`]
+    Error
+}
+
+/// Number of initialized ticks and tick bitmap words loaded, across all pools
+/// and per pool
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TickStats {
+    pub total_ticks: usize,
+    pub total_words: usize,
+    /// `(ticks, words)` for each pool
+    pub per_pool:    HashMap<PoolId, (usize, usize)>
+}
+
 #[derive(Clone)]
 pub struct UniswapPools<T: V4Network> {
-    pools:           Arc<DashMap<PoolId, BaselinePoolState<T>>>,
-    slot0_notifiers: Arc<DashMap<PoolId, Arc<Notify>>>,
+    pools:               Arc<DashMap<PoolId, BaselinePoolState<T>>>,
+    slot0_notifiers:     Arc<DashMap<PoolId, Arc<Notify>>>,
+    // block each pool's slot0 was last set at, pools without an entry haven't
+    // had it set since they were loaded
+    slot0_blocks:        Arc<DashMap<PoolId, u64>>,
     // what block these are up to date for.
-    block_number:    Arc<AtomicU64>,
+    block_number:        Arc<AtomicU64>,
     // When the manager for the pools pushes a new block. It will notify all people who are
     // waiting.
-    notifier:        Arc<Notify>
+    notifier:            Arc<Notify>,
+    // `PoolUpdate::TickBitmapDelta`s for replicas, only sent once enabled
+    bitmap_deltas:       Option<broadcast::Sender<PoolUpdate<T>>>,
+    // estimated `(token0, token1)` fees from swaps seen since start or the last
+    // reset
+    session_fees:        Arc<DashMap<PoolId, (U256, U256)>>,
+    unknown_pool_policy: UnknownPoolPolicy,
+    // pool updates applied by the last `update_pools` call
+    last_update_count:   Arc<AtomicUsize>
 }
 
 impl<T: V4Network> Deref for UniswapPools<T> {
@@ -37,7 +93,52 @@ impl<T: V4Network> Deref for UniswapPools<T> {
     }
 }
 
+/// A read-only snapshot of pools at a block, see [`UniswapPools::frozen`]
+#[derive(Clone)]
+pub struct FrozenUniswapPools<T: V4Network> {
+    pools:        Arc<HashMap<PoolId, BaselinePoolState<T>>>,
+    block_number: u64
+}
+
+impl<T: V4Network> Deref for FrozenUniswapPools<T> {
+    type Target = HashMap<PoolId, BaselinePoolState<T>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.pools
+    }
+}
+
+impl<T: V4Network> FrozenUniswapPools<T> {
+    pub fn get_block(&self) -> u64 {
+        self.block_number
+    }
+
+    pub fn get_pool(&self, pool_id: &PoolId) -> Option<&BaselinePoolState<T>> {
+        self.pools.get(pool_id)
+    }
+
+    pub fn get_pools(&self) -> &HashMap<PoolId, BaselinePoolState<T>> {
+        &self.pools
+    }
+
+    /// Frozen pools never change, every update is refused with
+    /// [`PoolError::Frozen`]
+    pub fn update_pools(&self, updates: Vec<PoolUpdate<T>>) -> Result<(), PoolError> {
+        if updates.is_empty() { Ok(()) } else { Err(PoolError::Frozen) }
+    }
+}
+
 impl<T: V4Network> UniswapPools<T> {
+    /// Pools that are only ever read, e.g. for simulating against a fixed
+    /// block. Reads go straight to a shared `HashMap` without the locking
+    /// of the live pools.
+    pub fn frozen(
+        pools: HashMap<PoolId, BaselinePoolState<T>>,
+        block_number: u64
+    ) -> FrozenUniswapPools<T> {
+        FrozenUniswapPools { pools: Arc::new(pools), block_number }
+    }
+
     pub fn new(pools: Arc<DashMap<PoolId, BaselinePoolState<T>>>, block_number: u64) -> Self {
         Self {
             slot0_notifiers: Arc::new(
@@ -47,8 +148,47 @@ impl<T: V4Network> UniswapPools<T> {
                     .collect()
             ),
             pools,
+            slot0_blocks: Default::default(),
             block_number: Arc::new(AtomicU64::from(block_number)),
-            notifier: Arc::new(Notify::new())
+            notifier: Arc::new(Notify::new()),
+            bitmap_deltas: None,
+            session_fees: Default::default(),
+            unknown_pool_policy: UnknownPoolPolicy::default(),
+            last_update_count: Default::default()
+        }
+    }
+
+    /// Emits a [`PoolUpdate::TickBitmapDelta`] for every tick bitmap word that
+    /// liquidity events or newly loaded ticks change, so a replica can follow
+    /// the bitmap without being sent it in full. Shared by all clones made
+    /// after this call.
+    pub fn with_tick_bitmap_deltas(mut self, capacity: usize) -> Self {
+        self.bitmap_deltas = Some(broadcast::channel(capacity).0);
+        self
+    }
+
+    /// How updates for pools that aren't loaded are handled, see
+    /// [`UnknownPoolPolicy`]
+    pub fn with_unknown_pool_policy(mut self, policy: UnknownPoolPolicy) -> Self {
+        self.unknown_pool_policy = policy;
+        self
+    }
+
+    /// Subscribes to tick bitmap deltas, `None` unless enabled with
+    /// [`Self::with_tick_bitmap_deltas`]
+    pub fn subscribe_tick_bitmap_deltas(&self) -> Option<broadcast::Receiver<PoolUpdate<T>>> {
+        self.bitmap_deltas
+            .as_ref()
+            .map(broadcast::Sender::subscribe)
+    }
+
+    /// Sends a delta if the word changed, a send only fails without
+    /// subscribers which is fine to ignore
+    fn emit_bitmap_delta(&self, pool_id: PoolId, word_pos: i16, old: U256, new: U256) {
+        if let Some(deltas) = &self.bitmap_deltas
+            && old != new
+        {
+            let _ = deltas.send(PoolUpdate::TickBitmapDelta { pool_id, word_pos, old, new });
         }
     }
 
@@ -79,10 +219,174 @@ impl<T: V4Network> UniswapPools<T> {
         self.pools.get(pool_id)
     }
 
+    /// Clones the pool's state out of the map. Unlike [`Self::get_pool`] this
+    /// doesn't hold the shard's lock, so it is safe to keep across an
+    /// `.await` at the cost of copying the pool's ticks. The copy doesn't
+    /// see updates applied after it was taken.
+    pub fn get_pool_owned(&self, pool_id: &PoolId) -> Option<BaselinePoolState<T>> {
+        self.pools.get(pool_id).map(|pool| pool.clone())
+    }
+
     pub fn get_pools(&self) -> &DashMap<PoolId, BaselinePoolState<T>> {
         &self.pools
     }
 
+    /// Whether the pool is tracked and has its slot0 and initial ticks, so
+    /// quoting it won't run off the loaded ticks early
+    pub fn pool_ready(&self, pool_id: &PoolId) -> bool {
+        self.pools
+            .get(pool_id)
+            .is_some_and(|pool| pool.ticks_loaded())
+    }
+
+    pub fn pool_health(&self, pool_id: &PoolId) -> Option<PoolHealth> {
+        let pool = self.pools.get(pool_id)?;
+        let liquidity = pool.liquidity();
+        let slot0_block = self
+            .slot0_blocks
+            .get(pool_id)
+            .map_or(pool.block_number(), |block| *block);
+
+        Some(PoolHealth {
+            tick: pool.current_tick(),
+            sqrt_price: pool.current_price(),
+            liquidity: pool.current_liquidity(),
+            loaded_ticks: liquidity
+                .get_min_initialized_tick()
+                .zip(liquidity.get_max_initialized_tick()),
+            last_update_block: pool.last_updated_block(),
+            fee: pool.current_fee(),
+            slot0_block,
+            slot0_fresh: slot0_block >= self.get_block()
+        })
+    }
+
+    /// How many ticks and bitmap words are held in memory, for sizing large
+    /// registries
+    pub fn tick_memory_stats(&self) -> TickStats {
+        let mut stats = TickStats::default();
+        for pool in self.pools.iter() {
+            let liquidity = pool.liquidity();
+            let (ticks, words) =
+                (liquidity.initialized_ticks().len(), liquidity.tick_bitmap().len());
+
+            stats.total_ticks += ticks;
+            stats.total_words += words;
+            stats.per_pool.insert(*pool.key(), (ticks, words));
+        }
+        stats
+    }
+
+    /// Finds the loaded pool for two token symbols, in either order, and a fee
+    /// using `symbols` to map each symbol to its token address. For CLI and
+    /// REPL tools, `None` if a symbol is unknown or no such pool is loaded.
+    pub fn resolve_pool_by_symbols(
+        &self,
+        registry: &T::PoolRegistry,
+        symbol_a: &str,
+        symbol_b: &str,
+        fee: u32,
+        symbols: &HashMap<String, Address>
+    ) -> Option<PoolId> {
+        let (token_a, token_b) = (symbols.get(symbol_a)?, symbols.get(symbol_b)?);
+        registry
+            .get_pool_id_by_tokens_and_fee(*token_a, *token_b, fee, None)
+            .filter(|pool_id| self.pools.contains_key(pool_id))
+    }
+
+    /// Quotes an exact input swap of `amount_in` against every loaded pool the
+    /// registry has for the pair, optionally only those using `hook`, and
+    /// returns the pool giving the most out. With the `rayon` feature the
+    /// pools are simulated in parallel.
+    pub fn best_quote(
+        &self,
+        registry: &T::PoolRegistry,
+        token_in: Address,
+        token_out: Address,
+        amount_in: u128,
+        hook: Option<Address>
+    ) -> Option<(PoolId, Quote)> {
+        let zero_for_one = token_in < token_out;
+        let pool_ids = registry
+            .get_pools_by_token_pair(token_in, token_out, hook)
+            .into_iter()
+            .map(|pool_key| PoolId::from(*pool_key))
+            .collect::<Vec<_>>();
+
+        let quote = |pool_id: &PoolId| {
+            let pool = self.pools.get(pool_id)?;
+            let quote = pool
+                .quote(amount_in, zero_for_one, pool.mode().is_bundle())
+                .ok()?;
+            Some((*pool_id, quote))
+        };
+
+        #[cfg(feature = "rayon")]
+        let best = {
+            use rayon::prelude::*;
+            pool_ids
+                .par_iter()
+                .filter_map(quote)
+                .max_by_key(|(_, quote)| quote.amount_out)
+        };
+        #[cfg(not(feature = "rayon"))]
+        let best = pool_ids
+            .iter()
+            .filter_map(quote)
+            .max_by_key(|(_, quote)| quote.amount_out);
+
+        best
+    }
+
+    /// How many pool updates the last [`Self::update_pools`] call applied, for
+    /// throughput metrics. Block and reorg markers aren't counted, nor are
+    /// updates that were skipped, e.g. for pools that aren't loaded.
+    pub fn last_block_update_count(&self) -> usize {
+        self.last_update_count
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Estimated fees paid to the pool since start or the last
+    /// [`Self::reset_session_fees`], as `(token0, token1)`. Each swap event
+    /// charges the fee it reports on its input token. `None` for pools that
+    /// aren't loaded.
+    pub fn session_fees(&self, pool_id: &PoolId) -> Option<(U256, U256)> {
+        if !self.pools.contains_key(pool_id) {
+            return None;
+        }
+        Some(
+            self.session_fees
+                .get(pool_id)
+                .map_or((U256::ZERO, U256::ZERO), |fees| *fees)
+        )
+    }
+
+    /// Starts [`Self::session_fees`] over from zero for every pool
+    pub fn reset_session_fees(&self) {
+        self.session_fees.clear();
+    }
+
+    /// Adds the fee on a swap's input to [`Self::session_fees`]. Swap amounts
+    /// are the swapper's balance deltas, so the input is the negative one, and
+    /// the fee is in pips like the swap event's.
+    fn record_swap_fee(&self, pool_id: PoolId, amount0: i128, amount1: i128, fee: u32) {
+        let fee_on = |amount_in: i128| {
+            U256::from(amount_in.unsigned_abs()) * U256::from(fee) / U256::from(MAX_LP_FEE)
+        };
+
+        let mut fees = self.session_fees.entry(pool_id).or_default();
+        if amount0 < 0 {
+            fees.0 += fee_on(amount0);
+        } else if amount1 < 0 {
+            fees.1 += fee_on(amount1);
+        }
+    }
+
+    fn record_slot0(&self, pool_id: PoolId, block: u64) {
+        let mut slot0_block = self.slot0_blocks.entry(pool_id).or_default();
+        *slot0_block = (*slot0_block).max(block);
+    }
+
     pub fn next_block_future(&self) -> Notified<'_> {
         self.notifier.notified()
     }
@@ -99,18 +403,46 @@ impl<T: V4Network> UniswapPools<T> {
             .notified_owned()
     }
 
-    pub fn update_pools(&self, mut updates: Vec<PoolUpdate<T>>) {
+    /// Applies a batch of updates. Updates for pools that aren't loaded are
+    /// skipped and handled according to the [`UnknownPoolPolicy`], errors are
+    /// only logged.
+    pub fn update_pools(&self, updates: Vec<PoolUpdate<T>>) {
+        if let Err(e) = self.try_update_pools(updates) {
+            tracing::error!("Applying pool updates: {e}");
+        }
+    }
+
+    /// [`Self::update_pools`], returning [`PoolError::UnknownPool`] for the
+    /// first pool that isn't loaded under [`UnknownPoolPolicy::Error`]. The
+    /// rest of the batch is still applied.
+    pub fn try_update_pools(&self, mut updates: Vec<PoolUpdate<T>>) -> Result<(), PoolError> {
         if updates.is_empty() {
-            return;
+            self.last_update_count
+                .store(0, std::sync::atomic::Ordering::Relaxed);
+            return Ok(());
         }
 
         let current_block_number = self.block_number.load(std::sync::atomic::Ordering::Relaxed);
 
         let mut new_block_number = None;
+        // slot0 waiters are woken once per batch, after every update is applied
+        let mut slot0_notifiers: HashMap<PoolId, Arc<Notify>> = HashMap::new();
+        let mut notify_slot0 = |pool_id: PoolId| {
+            if let Some(notifier) = self.slot0_notifiers.get(&pool_id) {
+                slot0_notifiers
+                    .entry(pool_id)
+                    .or_insert_with(|| notifier.clone());
+            }
+        };
+        let mut unknown_pool = None;
+        let mut applied = 0;
         // we sort ascending
         updates.sort_by(|a, b| a.sort(b));
 
         for update in updates {
+            let block = new_block_number.unwrap_or(current_block_number);
+            // skipped updates `continue` before reaching the count
+            let pool_update = !matches!(update, PoolUpdate::NewBlock(_) | PoolUpdate::Reorg { .. });
             match update {
                 PoolUpdate::NewBlock(block_number) => {
                     new_block_number = Some(block_number);
@@ -118,49 +450,79 @@ impl<T: V4Network> UniswapPools<T> {
                 PoolUpdate::Reorg { to_block, .. } => {
                     new_block_number = Some(to_block);
                 }
-                PoolUpdate::SwapEvent { pool_id, event, .. } => {
+                PoolUpdate::SwapEvent { pool_id, event, block, .. } => {
                     let Some(mut pool) = self.pools.get_mut(&pool_id) else {
+                        self.on_unknown_pool(pool_id, &mut unknown_pool);
                         continue;
                     };
 
                     let state = pool.value_mut();
                     // update slot0 values
                     state.update_slot0(event.tick, event.sqrt_price_x96.into(), event.liquidity);
+                    state.mark_updated(block);
+                    drop(pool);
+                    self.record_slot0(pool_id, block);
+                    self.record_swap_fee(pool_id, event.amount0, event.amount1, event.fee);
                 }
-                PoolUpdate::LiquidityEvent { pool_id, event, .. } => {
+                PoolUpdate::LiquidityEvent { pool_id, event, block, .. } => {
                     let Some(mut pool) = self.pools.get_mut(&pool_id) else {
+                        self.on_unknown_pool(pool_id, &mut unknown_pool);
                         continue;
                     };
                     let state = pool.value_mut();
+                    let words = self.bitmap_deltas.is_some().then(|| {
+                        let mut words = [event.tick_lower, event.tick_upper]
+                            .map(|tick| {
+                                let word_pos = state.liquidity().tick_bitmap_word_pos(tick);
+                                (word_pos, state.liquidity().tick_bitmap_word(word_pos))
+                            })
+                            .to_vec();
+                        // both ticks can sit in the same word, only report it once
+                        words.dedup_by_key(|(word_pos, _)| *word_pos);
+                        words
+                    });
 
-                    state.update_liquidity(
+                    if let Err(e) = state.update_liquidity(
                         event.tick_lower,
                         event.tick_upper,
                         event.liquidity_delta
-                    );
+                    ) {
+                        tracing::error!("Skipping liquidity event for pool {pool_id:?}: {e}");
+                        continue;
+                    }
+                    state.mark_updated(block);
+
+                    for (word_pos, old) in words.into_iter().flatten() {
+                        let new = state.liquidity().tick_bitmap_word(word_pos);
+                        self.emit_bitmap_delta(pool_id, word_pos, old, new);
+                    }
                 }
-                PoolUpdate::FeeUpdate { pool_id, update, .. } => {
+                PoolUpdate::FeeUpdate { pool_id, update, block, .. } => {
                     let Some(mut pool) = self.pools.get_mut(&pool_id) else {
+                        self.on_unknown_pool(pool_id, &mut unknown_pool);
                         continue;
                     };
                     let fees = pool.value_mut().fees_mut();
 
                     fees.update_fees(update);
+                    pool.mark_updated(block);
                 }
                 PoolUpdate::UpdatedSlot0 { pool_id, data } => {
                     let Some(mut pool) = self.pools.get_mut(&pool_id) else {
+                        self.on_unknown_pool(pool_id, &mut unknown_pool);
                         continue;
                     };
 
                     let state = pool.value_mut();
                     state.update_slot0(data.tick, data.sqrt_price_x96.into(), data.liquidity);
-
-                    if let Some(notifier) = self.slot0_notifiers.get(&pool_id) {
-                        notifier.notify_waiters();
-                    }
+                    state.mark_updated(block);
+                    drop(pool);
+                    self.record_slot0(pool_id, block);
+                    notify_slot0(pool_id);
                 }
                 PoolUpdate::NewTicks { pool_id, ticks, tick_bitmap } => {
                     let Some(mut pool) = self.pools.get_mut(&pool_id) else {
+                        self.on_unknown_pool(pool_id, &mut unknown_pool);
                         continue;
                     };
 
@@ -171,11 +533,25 @@ impl<T: V4Network> UniswapPools<T> {
 
                     // Update tick bitmap
                     for (word_pos, word) in tick_bitmap {
+                        let old = baseline.tick_bitmap_word(word_pos);
                         baseline.update_tick_bitmap(word_pos, word);
+                        self.emit_bitmap_delta(pool_id, word_pos, old, word);
                     }
+                    pool.mark_ticks_loaded();
+                    pool.mark_updated(block);
+                }
+                PoolUpdate::TickBitmapDelta { pool_id, word_pos, new, .. } => {
+                    let Some(mut pool) = self.pools.get_mut(&pool_id) else {
+                        self.on_unknown_pool(pool_id, &mut unknown_pool);
+                        continue;
+                    };
+
+                    pool.liquidity_mut().update_tick_bitmap(word_pos, new);
+                    pool.mark_updated(block);
                 }
                 PoolUpdate::NewPoolState { pool_id, state } => {
                     self.pools.insert(pool_id, state);
+                    self.slot0_blocks.remove(&pool_id);
                     self.slot0_notifiers
                         .insert(pool_id, Arc::new(Notify::new()));
                 }
@@ -185,18 +561,27 @@ impl<T: V4Network> UniswapPools<T> {
                     }
 
                     let Some(mut pool) = self.pools.get_mut(&pool_id) else {
+                        self.on_unknown_pool(pool_id, &mut unknown_pool);
                         continue;
                     };
 
                     let should_notify = update.should_notify_waiters();
 
                     pool.update_chain_specific(update);
+                    pool.mark_updated(block);
 
-                    if should_notify && let Some(notifier) = self.slot0_notifiers.get(&pool_id) {
-                        notifier.notify_waiters();
+                    if should_notify {
+                        notify_slot0(pool_id);
                     }
                 }
             }
+            applied += usize::from(pool_update);
+        }
+        self.last_update_count
+            .store(applied, std::sync::atomic::Ordering::Relaxed);
+
+        for notifier in slot0_notifiers.into_values() {
+            notifier.notify_waiters();
         }
 
         if let Some(bn) = new_block_number {
@@ -204,6 +589,20 @@ impl<T: V4Network> UniswapPools<T> {
                 .store(bn, std::sync::atomic::Ordering::SeqCst);
             self.notifier.notify_waiters();
         }
+
+        unknown_pool.map_or(Ok(()), |pool_id| Err(PoolError::UnknownPool(pool_id)))
+    }
+
+    fn on_unknown_pool(&self, pool_id: PoolId, first_unknown: &mut Option<PoolId>) {
+        match self.unknown_pool_policy {
+            UnknownPoolPolicy::Ignore => {}
+            UnknownPoolPolicy::Warn => {
+                tracing::warn!("Skipping update for pool {pool_id:?}, it isn't loaded");
+            }
+            UnknownPoolPolicy::Error => {
+                first_unknown.get_or_insert(pool_id);
+            }
+        }
     }
 
     /// Update pools using a PoolUpdateDelivery source
@@ -257,6 +656,10 @@ pub enum PoolError {
     PoolAlreadyInitialized,
     #[error("Pool is not initialized")]
     PoolNotInitialized,
+    #[error("Frozen pools can't be updated")]
+    Frozen,
+    #[error("Update for pool {0:?}, which isn't loaded")]
+    UnknownPool(PoolId),
     #[error(transparent)]
     SwapSimulationError(#[from] SwapSimulationError),
     #[error(transparent)]
@@ -264,5 +667,467 @@ pub enum PoolError {
     #[error(transparent)]
     AlloySolTypeError(#[from] alloy_sol_types::Error),
     #[error(transparent)]
+    Conversion(#[from] ConversionError),
+    #[error(transparent)]
     Eyre(#[from] eyre::Error)
 }
+
+#[cfg(test)]
+mod tests {
+    use alloy_network::Ethereum;
+    use alloy_primitives::{
+        Address, U256,
+        aliases::{I24, U24}
+    };
+    use uni_v4_structure::{
+        PoolKey, PoolMode,
+        pool_registry::L1PoolRegistry,
+        pool_updates::{ModifyLiquidityEventData, Slot0Data, SwapEventData},
+        testing::{l1_fee_config, pool_with_positions},
+        tick_info::TickInfo
+    };
+    use uniswap_v3_math::tick_bitmap::flip_tick;
+
+    use super::*;
+
+    const POOL: PoolId = PoolId::repeat_byte(1);
+    const OTHER_POOL: PoolId = PoolId::repeat_byte(2);
+
+    /// A pool with a single position over `[-600, 600]`, in unlocked mode
+    fn pool(liquidity: u128) -> BaselinePoolState<Ethereum> {
+        let mut pool = pool_with_positions(0, &[(-600, 600, liquidity)], l1_fee_config());
+        pool.set_mode(PoolMode::Unlocked).unwrap();
+        pool
+    }
+
+    fn pools() -> UniswapPools<Ethereum> {
+        UniswapPools::new(
+            Arc::new(DashMap::from_iter([(POOL, pool(1_000)), (OTHER_POOL, pool(1_000))])),
+            1
+        )
+    }
+
+    fn swap(pool_id: PoolId, block: u64, tick: i32, liquidity: u128) -> PoolUpdate<Ethereum> {
+        PoolUpdate::from_swap(
+            pool_id,
+            block,
+            0,
+            0,
+            SwapEventData {
+                sender: Address::ZERO,
+                amount0: 0,
+                amount1: 0,
+                sqrt_price_x96: SqrtPriceX96::at_tick(tick).unwrap().into(),
+                liquidity,
+                tick,
+                fee: 0
+            }
+        )
+    }
+
+    #[test]
+    fn health_follows_swaps_and_slot0_updates() {
+        let pools = pools();
+
+        let health = pools.pool_health(&POOL).unwrap();
+        assert_eq!(health.loaded_ticks, Some((-600, 600)));
+        assert_eq!(health.fee, 3500);
+        assert_eq!((health.last_update_block, health.slot0_block), (1, 1));
+        assert!(health.slot0_fresh);
+
+        let sqrt_price = SqrtPriceX96::at_tick(-60).unwrap();
+        pools.update_pools(vec![PoolUpdate::NewBlock(12), swap(POOL, 12, -60, 900)]);
+
+        let health = pools.pool_health(&POOL).unwrap();
+        assert_eq!((health.tick, health.sqrt_price, health.liquidity), (-60, sqrt_price, 900));
+        assert_eq!((health.last_update_block, health.slot0_block), (12, 12));
+        assert!(health.slot0_fresh);
+
+        // a new block without activity leaves slot0 behind until it is refreshed
+        pools.update_pools(vec![PoolUpdate::NewBlock(13)]);
+        assert!(!pools.pool_health(&POOL).unwrap().slot0_fresh);
+
+        pools.update_pools(vec![PoolUpdate::UpdatedSlot0 {
+            pool_id: POOL,
+            data:    Slot0Data {
+                sqrt_price_x96: sqrt_price.into(),
+                tick:           -60,
+                liquidity:      950
+            }
+        }]);
+        let health = pools.pool_health(&POOL).unwrap();
+        assert_eq!(health.liquidity, 950);
+        assert_eq!((health.last_update_block, health.slot0_block), (13, 13));
+        assert!(health.slot0_fresh);
+
+        assert!(pools.pool_health(&PoolId::ZERO).is_none());
+    }
+
+    #[test]
+    fn pool_is_ready_once_its_ticks_are_merged() {
+        let pools = pools();
+        assert!(!pools.pool_ready(&POOL));
+
+        pools.update_pools(vec![PoolUpdate::NewTicks {
+            pool_id:     POOL,
+            ticks:       HashMap::new(),
+            tick_bitmap: HashMap::new()
+        }]);
+
+        assert!(pools.pool_ready(&POOL));
+        assert!(!pools.pool_ready(&OTHER_POOL));
+        assert!(!pools.pool_ready(&PoolId::ZERO));
+    }
+
+    #[test]
+    fn untouched_pool_keeps_its_last_updated_block() {
+        let pools = pools();
+
+        pools.update_pools(vec![PoolUpdate::NewBlock(11), swap(POOL, 11, -60, 900)]);
+        pools.update_pools(vec![PoolUpdate::NewBlock(12), swap(OTHER_POOL, 12, 60, 900)]);
+        pools.update_pools(vec![PoolUpdate::NewBlock(13)]);
+
+        assert_eq!(pools.get_pool(&POOL).unwrap().last_updated_block(), 11);
+        assert_eq!(pools.get_pool(&OTHER_POOL).unwrap().last_updated_block(), 12);
+        assert_eq!(pools.get_block(), 13);
+    }
+
+    #[test]
+    fn tick_stats_count_loaded_ticks_and_words() {
+        let pools = pools();
+        let tick = |liquidity_net| TickInfo {
+            liquidity_net,
+            liquidity_gross: liquidity_net.unsigned_abs(),
+            initialized: true
+        };
+
+        pools.update_pools(vec![
+            PoolUpdate::NewTicks {
+                pool_id:     POOL,
+                ticks:       HashMap::from([(-1200, tick(500)), (1200, tick(-500))]),
+                tick_bitmap: HashMap::from([(-1, U256::from(1)), (0, U256::from(1))])
+            },
+            PoolUpdate::NewTicks {
+                pool_id:     OTHER_POOL,
+                ticks:       HashMap::from([(-16_000, tick(10))]),
+                tick_bitmap: HashMap::from([(-2, U256::from(1))])
+            },
+        ]);
+
+        let stats = pools.tick_memory_stats();
+        assert_eq!(stats.per_pool[&POOL], (4, 2));
+        assert_eq!(stats.per_pool[&OTHER_POOL], (3, 3));
+        assert_eq!((stats.total_ticks, stats.total_words), (7, 5));
+    }
+
+    #[test]
+    fn frozen_pools_read_but_refuse_updates() {
+        let pool = pool(1_000);
+
+        let frozen = UniswapPools::frozen(HashMap::from([(POOL, pool.clone())]), 10);
+        assert_eq!(frozen.get_block(), 10);
+        assert_eq!(frozen.len(), 1);
+        assert!(frozen.get_pool(&OTHER_POOL).is_none());
+
+        let amount = alloy_primitives::I256::unchecked_from(10);
+        let swap_result = frozen
+            .get_pool(&POOL)
+            .unwrap()
+            .swap_current_with_amount(amount, true, false)
+            .unwrap();
+        assert_eq!(
+            swap_result.total_d_t1,
+            pool.swap_current_with_amount(amount, true, false)
+                .unwrap()
+                .total_d_t1
+        );
+
+        assert!(matches!(
+            frozen.update_pools(vec![PoolUpdate::NewBlock(11), swap(POOL, 11, -60, 900)]),
+            Err(PoolError::Frozen)
+        ));
+        assert_eq!(frozen.get_pool(&POOL).unwrap().current_tick(), 0);
+    }
+
+    #[test]
+    fn best_quote_picks_the_deepest_pool() {
+        let (token0, token1) = (Address::with_last_byte(1), Address::with_last_byte(2));
+        let mut registry = L1PoolRegistry::new(Address::with_last_byte(0xaa));
+        let pool_ids = [(500, 10), (3000, 60), (10000, 200)].map(|(fee, tick_spacing)| {
+            let pool_key = PoolKey {
+                currency0:   token0,
+                currency1:   token1,
+                fee:         U24::from(fee),
+                tickSpacing: I24::unchecked_from(tick_spacing),
+                hooks:       Address::ZERO
+            };
+            registry.add_new_pool(pool_key);
+            PoolId::from(pool_key)
+        });
+
+        let pools = UniswapPools::<Ethereum>::new(
+            Arc::new(DashMap::from_iter(
+                pool_ids
+                    .into_iter()
+                    .zip([10u128.pow(18), 10u128.pow(20), 10u128.pow(19)])
+                    .map(|(pool_id, liquidity)| (pool_id, pool(liquidity)))
+            )),
+            10
+        );
+
+        let amount_in = 10u128.pow(16);
+        let (best_id, best) = pools
+            .best_quote(&registry, token0, token1, amount_in, None)
+            .unwrap();
+        assert_eq!(best_id, pool_ids[1]);
+        for pool_id in pool_ids {
+            let quote = pools
+                .get_pool(&pool_id)
+                .unwrap()
+                .quote(amount_in, true, false)
+                .unwrap();
+            assert!(best.amount_out >= quote.amount_out);
+        }
+
+        // selling token1 uses the other direction, and a hook no pool has
+        // finds nothing
+        let (best_id, _) = pools
+            .best_quote(&registry, token1, token0, amount_in, None)
+            .unwrap();
+        assert_eq!(best_id, pool_ids[1]);
+        assert!(
+            pools
+                .best_quote(&registry, token0, token1, amount_in, Some(Address::with_last_byte(3)))
+                .is_none()
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn owned_pools_simulate_across_awaits() {
+        let pools = UniswapPools::<Ethereum>::new(
+            Arc::new(DashMap::from_iter([(POOL, pool(10u128.pow(18)))])),
+            10
+        );
+
+        // the holder keeps its copy across an await until the writer, a
+        // separate task, has updated the same pool, which would deadlock if
+        // the holder kept a `Ref` into the map instead
+        let (held_tx, held_rx) = tokio::sync::oneshot::channel();
+        let (updated_tx, updated_rx) = tokio::sync::oneshot::channel();
+        let holder = tokio::spawn({
+            let pools = pools.clone();
+            async move {
+                let pool = pools.get_pool_owned(&POOL).unwrap();
+                held_tx.send(()).unwrap();
+                updated_rx.await.unwrap();
+                (pool.current_tick(), pool.quote(10u128.pow(15), true, false).unwrap())
+            }
+        });
+        let writer = tokio::spawn({
+            let pools = pools.clone();
+            async move {
+                held_rx.await.unwrap();
+                pools.update_pools(vec![swap(POOL, 11, -10, 10u128.pow(18))]);
+                updated_tx.send(()).unwrap();
+            }
+        });
+
+        let (held, written) = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            tokio::join!(holder, writer)
+        })
+        .await
+        .expect("simulation deadlocked");
+        written.unwrap();
+        let (held_tick, quote) = held.unwrap();
+
+        // the copy still simulates against the state it was taken at
+        assert_eq!(held_tick, 0);
+        assert!(quote.amount_out > 0);
+        assert_eq!(pools.get_pool(&POOL).unwrap().current_tick(), -10);
+    }
+
+    #[derive(Default)]
+    struct CountingWaker(std::sync::atomic::AtomicUsize);
+
+    impl std::task::Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn slot0_batch_wakes_waiters_once() {
+        let pools = pools();
+        let wakes = Arc::new(CountingWaker::default());
+        let waker = std::task::Waker::from(wakes.clone());
+        let mut cx = std::task::Context::from_waker(&waker);
+
+        let mut waiter = std::pin::pin!(pools.wait_for_next_slot0_update(POOL));
+        assert!(waiter.as_mut().poll(&mut cx).is_pending());
+
+        let updates = (0..120)
+            .map(|tick| PoolUpdate::UpdatedSlot0 {
+                pool_id: POOL,
+                data:    Slot0Data {
+                    sqrt_price_x96: SqrtPriceX96::at_tick(-tick).unwrap().into(),
+                    tick:           -tick,
+                    liquidity:      1_000
+                }
+            })
+            .collect();
+        pools.update_pools(updates);
+
+        // woken once, with every update already applied
+        assert_eq!(wakes.0.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert!(waiter.as_mut().poll(&mut cx).is_ready());
+        assert_eq!(pools.pool_health(&POOL).unwrap().tick, -119);
+    }
+
+    #[test]
+    fn liquidity_events_emit_bitmap_deltas() {
+        let loaded = || Arc::new(DashMap::from_iter([(POOL, pool(1_000))]));
+        let pools = UniswapPools::new(loaded(), 10).with_tick_bitmap_deltas(16);
+        let replica = UniswapPools::new(loaded(), 10);
+        let mut deltas = pools.subscribe_tick_bitmap_deltas().unwrap();
+
+        let add = |delta: i64| {
+            PoolUpdate::from_liquidity(
+                POOL,
+                11,
+                0,
+                0,
+                ModifyLiquidityEventData {
+                    sender:          Address::ZERO,
+                    tick_lower:      -120,
+                    tick_upper:      120,
+                    liquidity_delta: alloy_primitives::I256::try_from(delta).unwrap(),
+                    salt:            [0; 32]
+                }
+            )
+        };
+
+        // -120 and 120 get initialized, in words -1 and 0
+        pools.update_pools(vec![add(5)]);
+        let mut expected = pool(1_000).liquidity().tick_bitmap().clone();
+        let mut received = Vec::new();
+        for word_pos in [-1, 0] {
+            let old = expected[&word_pos];
+            flip_tick(&mut expected, if word_pos < 0 { -120 } else { 120 }, 60).unwrap();
+            let delta = deltas.try_recv().unwrap();
+            assert!(matches!(
+                delta,
+                PoolUpdate::TickBitmapDelta { pool_id: POOL, word_pos: pos, old: o, new }
+                    if pos == word_pos && o == old && new == expected[&word_pos]
+            ));
+            received.push(delta);
+        }
+
+        // adding to ticks that are already initialized flips nothing
+        pools.update_pools(vec![add(5)]);
+        assert!(deltas.try_recv().is_err());
+
+        replica.update_pools(received);
+        assert_eq!(
+            replica.get(&POOL).unwrap().liquidity().tick_bitmap(),
+            pools.get(&POOL).unwrap().liquidity().tick_bitmap()
+        );
+    }
+
+    #[test]
+    fn pools_resolve_by_symbol() {
+        let (weth, usdc) = (Address::with_last_byte(1), Address::with_last_byte(2));
+        let symbols = HashMap::from([("WETH".to_string(), weth), ("USDC".to_string(), usdc)]);
+
+        let mut registry = L1PoolRegistry::new(Address::with_last_byte(0xaa));
+        let [loaded, unloaded] = [500, 3000].map(|fee| {
+            let pool_key = PoolKey {
+                currency0:   weth,
+                currency1:   usdc,
+                fee:         U24::from(fee),
+                tickSpacing: I24::unchecked_from(60),
+                hooks:       Address::ZERO
+            };
+            registry.add_new_pool(pool_key);
+            PoolId::from(pool_key)
+        });
+        let pools = UniswapPools::<Ethereum>::new(
+            Arc::new(DashMap::from_iter([(loaded, pool(1_000))])),
+            10
+        );
+
+        for (a, b) in [("WETH", "USDC"), ("USDC", "WETH")] {
+            assert_eq!(pools.resolve_pool_by_symbols(&registry, a, b, 500, &symbols), Some(loaded));
+        }
+        // registered but not loaded, an unknown fee tier and an unknown symbol
+        assert_ne!(unloaded, loaded);
+        assert_eq!(pools.resolve_pool_by_symbols(&registry, "WETH", "USDC", 3000, &symbols), None);
+        assert_eq!(pools.resolve_pool_by_symbols(&registry, "WETH", "USDC", 100, &symbols), None);
+        assert_eq!(pools.resolve_pool_by_symbols(&registry, "WETH", "DAI", 500, &symbols), None);
+    }
+
+    #[test]
+    fn session_fees_accumulate_on_the_input_token() {
+        let pools = pools();
+        let swap = |amount0: i128, amount1: i128, fee: u32| {
+            PoolUpdate::from_swap(
+                POOL,
+                11,
+                0,
+                0,
+                SwapEventData {
+                    sender: Address::ZERO,
+                    amount0,
+                    amount1,
+                    sqrt_price_x96: SqrtPriceX96::at_tick(0).unwrap().into(),
+                    liquidity: 1_000,
+                    tick: 0,
+                    fee
+                }
+            )
+        };
+        assert_eq!(pools.session_fees(&POOL), Some((U256::ZERO, U256::ZERO)));
+
+        // the fee each event reports, 0.35% on token0 going in and then 0.35%
+        // and 0.1% on token1, whatever the pool's own fee is
+        pools.update_pools(vec![swap(-1_000_000, 990_000, 3_500)]);
+        pools.update_pools(vec![swap(1_500_000, -1_000_000, 3_500)]);
+        pools.update_pools(vec![swap(1_500_000, -3_500_000, 1_000)]);
+
+        assert_eq!(pools.session_fees(&POOL), Some((U256::from(3_500), U256::from(7_000))));
+        assert_eq!(pools.session_fees(&OTHER_POOL), Some((U256::ZERO, U256::ZERO)));
+        assert_eq!(pools.session_fees(&PoolId::repeat_byte(9)), None);
+
+        pools.reset_session_fees();
+        assert_eq!(pools.session_fees(&POOL), Some((U256::ZERO, U256::ZERO)));
+    }
+
+    #[test]
+    fn error_policy_reports_unknown_pools() {
+        let unknown = PoolId::repeat_byte(9);
+        let batch = || vec![swap(unknown, 11, 60, 1_000), swap(POOL, 11, 120, 1_000)];
+
+        assert!(pools().try_update_pools(batch()).is_ok());
+
+        let pools = pools().with_unknown_pool_policy(UnknownPoolPolicy::Error);
+        let err = pools.try_update_pools(batch()).unwrap_err();
+        assert!(matches!(err, PoolError::UnknownPool(pool_id) if pool_id == unknown));
+        // the rest of the batch still lands
+        assert_eq!(pools.pool_health(&POOL).unwrap().tick, 120);
+    }
+
+    #[test]
+    fn last_update_count_skips_markers_and_unknown_pools() {
+        let pools = pools();
+
+        pools.update_pools(vec![
+            PoolUpdate::NewBlock(11),
+            swap(POOL, 11, 60, 1_000),
+            swap(OTHER_POOL, 11, 60, 1_000),
+            swap(PoolId::repeat_byte(9), 11, 60, 1_000),
+        ]);
+        assert_eq!(pools.last_block_update_count(), 2);
+
+        pools.update_pools(vec![PoolUpdate::NewBlock(12)]);
+        assert_eq!(pools.last_block_update_count(), 0);
+    }
+}
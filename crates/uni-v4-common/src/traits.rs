@@ -1,4 +1,7 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    task::{Context, Poll}
+};
 
 use alloy_primitives::U256;
 use uni_v4_structure::{
@@ -32,8 +35,10 @@ pub trait PoolUpdateDelivery<T: V4Network>: Send + Sync {
     fn get_liquidity_event(&mut self) -> Option<(PoolId, u64, u64, u64, ModifyLiquidityEventData)>;
 
     /// Get a fee update event
-    /// Returns: (pool_id, block, bundle_fee, swap_fee, protocol_fee)
-    fn get_fee_update(&mut self) -> Option<(PoolId, u64, <T::FeeConfig as FeeConfig>::Update)>;
+    /// Returns: (pool_id, block, tx_index, log_index, fee_update)
+    fn get_fee_update(
+        &mut self
+    ) -> Option<(PoolId, u64, u64, u64, <T::FeeConfig as FeeConfig>::Update)>;
 
     /// Get a slot0 update (real-time price/liquidity/tick update)
     /// Returns: (pool_id, slot0_data)
@@ -52,53 +57,262 @@ pub trait PoolUpdateDelivery<T: V4Network>: Send + Sync {
 
     /// Get chain specific update
     fn get_chain_specific_update(&mut self) -> Option<(PoolId, T::PoolUpdate)>;
+
+    /// Resolves once the source may have new updates, used by
+    /// [`PoolUpdateDeliveryExt::next_update_async`]. A source with nothing
+    /// to deliver should register the waker and return `Pending`, waking it
+    /// when an update arrives. The default can't know when that is, so it
+    /// wakes the task straight away and the getters are asked again once the
+    /// task has yielded to the runtime.
+    fn poll_updates(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
 }
 
+/// Order [`PoolUpdateDeliveryExt::next_update_ordered`] asks a source's
+/// getters in
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct UpdateOrder {
+    round_robin: bool,
+    // getter to start from in round robin order
+    next:        usize
+}
+
+impl UpdateOrder {
+    /// The fixed order of [`PoolUpdateDeliveryExt::next_update`]: blocks,
+    /// reorgs, swaps, liquidity, fees, slot0, ticks, new pools, chain specific.
+    /// A getter that always has data starves every one after it.
+    pub fn priority() -> Self {
+        Self::default()
+    }
+
+    /// Starts each call from the getter after the one that produced the last
+    /// update, so every kind of update gets a turn. New blocks are still
+    /// asked for first, so no update comes out ahead of its block.
+    pub fn round_robin() -> Self {
+        Self { round_robin: true, next: 0 }
+    }
+}
+
+/// Number of getters on [`PoolUpdateDelivery`]
+const UPDATE_KINDS: usize = 9;
+
 /// Extension trait for PoolUpdateDelivery that provides a method to get the
 /// next available update
 pub trait PoolUpdateDeliveryExt<T: V4Network>: PoolUpdateDelivery<T> {
     /// Get the next available update of any type
     fn next_update(&mut self) -> Option<PoolUpdate<T>> {
-        // Try each update type in priority order
+        self.next_update_ordered(&mut UpdateOrder::priority())
+    }
+
+    /// Get the next available update, asking the getters in `order`
+    fn next_update_ordered(&mut self, order: &mut UpdateOrder) -> Option<PoolUpdate<T>> {
         if let Some(block) = self.get_new_block() {
             return Some(PoolUpdate::NewBlock(block));
         }
 
-        if let Some((from_block, to_block)) = self.get_reorg() {
-            return Some(PoolUpdate::Reorg { from_block, to_block });
+        // every getter after the new block one, `order.next` counts from there
+        let others = UPDATE_KINDS - 1;
+        let start = if order.round_robin { order.next } else { 0 };
+        (0..others)
+            .map(|offset| 1 + (start + offset) % others)
+            .find_map(|kind| {
+                let update = update_of_kind(self, kind)?;
+                order.next = kind % others;
+                Some(update)
+            })
+    }
+
+    /// Waits for the next update, for sources that can be awaited through
+    /// [`PoolUpdateDelivery::poll_updates`]. Cancellation safe, an update is
+    /// only taken from the source when it is returned.
+    fn next_update_async<'a>(
+        &'a mut self,
+        order: &'a mut UpdateOrder
+    ) -> impl Future<Output = PoolUpdate<T>> + Send + 'a {
+        async move {
+            loop {
+                if let Some(update) = self.next_update_ordered(order) {
+                    return update;
+                }
+
+                std::future::poll_fn(|cx| self.poll_updates(cx)).await;
+            }
+        }
+    }
+}
+
+// Blanket implementation
+impl<D: PoolUpdateDelivery<T>, T: V4Network> PoolUpdateDeliveryExt<T> for D {}
+
+/// The getter at `kind` in [`UpdateOrder::priority`] order
+fn update_of_kind<T: V4Network, D: PoolUpdateDelivery<T> + ?Sized>(
+    source: &mut D,
+    kind: usize
+) -> Option<PoolUpdate<T>> {
+    match kind {
+        0 => source.get_new_block().map(PoolUpdate::NewBlock),
+        1 => source
+            .get_reorg()
+            .map(|(from_block, to_block)| PoolUpdate::Reorg { from_block, to_block }),
+        2 => source
+            .get_swap_event()
+            .map(|(pool_id, block, tx_index, log_index, event)| {
+                PoolUpdate::from_swap(pool_id, block, tx_index, log_index, event)
+            }),
+        3 => source
+            .get_liquidity_event()
+            .map(|(pool_id, block, tx_index, log_index, event)| {
+                PoolUpdate::from_liquidity(pool_id, block, tx_index, log_index, event)
+            }),
+        4 => source
+            .get_fee_update()
+            .map(|(pool_id, block, tx_index, log_index, update)| {
+                PoolUpdate::from_fee_update(pool_id, block, tx_index, log_index, update)
+            }),
+        5 => source
+            .get_slot0_update()
+            .map(|(pool_id, data)| PoolUpdate::UpdatedSlot0 { pool_id, data }),
+        6 => source
+            .get_new_ticks()
+            .map(|(pool_id, ticks, tick_bitmap)| PoolUpdate::NewTicks {
+                pool_id,
+                ticks,
+                tick_bitmap
+            }),
+        7 => source
+            .get_new_pool_state()
+            .map(|(pool_id, state)| PoolUpdate::NewPoolState { pool_id, state }),
+        8 => source
+            .get_chain_specific_update()
+            .map(|(pool_id, update)| PoolUpdate::ChainSpecific { pool_id, update }),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_network::Ethereum;
+    use alloy_primitives::Address;
+    use uni_v4_structure::fee_config::L1FeeUpdate;
+
+    use super::*;
+
+    /// Always has another swap, and a single fee update
+    struct SwapFlood {
+        fee_update: Option<L1FeeUpdate>,
+        new_block:  Option<u64>
+    }
+
+    impl PoolUpdateDelivery<Ethereum> for SwapFlood {
+        fn get_new_block(&mut self) -> Option<u64> {
+            self.new_block.take()
+        }
+
+        fn get_reorg(&mut self) -> Option<(u64, u64)> {
+            None
         }
 
-        if let Some((pool_id, block, tx_index, log_index, event)) = self.get_swap_event() {
-            return Some(PoolUpdate::from_swap(pool_id, block, tx_index, log_index, event));
+        fn get_swap_event(&mut self) -> Option<(PoolId, u64, u64, u64, SwapEventData)> {
+            let event = SwapEventData {
+                sender:         Address::ZERO,
+                amount0:        -1,
+                amount1:        1,
+                sqrt_price_x96: Default::default(),
+                liquidity:      0,
+                tick:           0,
+                fee:            0
+            };
+            Some((PoolId::ZERO, 1, 0, 0, event))
         }
 
-        if let Some((pool_id, block, tx_index, log_index, event)) = self.get_liquidity_event() {
-            return Some(PoolUpdate::from_liquidity(pool_id, block, tx_index, log_index, event));
+        fn get_liquidity_event(
+            &mut self
+        ) -> Option<(PoolId, u64, u64, u64, ModifyLiquidityEventData)> {
+            None
         }
 
-        if let Some((pool_id, block, update)) = self.get_fee_update() {
-            return Some(PoolUpdate::from_fee_update(pool_id, block, update));
+        fn get_fee_update(&mut self) -> Option<(PoolId, u64, u64, u64, L1FeeUpdate)> {
+            self.fee_update
+                .take()
+                .map(|update| (PoolId::ZERO, 1, 0, 0, update))
         }
 
-        if let Some((pool_id, data)) = self.get_slot0_update() {
-            return Some(PoolUpdate::UpdatedSlot0 { pool_id, data });
+        fn get_slot0_update(&mut self) -> Option<(PoolId, Slot0Data)> {
+            None
         }
 
-        if let Some((pool_id, ticks, tick_bitmap)) = self.get_new_ticks() {
-            return Some(PoolUpdate::NewTicks { pool_id, ticks, tick_bitmap });
+        fn get_new_ticks(
+            &mut self
+        ) -> Option<(PoolId, HashMap<i32, TickInfo>, HashMap<i16, U256>)> {
+            None
         }
 
-        if let Some((pool_id, state)) = self.get_new_pool_state() {
-            return Some(PoolUpdate::NewPoolState { pool_id, state });
+        fn get_new_pool_state(&mut self) -> Option<(PoolId, BaselinePoolState<Ethereum>)> {
+            None
         }
 
-        if let Some((pool_id, update)) = self.get_chain_specific_update() {
-            return Some(PoolUpdate::ChainSpecific { pool_id, update });
+        fn get_chain_specific_update(
+            &mut self
+        ) -> Option<(PoolId, <Ethereum as V4Network>::PoolUpdate)> {
+            None
         }
 
-        None
+        fn poll_updates(&mut self, _: &mut Context<'_>) -> Poll<()> {
+            Poll::Ready(())
+        }
     }
-}
 
-// Blanket implementation
-impl<D: PoolUpdateDelivery<T>, T: V4Network> PoolUpdateDeliveryExt<T> for D {}
+    fn flood() -> SwapFlood {
+        SwapFlood {
+            fee_update: Some(L1FeeUpdate { bundle_fee: 1, swap_fee: 2, protocol_fee: 3 }),
+            new_block:  None
+        }
+    }
+
+    fn is_fee_update(update: &PoolUpdate<Ethereum>) -> bool {
+        matches!(update, PoolUpdate::FeeUpdate { .. })
+    }
+
+    #[test]
+    fn round_robin_reaches_fees_behind_a_swap_flood() {
+        let mut source = flood();
+        assert!(!(0..100).any(|_| is_fee_update(&source.next_update().unwrap())));
+
+        let mut source = flood();
+        let mut order = UpdateOrder::round_robin();
+        let updates = (0..4)
+            .map(|_| source.next_update_ordered(&mut order).unwrap())
+            .collect::<Vec<_>>();
+        assert!(matches!(updates[0], PoolUpdate::SwapEvent { .. }));
+        assert!(is_fee_update(&updates[1]));
+        // only swaps are left
+        assert!(!updates[2..].iter().any(is_fee_update));
+    }
+
+    #[test]
+    fn round_robin_keeps_new_blocks_first() {
+        let mut source = flood();
+        let mut order = UpdateOrder::round_robin();
+        assert!(matches!(
+            source.next_update_ordered(&mut order),
+            Some(PoolUpdate::SwapEvent { .. })
+        ));
+
+        // the fee update is next in turn, but the block its updates belong to
+        // goes out before it
+        source.new_block = Some(2);
+        assert!(matches!(source.next_update_ordered(&mut order), Some(PoolUpdate::NewBlock(2))));
+        assert!(is_fee_update(&source.next_update_ordered(&mut order).unwrap()));
+    }
+
+    #[tokio::test]
+    async fn async_updates_come_in_the_same_order() {
+        let mut source = flood();
+        let mut order = UpdateOrder::round_robin();
+
+        assert!(matches!(source.next_update_async(&mut order).await, PoolUpdate::SwapEvent { .. }));
+        assert!(is_fee_update(&source.next_update_async(&mut order).await));
+    }
+}
@@ -9,6 +9,60 @@ pub const L2_SWAP_TAXED_GAS: u128 = 120_000;
 /// is `SWAP_MEV_TAX_FACTOR / (SWAP_MEV_TAX_FACTOR + 1)`
 pub const L2_SWAP_MEV_TAX_FACTOR: u128 = 99;
 
+/// The gas and factor the L2 MEV tax is charged with. Defaults to the
+/// AngstromL2.sol constants, override it for hooks deployed with different
+/// ones.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct MevTaxParams {
+    pub taxed_gas:      u128,
+    pub mev_tax_factor: u128
+}
+
+impl Default for MevTaxParams {
+    fn default() -> Self {
+        Self { taxed_gas: L2_SWAP_TAXED_GAS, mev_tax_factor: L2_SWAP_MEV_TAX_FACTOR }
+    }
+}
+
+/// L2 MEV tax at `priority_fee_wei` using the AngstromL2.sol constants
+pub fn calculate_l2_mev_tax(priority_fee_wei: u128, priority_fee_tax_floor: u128) -> u128 {
+    calculate_l2_mev_tax_with(MevTaxParams::default(), priority_fee_wei, priority_fee_tax_floor)
+}
+
+/// L2 MEV tax at `priority_fee_wei`, zero at or below the floor and
+/// `mev_tax_factor * taxed_gas * (priority_fee - floor)` above it
+pub fn calculate_l2_mev_tax_with(
+    params: MevTaxParams,
+    priority_fee_wei: u128,
+    priority_fee_tax_floor: u128
+) -> u128 {
+    if priority_fee_wei <= priority_fee_tax_floor {
+        return 0;
+    }
+    params.mev_tax_factor * params.taxed_gas * (priority_fee_wei - priority_fee_tax_floor)
+}
+
+/// Which fee a pool is currently charging. L1 pools switch between bundle
+/// and unlocked mode, L2 pools have no bundle mode and are always unlocked.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PoolMode {
+    Bundle,
+    Unlocked
+}
+
+impl PoolMode {
+    pub fn is_bundle(&self) -> bool {
+        matches!(self, Self::Bundle)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum FeeError {
+    /// A bundle fee was asked for on a pool without bundle mode (L2)
+    #[error("pool has no bundle mode")]
+    NoBundleMode
+}
+
 /// Fee configuration for different pool modes
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct L1FeeConfiguration {
@@ -27,7 +81,9 @@ pub struct L2FeeConfiguration {
     pub protocol_swap_fee_e6:   u32,
     pub priority_fee_tax_floor: u128,
     pub jit_tax_enabled:        bool,
-    pub withdraw_only:          bool
+    pub withdraw_only:          bool,
+    #[serde(default)]
+    pub mev_tax_params:         MevTaxParams
 }
 
 pub trait FeeConfig:
@@ -61,8 +117,19 @@ pub trait FeeConfig:
     /// Returns the total fee for a swap.
     /// - L1 bundle mode: uses bundle_fee
     /// - L1 unlocked mode: swap_fee + protocol_fee
-    /// - L2: lp_fee + protocol_fee (creator + protocol swap fees)
-    fn fee(&self, bundle: bool) -> u32;
+    /// - L2: lp_fee + protocol_fee (creator + protocol swap fees), asking for
+    ///   the bundle fee is a [`FeeError::NoBundleMode`]
+    fn fee(&self, bundle: bool) -> Result<u32, FeeError>;
+
+    /// Units [`Self::fee`] is denominated in, a fee of `FEE_SCALE` is 100%.
+    /// L1 pips and L2 e6 fees are both millionths.
+    const FEE_SCALE: u32 = 1_000_000;
+
+    /// [`Self::fee`] as a fraction of the amount swapped, 0.003 for a 0.3%
+    /// fee
+    fn fee_fraction(&self, bundle: bool) -> Result<f64, FeeError> {
+        Ok(self.fee(bundle)? as f64 / Self::FEE_SCALE as f64)
+    }
 
     fn priority_fee_tax_floor(&self) -> u128 {
         0
@@ -73,9 +140,14 @@ pub trait FeeConfig:
     /// Whether this fee config uses L2-style fees (BeforeSwapDelta + MEV tax).
     fn l2_fees(&self) -> bool;
 
+    /// Whether the pool only accepts withdrawals, swaps revert while set.
+    fn withdraw_only(&self) -> bool {
+        false
+    }
+
     /// Calculate MEV tax given a priority fee in wei.
-    /// L1 returns 0. L2 implements: SWAP_MEV_TAX_FACTOR * SWAP_TAXED_GAS *
-    /// (priority_fee - floor)
+    /// L1 returns 0. L2 implements: mev_tax_factor * taxed_gas *
+    /// (priority_fee - floor) with the pool's [`MevTaxParams`]
     fn mev_tax(&self, _priority_fee_wei: u128) -> u128 {
         0
     }
@@ -100,13 +172,8 @@ impl FeeConfig for L1FeeConfiguration {
         Some(self.bundle_fee)
     }
 
-    fn fee(&self, bundle: bool) -> u32 {
-        if bundle {
-            self.bundle_fee()
-                .expect("bundle fee must have a value if bundle is set to true")
-        } else {
-            self.swap_fee() + self.protocol_fee()
-        }
+    fn fee(&self, bundle: bool) -> Result<u32, FeeError> {
+        if bundle { Ok(self.bundle_fee) } else { Ok(self.swap_fee() + self.protocol_fee()) }
     }
 
     fn update_fees(&mut self, update: Self::Update) {
@@ -131,8 +198,11 @@ impl FeeConfig for L2FeeConfiguration {
         None
     }
 
-    fn fee(&self, _: bool) -> u32 {
-        self.swap_fee() + self.protocol_fee()
+    fn fee(&self, bundle: bool) -> Result<u32, FeeError> {
+        if bundle {
+            return Err(FeeError::NoBundleMode);
+        }
+        Ok(self.swap_fee() + self.protocol_fee())
     }
 
     fn l2_fees(&self) -> bool {
@@ -143,6 +213,10 @@ impl FeeConfig for L2FeeConfiguration {
         self.priority_fee_tax_floor
     }
 
+    fn withdraw_only(&self) -> bool {
+        self.withdraw_only
+    }
+
     fn update_fees(&mut self, update: Self::Update) {
         if let Some(fee) = update.protocol_tax_fee_e6 {
             self.protocol_tax_fee_e6 = fee;
@@ -162,12 +236,11 @@ impl FeeConfig for L2FeeConfiguration {
     }
 
     fn mev_tax(&self, priority_fee_wei: u128) -> u128 {
-        if priority_fee_wei <= self.priority_fee_tax_floor {
-            return 0;
-        }
-        L2_SWAP_MEV_TAX_FACTOR
-            * L2_SWAP_TAXED_GAS
-            * (priority_fee_wei - self.priority_fee_tax_floor)
+        calculate_l2_mev_tax_with(
+            self.mev_tax_params,
+            priority_fee_wei,
+            self.priority_fee_tax_floor
+        )
     }
 }
 
@@ -178,6 +251,9 @@ pub struct L1FeeUpdate {
     pub protocol_fee: u32
 }
 
+/// Fields left `None` keep their current value. Like every
+/// [`FeeConfig::Update`] this has to stay `Copy`, queued updates are copied out
+/// from behind a reference when delivered.
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct L2FeeUpdate {
     pub protocol_tax_fee_e6:    Option<u32>,
@@ -201,10 +277,23 @@ mod tests {
             protocol_swap_fee_e6:   4000,
             priority_fee_tax_floor: floor,
             jit_tax_enabled:        false,
-            withdraw_only:          false
+            withdraw_only:          false,
+            mev_tax_params:         MevTaxParams::default()
         }
     }
 
+    #[test]
+    fn fee_fractions() {
+        let l1 = L1FeeConfiguration { bundle_fee: 3000, swap_fee: 2500, protocol_fee: 500 };
+        assert_eq!(l1.fee_fraction(true), Ok(0.003));
+        assert_eq!(l1.fee_fraction(false), Ok(0.003));
+
+        // 3000 lp + 3000 creator + 4000 protocol swap fee e6
+        let l2 = L2FeeConfiguration { lp_fee: 3000, ..l2_fee_config(0) };
+        assert_eq!(l2.fee_fraction(false), Ok(0.01));
+        assert_eq!(l2.fee_fraction(true), Err(FeeError::NoBundleMode));
+    }
+
     #[test]
     fn l1_mev_tax_always_zero() {
         let cfg = L1FeeConfiguration { bundle_fee: 100, swap_fee: 200, protocol_fee: 300 };
@@ -243,6 +332,66 @@ mod tests {
         assert_eq!(cfg.mev_tax(150), 99 * 120_000 * 50);
     }
 
+    #[test]
+    fn l2_mev_tax_uses_pool_params() {
+        let mut cfg = l2_fee_config(100);
+        cfg.mev_tax_params = MevTaxParams { taxed_gas: 200_000, mev_tax_factor: 49 };
+
+        assert_eq!(cfg.mev_tax(150), 49 * 200_000 * 50);
+        assert_eq!(cfg.mev_tax(150), calculate_l2_mev_tax_with(cfg.mev_tax_params, 150, 100));
+        assert_ne!(cfg.mev_tax(150), calculate_l2_mev_tax(150, 100));
+        assert_eq!(cfg.mev_tax(100), 0);
+    }
+
+    #[test]
+    fn l2_fee_config_without_params_deserializes_to_defaults() {
+        let mut json = serde_json::to_value(l2_fee_config(0)).unwrap();
+        json.as_object_mut().unwrap().remove("mev_tax_params");
+
+        let cfg: L2FeeConfiguration = serde_json::from_value(json).unwrap();
+        assert_eq!(cfg.mev_tax_params, MevTaxParams::default());
+    }
+
+    #[test]
+    fn l1_fee_resolves_both_modes() {
+        let cfg = L1FeeConfiguration { bundle_fee: 100, swap_fee: 200, protocol_fee: 300 };
+        assert_eq!(cfg.fee(true), Ok(100));
+        assert_eq!(cfg.fee(false), Ok(500));
+    }
+
+    #[test]
+    fn l2_fee_rejects_bundle_mode() {
+        let cfg = l2_fee_config(0);
+        assert_eq!(cfg.fee(true), Err(FeeError::NoBundleMode));
+        assert_eq!(cfg.fee(false), Ok(3000 + 4000));
+    }
+
+    #[test]
+    fn l1_pool_defaults_to_bundle_mode() {
+        let mut pool = crate::testing::l1_pool();
+        assert_eq!(pool.mode(), PoolMode::Bundle);
+        assert_eq!(pool.current_fee(), 0);
+
+        pool.set_mode(PoolMode::Unlocked).unwrap();
+        assert_eq!(pool.mode(), PoolMode::Unlocked);
+        assert_eq!(pool.current_fee(), 3000 + 500);
+    }
+
+    #[cfg(feature = "l2")]
+    #[test]
+    fn l2_pool_is_always_unlocked() {
+        let mut pool = crate::testing::pool_with_positions::<op_alloy_network::Optimism>(
+            0,
+            &[(-600, 600, 1_000_000_000_000_000_000)],
+            l2_fee_config(0)
+        );
+        assert_eq!(pool.mode(), PoolMode::Unlocked);
+        assert_eq!(pool.current_fee(), 3000 + 4000);
+
+        assert_eq!(pool.set_mode(PoolMode::Bundle), Err(FeeError::NoBundleMode));
+        assert_eq!(pool.mode(), PoolMode::Unlocked);
+    }
+
     #[test]
     fn l2_update_fees_floor_some() {
         let mut cfg = l2_fee_config(0);
@@ -286,4 +435,31 @@ mod tests {
         assert!(cfg.jit_tax_enabled);
         assert!(cfg.withdraw_only);
     }
+
+    #[cfg(feature = "l2")]
+    #[test]
+    fn l2_updates_copy_out_of_pool_updates() {
+        use crate::{PoolId, pool_updates::PoolUpdate};
+
+        let queued = PoolUpdate::<op_alloy_network::Optimism>::FeeUpdate {
+            pool_id:   PoolId::ZERO,
+            block:     1,
+            tx_index:  0,
+            log_index: 0,
+            update:    L2FeeUpdate {
+                protocol_tax_fee_e6:    None,
+                protocol_swap_fee_e6:   Some(222),
+                priority_fee_tax_floor: None,
+                jit_tax_enabled:        None,
+                withdraw_only:          None
+            }
+        };
+
+        // the same copy the update delivery makes
+        let PoolUpdate::FeeUpdate { update, .. } = &queued else { unreachable!() };
+        let mut cfg = l2_fee_config(0);
+        cfg.update_fees(*update);
+        cfg.update_fees(*update);
+        assert_eq!(cfg.protocol_swap_fee_e6, 222);
+    }
 }
@@ -1,5 +1,8 @@
-use alloy_primitives::Address;
+use alloy_primitives::{Address, address};
 
+/// Addresses the L2 code needs. The L2 path runs on op-alloy's `Optimism`
+/// network type, which covers every OP stack chain, so the chain is picked by
+/// the addresses here and the RPC the provider points at.
 #[derive(Debug, Clone, Copy)]
 pub struct L2AddressBook {
     pub angstrom_v2_factory: Address
@@ -9,4 +12,9 @@ impl L2AddressBook {
     pub fn new(angstrom_v2_factory: Address) -> Self {
         Self { angstrom_v2_factory }
     }
+
+    /// The Angstrom deployment on Base
+    pub fn base() -> Self {
+        Self::new(address!("0x0000000000a5f21b113a18dd18f6fbeebd01201b"))
+    }
 }
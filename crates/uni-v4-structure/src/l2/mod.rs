@@ -7,6 +7,7 @@ pub mod pool_registry;
 pub mod pool_updates;
 use pool_updates::L2PoolUpdate;
 
+// `Optimism` is the network type for all OP stack chains, Base included
 impl V4Network for Optimism {
     type AddressBook = L2AddressBook;
     type FeeConfig = L2FeeConfiguration;
@@ -1,15 +1,38 @@
 use std::collections::HashMap;
 
 use alloy_primitives::Address;
+#[cfg(feature = "registry-events")]
+use tokio::sync::broadcast;
 
 use crate::{
-    PoolId, PoolKey,
-    pool_registry::{PoolRegistry, UniswapPoolIdSet}
+    L2FeeConfiguration, PoolId, PoolKey, PoolKeyWithFees,
+    pool_registry::{
+        PoolRegistry, RegistryEvent, RegistryEvents, UniswapPoolIdSet, has_valid_tick_spacing
+    }
 };
 
 #[derive(Debug, Default, Clone)]
 pub struct L2PoolRegistry {
-    pools: HashMap<PoolId, PoolKey>
+    pools:           HashMap<PoolId, PoolKey>,
+    registry_events: RegistryEvents
+}
+
+impl L2PoolRegistry {
+    /// Builds a registry from a known set of pools instead of scanning the
+    /// chain for them
+    pub fn from_config(pools: Vec<PoolKeyWithFees<L2FeeConfiguration>>) -> Self {
+        let mut this = Self::default();
+        this.add_new_pools(pools.into_iter().map(|pool| pool.pool_key));
+        this
+    }
+
+    /// Enables [`RegistryEvent`]s, which are shared by all clones of this
+    /// registry
+    #[cfg(feature = "registry-events")]
+    pub fn with_registry_events(mut self, capacity: usize) -> Self {
+        self.registry_events = RegistryEvents::enabled(capacity);
+        self
+    }
 }
 
 impl PoolRegistry for L2PoolRegistry {
@@ -31,11 +54,19 @@ impl PoolRegistry for L2PoolRegistry {
     }
 
     fn add_new_pool(&mut self, pool_key: PoolKey) {
-        self.pools.insert(pool_key.into(), pool_key);
+        if !has_valid_tick_spacing(&pool_key) {
+            return;
+        }
+
+        let pool_id = PoolId::from(pool_key);
+        self.pools.insert(pool_id, pool_key);
+        self.registry_events.emit(RegistryEvent::Added(pool_id));
     }
 
     fn remove(&mut self, pool_id: &PoolId) {
-        self.pools.remove(pool_id);
+        if self.pools.remove(pool_id).is_some() {
+            self.registry_events.emit(RegistryEvent::Removed(*pool_id));
+        }
     }
 
     fn get_pools_by_token_pair(
@@ -90,8 +121,20 @@ impl PoolRegistry for L2PoolRegistry {
         self.pools.keys().cloned()
     }
 
-    fn angstrom_pool_id_from_uniswap_pool_id(&self, _pool_id: PoolId) -> Option<PoolId> {
-        None
+    /// L2 pools have no angstrom specific id, so this is the identity for
+    /// pools in the registry
+    fn angstrom_pool_id_from_uniswap_pool_id(&self, pool_id: PoolId) -> Option<PoolId> {
+        self.make_pool_id_set(pool_id)
+    }
+
+    /// See [`Self::angstrom_pool_id_from_uniswap_pool_id`]
+    fn uniswap_pool_id_from_angstrom_pool_id(&self, pool_id: PoolId) -> Option<PoolId> {
+        self.make_pool_id_set(pool_id)
+    }
+
+    #[cfg(feature = "registry-events")]
+    fn subscribe_registry_events(&self) -> Option<broadcast::Receiver<RegistryEvent>> {
+        self.registry_events.subscribe()
     }
 }
 
@@ -100,3 +143,76 @@ impl UniswapPoolIdSet for PoolId {
         *self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::{
+        Address,
+        aliases::{I24, U24}
+    };
+
+    use super::L2PoolRegistry;
+    use crate::{
+        L2FeeConfiguration, PoolId, PoolKey, PoolKeyWithFees, pool_registry::PoolRegistry,
+        testing::l2_fee_config
+    };
+
+    fn pool(fee: u32, tick_spacing: i32) -> PoolKeyWithFees<L2FeeConfiguration> {
+        PoolKeyWithFees {
+            pool_key: PoolKey {
+                currency0:   Address::ZERO,
+                currency1:   Address::with_last_byte(2),
+                fee:         U24::from(fee),
+                tickSpacing: I24::unchecked_from(tick_spacing),
+                hooks:       Address::with_last_byte(0xaa)
+            },
+            fee_cfg:  l2_fee_config(fee)
+        }
+    }
+
+    #[test]
+    fn from_config_resolves_pools() {
+        let pools = vec![pool(500, 10), pool(3000, 60)];
+        let keys = pools.iter().map(|pool| pool.pool_key).collect::<Vec<_>>();
+        let registry = L2PoolRegistry::from_config(pools);
+
+        for (key, fee) in keys.into_iter().zip([500, 3000]) {
+            assert_eq!(
+                registry.get_pool_id_by_tokens_and_fee(
+                    Address::ZERO,
+                    Address::with_last_byte(2),
+                    fee,
+                    Some(Address::with_last_byte(0xaa))
+                ),
+                Some(PoolId::from(key))
+            );
+        }
+    }
+
+    #[cfg(feature = "registry-events")]
+    #[test]
+    fn add_emits_registry_event() {
+        use crate::pool_registry::RegistryEvent;
+
+        let pool_key = pool(500, 10).pool_key;
+        let mut registry = L2PoolRegistry::default().with_registry_events(8);
+        let mut events = registry.subscribe_registry_events().unwrap();
+
+        registry.add_new_pool(pool_key);
+        assert_eq!(events.try_recv().unwrap(), RegistryEvent::Added(pool_key.into()));
+        assert!(
+            L2PoolRegistry::default()
+                .subscribe_registry_events()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn uniswap_id_from_angstrom_id_is_identity() {
+        let pool_id = PoolId::from(pool(500, 10).pool_key);
+        let registry = L2PoolRegistry::from_config(vec![pool(500, 10)]);
+
+        assert_eq!(registry.uniswap_pool_id_from_angstrom_pool_id(pool_id), Some(pool_id));
+        assert_eq!(registry.uniswap_pool_id_from_angstrom_pool_id(PoolId::ZERO), None);
+    }
+}
@@ -1,7 +1,7 @@
 use alloy_primitives::{Address, B256};
 use op_alloy_network::Optimism;
 
-use crate::{BaselinePoolState, UpdatePool};
+use crate::{BaselinePoolState, MevTaxParams, UpdatePool};
 
 #[derive(Debug, Clone)]
 pub enum L2PoolUpdate {
@@ -17,7 +17,10 @@ pub enum L2PoolUpdate {
         tick_spacing:           i32,
         hook:                   Address,
         block:                  u64,
+        tx_index:               u64,
+        log_index:              u64,
         priority_fee_tax_floor: u128,
+        mev_tax_params:         MevTaxParams,
         jit_tax_enabled:        bool,
         withdraw_only:          bool
     }
@@ -45,4 +48,16 @@ impl UpdatePool<Optimism> for L2PoolUpdate {
             L2PoolUpdate::NewPool { .. } => true
         }
     }
+
+    fn is_removal_event(&self) -> bool {
+        false
+    }
+
+    fn position(&self) -> Option<(u64, u64, u64)> {
+        match self {
+            L2PoolUpdate::NewPool { block, tx_index, log_index, .. } => {
+                Some((*block, *tx_index, *log_index))
+            }
+        }
+    }
 }
@@ -1,17 +1,36 @@
-use alloy_primitives::{Address, B256, I256};
-use liquidity_base::BaselineLiquidity;
+use alloy_primitives::{Address, B256, I256, U256};
+use liquidity_base::{BaselineLiquidity, LiquidityDivergence, LiquiditySource};
 pub use pool_key::{PoolKey, PoolKeyWithFees};
-use pool_swap::{PoolSwap, PoolSwapResult};
+use pool_swap::{
+    ArbSwapResult, PoolSwap, PoolSwapError, PoolSwapResult, Quote, SwapParams, SwapVerification
+};
 pub use pool_updates::UpdatePool;
+use pool_updates::{Slot0Update, SwapEventData};
+use ray::Ray;
 use serde::{Deserialize, Serialize};
+use spot_price::LabelledSpotPrice;
 use sqrt_pricex96::SqrtPriceX96;
+use tick_info::{MAX_TICK, MIN_TICK};
+use uniswap_v3_math::sqrt_price_math::{_get_amount_0_delta, _get_amount_1_delta};
 
 use crate::fee_config::FeeConfig;
 pub use crate::fee_config::{
-    L1FeeConfiguration, L2_SWAP_MEV_TAX_FACTOR, L2_SWAP_TAXED_GAS, L2FeeConfiguration
+    FeeError, L1FeeConfiguration, L2_SWAP_MEV_TAX_FACTOR, L2_SWAP_TAXED_GAS, L2FeeConfiguration,
+    MevTaxParams, PoolMode, calculate_l2_mev_tax, calculate_l2_mev_tax_with
 };
 pub type PoolId = B256;
 
+/// A value from chain didn't fit the type it is tracked as locally
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum ConversionError {
+    #[error("overflow from i32 to i24 {0:?}")]
+    OverflowErrorI24(i32),
+    #[error("overflow from I256 to I128 {0:?}")]
+    OverflowErrorI28(I256),
+    #[error("overflow from i128 to I256 {0:?}")]
+    OverflowErrorI256(i128)
+}
+
 #[cfg(feature = "l2")]
 mod l2;
 #[cfg(feature = "l2")]
@@ -30,7 +49,11 @@ pub use address_book::*;
 pub mod pool_registry;
 pub mod pool_swap;
 pub mod ray;
+pub mod spot_price;
 pub mod sqrt_pricex96;
+
+#[cfg(any(test, feature = "test-utils"))]
+pub mod testing;
 pub mod tick_info;
 
 //
@@ -38,7 +61,20 @@ pub mod tick_info;
 pub struct BaselinePoolState<T: V4Network> {
     liquidity:           BaselineLiquidity,
     block:               u64,
+    #[serde(default)]
+    last_updated_block:  u64,
     fee_config:          T::FeeConfig,
+    // unset until the protocol state is known, see `mode`
+    #[serde(default)]
+    mode:                Option<PoolMode>,
+    // last update from the slot0 feed applied to the pool (L1 only), boxed to
+    // keep `PoolUpdate::NewPoolState` small
+    #[serde(default)]
+    last_slot0_update:   Option<Box<Slot0Update>>,
+    // whether the initial tick band around the pool's price is in, see
+    // `ticks_loaded`
+    #[serde(default)]
+    ticks_loaded:        bool,
     pub token0:          Address,
     pub token1:          Address,
     pub token0_decimals: u8,
@@ -55,7 +91,19 @@ impl<T: V4Network> BaselinePoolState<T> {
         token0_decimals: u8,
         token1_decimals: u8
     ) -> Self {
-        Self { liquidity, block, fee_config, token1, token0, token0_decimals, token1_decimals }
+        Self {
+            liquidity,
+            block,
+            last_updated_block: block,
+            fee_config,
+            mode: None,
+            last_slot0_update: None,
+            ticks_loaded: false,
+            token1,
+            token0,
+            token0_decimals,
+            token1_decimals
+        }
     }
 
     pub fn update_slot0(
@@ -67,29 +115,119 @@ impl<T: V4Network> BaselinePoolState<T> {
         self.liquidity.start_tick = start_tick;
         self.liquidity.start_sqrt_price = start_sqrt_price;
         self.liquidity.start_liquidity = start_liquidity;
+        self.liquidity.liquidity_source = LiquiditySource::Slot0;
     }
 
     pub fn update_chain_specific(&mut self, update: T::PoolUpdate) {
         update.update_pool(self);
     }
 
-    pub fn update_liquidity(&mut self, tick_lower: i32, tick_upper: i32, liquidity_delta: I256) {
+    pub fn update_liquidity(
+        &mut self,
+        tick_lower: i32,
+        tick_upper: i32,
+        liquidity_delta: I256
+    ) -> Result<(), ConversionError> {
         self.liquidity
-            .update_liquidity_from_event(tick_lower, tick_upper, liquidity_delta);
+            .update_liquidity_from_event(tick_lower, tick_upper, liquidity_delta)
+    }
+
+    /// Compares the locally tracked active liquidity with `on_chain_liquidity`,
+    /// returning the divergence if they differ. With `correct` set the local
+    /// value is overwritten with the on-chain one.
+    pub fn reconcile_liquidity(
+        &mut self,
+        on_chain_liquidity: u128,
+        correct: bool
+    ) -> Option<LiquidityDivergence> {
+        let local = self.liquidity.start_liquidity;
+        if local == on_chain_liquidity {
+            return None;
+        }
+
+        if correct {
+            self.liquidity.start_liquidity = on_chain_liquidity;
+            self.liquidity.liquidity_source = LiquiditySource::Slot0;
+        }
+        Some(LiquidityDivergence { local, on_chain: on_chain_liquidity })
+    }
+
+    /// Frees the ticks outside `[lower, upper]`, see
+    /// [`BaselineLiquidity::evict_ticks_outside`]
+    pub fn evict_ticks_outside(&mut self, lower: i32, upper: i32) -> usize {
+        self.liquidity.evict_ticks_outside(lower, upper)
     }
 
     pub fn block_number(&self) -> u64 {
         self.block
     }
 
+    /// The latest block an update was applied to this pool at, the block it
+    /// was loaded at until then
+    pub fn last_updated_block(&self) -> u64 {
+        self.last_updated_block.max(self.block)
+    }
+
+    /// Records that an update from `block` was applied
+    pub fn mark_updated(&mut self, block: u64) {
+        self.last_updated_block = self.last_updated_block.max(block);
+    }
+
+    /// The most recent slot0 feed update applied to the pool, with the
+    /// `seq_id` and `current_block` it was sent at. Always `None` on L2, which
+    /// has no slot0 feed.
+    pub fn last_slot0_update(&self) -> Option<&Slot0Update> {
+        self.last_slot0_update.as_deref()
+    }
+
+    /// Whether the ticks around the pool's price have been loaded, either
+    /// along with the pool or by a later `NewTicks` update. Until then quotes
+    /// can stop short at the edge of the few ticks the pool has.
+    pub fn ticks_loaded(&self) -> bool {
+        self.ticks_loaded
+    }
+
+    pub fn mark_ticks_loaded(&mut self) {
+        self.ticks_loaded = true;
+    }
+
     pub fn fees_mut(&mut self) -> &mut T::FeeConfig {
         &mut self.fee_config
     }
 
-    pub fn fee(&self, bundle: bool) -> u32 {
+    pub fn fee(&self, bundle: bool) -> Result<u32, FeeError> {
         self.fee_config.fee(bundle)
     }
 
+    /// The mode the pool is in. Defaults to bundle mode for pools that have a
+    /// bundle fee (L1) and unlocked mode otherwise (L2).
+    pub fn mode(&self) -> PoolMode {
+        self.mode
+            .unwrap_or(if self.fee_config.bundle_fee().is_some() {
+                PoolMode::Bundle
+            } else {
+                PoolMode::Unlocked
+            })
+    }
+
+    /// Records the mode the pool is in on-chain. Fails for bundle mode on pools
+    /// without a bundle fee.
+    pub fn set_mode(&mut self, mode: PoolMode) -> Result<(), FeeError> {
+        if mode.is_bundle() && self.fee_config.bundle_fee().is_none() {
+            return Err(FeeError::NoBundleMode);
+        }
+        self.mode = Some(mode);
+        Ok(())
+    }
+
+    /// The fee charged in the pool's current [`PoolMode`]
+    pub fn current_fee(&self) -> u32 {
+        match (self.mode(), self.fee_config.bundle_fee()) {
+            (PoolMode::Bundle, Some(fee)) => fee,
+            _ => self.swap_fee() + self.protocol_fee()
+        }
+    }
+
     pub fn bundle_fee(&self) -> Option<u32> {
         self.fee_config.bundle_fee()
     }
@@ -114,6 +252,56 @@ impl<T: V4Network> BaselinePoolState<T> {
         self.liquidity.start_liquidity
     }
 
+    /// Whether [`Self::current_liquidity`] is the last value read from chain or
+    /// has since been adjusted by liquidity events. Whichever came last is the
+    /// authoritative one.
+    pub fn liquidity_source(&self) -> LiquiditySource {
+        self.liquidity.liquidity_source
+    }
+
+    /// The active liquidity range around the current tick as
+    /// `(lower tick, upper tick, liquidity)`: the liquidity a swap trades
+    /// against before it crosses an initialized tick in either direction
+    pub fn active_range_liquidity(&self) -> (i32, i32, u128) {
+        let (lower, upper) = self.liquidity.active_range();
+        (lower, upper, self.current_liquidity())
+    }
+
+    /// Estimates the `(token0, token1)` amounts backing the active liquidity
+    /// range, i.e. what the current liquidity holds between the range bounds
+    /// at the current price. Liquidity outside the active range is ignored,
+    /// so this is only an approximation of the pool's value locked.
+    pub fn locked_amounts(&self) -> eyre::Result<(U256, U256)> {
+        let (lower, upper, liquidity) = self.active_range_liquidity();
+        let price: U256 = self.current_price().into();
+        let lower_price: U256 = SqrtPriceX96::at_tick(lower)?.into();
+        let upper_price: U256 = SqrtPriceX96::at_tick(upper)?.into();
+
+        let amount0 = _get_amount_0_delta(price, upper_price, liquidity, false)?;
+        let amount1 = _get_amount_1_delta(lower_price, price, liquidity, false)?;
+        Ok((amount0, amount1))
+    }
+
+    /// The input amount, LP fee included, that moves the price exactly onto
+    /// the next initialized tick in `direction` without crossing it, i.e. the
+    /// most that trades against the current constant liquidity. Fees taken
+    /// by the hook outside the AMM (L2 protocol fee and MEV tax) come on top.
+    pub fn max_amount_before_tick_cross(
+        &self,
+        direction: bool,
+        is_bundle: bool
+    ) -> eyre::Result<u128> {
+        let (lower, upper, _) = self.active_range_liquidity();
+        let next_tick = if direction { lower } else { upper };
+        let swap = self.swap_current_to_price_raw(SqrtPriceX96::at_tick(next_tick)?, is_bundle)?;
+
+        Ok(swap
+            .steps
+            .iter()
+            .map(|step| step.amount_in + step.fee_amount)
+            .sum())
+    }
+
     pub fn current_price(&self) -> SqrtPriceX96 {
         self.liquidity.start_sqrt_price
     }
@@ -122,31 +310,66 @@ impl<T: V4Network> BaselinePoolState<T> {
         self.liquidity.tick_spacing
     }
 
+    /// Whether swaps can currently be simulated on the pool. Every swap entry
+    /// point checks this first and fails with the same error.
+    pub fn can_swap(&self) -> Result<(), PoolSwapError> {
+        if self.fee_config.withdraw_only() {
+            return Err(PoolSwapError::WithdrawOnly);
+        }
+        Ok(())
+    }
+
     pub fn noop(&self) -> PoolSwapResult<'_, T> {
         PoolSwapResult {
-            fee_config:    self.fee_config,
-            start_price:   self.liquidity.start_sqrt_price,
-            start_tick:    self.liquidity.start_tick,
-            end_price:     self.liquidity.start_sqrt_price,
-            end_tick:      self.liquidity.start_tick,
-            total_d_t0:    0,
-            total_d_t1:    0,
-            steps:         vec![],
+            fee_config: self.fee_config,
+            start_price: self.liquidity.start_sqrt_price,
+            start_tick: self.liquidity.start_tick,
+            end_price: self.liquidity.start_sqrt_price,
+            end_tick: self.liquidity.start_tick,
+            total_d_t0: 0,
+            total_d_t1: 0,
+            steps: vec![],
             end_liquidity: self.liquidity.current(),
-            is_bundle:     true
+            is_bundle: true,
+            mev_tax_charged: None,
+            amount_filled_before_limit: 0
         }
     }
 
+    /// Runs a swap against the pool's current state, all the `swap_current_*`
+    /// methods are shorthands for this. Built with [`PoolSwap::builder`], so
+    /// it fails on the same combinations: no amount or price limit, an amount
+    /// without a direction, a limit behind the current price or a priority
+    /// fee above the tax floor on a swap that never pays MEV tax.
+    pub fn swap(&self, params: SwapParams) -> eyre::Result<PoolSwapResult<'_, T>> {
+        self.can_swap()?;
+        params
+            .builder(self.liquidity.current(), self.fee_config)
+            .swap()
+    }
+
     pub fn swap_current_with_amount(
         &self,
         amount: I256,
         direction: bool,
         is_bundle: bool
     ) -> eyre::Result<PoolSwapResult<'_, T>> {
-        let liq = self.liquidity.current();
+        self.swap(SwapParams::amount(amount, direction).bundle(is_bundle))
+    }
 
+    /// Swaps against `liquidity_snapshot` instead of the pool's own liquidity,
+    /// with the pool's fees, for trying out hypothetical liquidity without
+    /// touching the pool. The result borrows from the snapshot.
+    pub fn swap_with_liquidity_override<'a>(
+        &self,
+        liquidity_snapshot: &'a BaselineLiquidity,
+        amount: I256,
+        direction: bool,
+        is_bundle: bool
+    ) -> eyre::Result<PoolSwapResult<'a, T>> {
+        self.can_swap()?;
         PoolSwap {
-            liquidity: liq,
+            liquidity: liquidity_snapshot.current(),
             target_amount: amount,
             target_price: None,
             direction,
@@ -159,7 +382,8 @@ impl<T: V4Network> BaselinePoolState<T> {
 
     /// L2 swap with MEV tax applied to token0 (ETH) delta.
     /// Pass the priority fee (tx.gasprice - block.basefee) in wei to calculate
-    /// the MEV tax.
+    /// the MEV tax. Only the part above the pool's priority fee tax floor is
+    /// taxed, a fee below the floor is treated as sitting at it.
     pub fn swap_current_with_amount_and_mev_tax(
         &self,
         amount: I256,
@@ -167,21 +391,11 @@ impl<T: V4Network> BaselinePoolState<T> {
         is_bundle: bool,
         priority_fee_wei: Option<u128>
     ) -> eyre::Result<PoolSwapResult<'_, T>> {
-        let liq = self.liquidity.current();
-        let mev_tax_amount = priority_fee_wei
-            .map(|fee| self.fee_config.mev_tax(fee))
-            .filter(|&tax| tax > 0);
-
-        PoolSwap {
-            liquidity: liq,
-            target_amount: amount,
-            target_price: None,
-            direction,
-            fee_config: self.fee_config,
-            is_bundle,
-            mev_tax_amount
-        }
-        .swap()
+        self.swap(
+            SwapParams::amount(amount, direction)
+                .bundle(is_bundle)
+                .with_priority_fee(priority_fee_wei)
+        )
     }
 
     pub fn swap_current_with_amount_and_limit(
@@ -191,18 +405,29 @@ impl<T: V4Network> BaselinePoolState<T> {
         is_bundle: bool,
         limit_price: SqrtPriceX96
     ) -> eyre::Result<PoolSwapResult<'_, T>> {
-        let liq = self.liquidity.current();
+        self.swap(
+            SwapParams::amount(amount, direction)
+                .with_limit(limit_price)
+                .bundle(is_bundle)
+        )
+    }
 
-        PoolSwap {
-            liquidity: liq,
-            target_amount: amount,
-            target_price: Some(limit_price),
-            direction,
-            fee_config: self.fee_config,
-            is_bundle,
-            mev_tax_amount: None
+    /// [`Self::swap_current_with_amount_and_limit`] with the limit given as a
+    /// tick. The tick has to be inside the valid tick range and at or past the
+    /// current price in the swap direction.
+    pub fn swap_current_with_amount_and_tick_limit(
+        &self,
+        amount: I256,
+        direction: bool,
+        is_bundle: bool,
+        tick_limit: i32
+    ) -> eyre::Result<PoolSwapResult<'_, T>> {
+        if tick_limit <= MIN_TICK || tick_limit >= MAX_TICK {
+            return Err(PoolSwapError::TickLimitOutOfRange.into());
         }
-        .swap()
+
+        let limit_price = SqrtPriceX96::at_tick(tick_limit)?;
+        self.swap_current_with_amount_and_limit(amount, direction, is_bundle, limit_price)
     }
 
     /// L2 swap with price limit and MEV tax applied to token0 (ETH) delta.
@@ -216,21 +441,81 @@ impl<T: V4Network> BaselinePoolState<T> {
         limit_price: SqrtPriceX96,
         priority_fee_wei: Option<u128>
     ) -> eyre::Result<PoolSwapResult<'_, T>> {
-        let liq = self.liquidity.current();
-        let mev_tax_amount = priority_fee_wei
-            .map(|fee| self.fee_config.mev_tax(fee))
-            .filter(|&tax| tax > 0);
+        self.swap(
+            SwapParams::amount(amount, direction)
+                .with_limit(limit_price)
+                .bundle(is_bundle)
+                .with_priority_fee(priority_fee_wei)
+        )
+    }
 
-        PoolSwap {
-            liquidity: liq,
-            target_amount: amount,
-            target_price: Some(limit_price),
-            direction,
-            fee_config: self.fee_config,
-            is_bundle,
-            mev_tax_amount
+    /// Exact output swap on an L2 pool that still pays the MEV tax at
+    /// `priority_fee_wei`. The hook only taxes exact input swaps, so this
+    /// searches for the smallest exact input whose output, after the tax is
+    /// taken from the ETH side, is at least `amount_out`. With no tax to pay
+    /// (bundle mode, L1 pools or a fee at or below the floor) it is a plain
+    /// exact output swap.
+    pub fn swap_for_exact_output_with_mev_tax(
+        &self,
+        amount_out: u128,
+        direction: bool,
+        is_bundle: bool,
+        priority_fee_wei: u128
+    ) -> eyre::Result<PoolSwapResult<'_, T>> {
+        let exact_out =
+            SwapParams::amount(-I256::unchecked_from(amount_out), direction).bundle(is_bundle);
+        let tax = self.fee_config.mev_tax(priority_fee_wei);
+        if tax == 0 || is_bundle || !self.fee_config.l2_fees() {
+            return self.swap(exact_out);
         }
-        .swap()
+
+        let taxed_swap = |amount_in: u128| {
+            self.swap(
+                SwapParams::amount(I256::unchecked_from(amount_in), direction)
+                    .with_priority_fee(Some(priority_fee_wei))
+            )
+        };
+        let net_out = |swap: &PoolSwapResult<'_, T>| {
+            if direction { swap.total_d_t1 } else { swap.total_d_t0 }
+        };
+
+        // the untaxed exact output swap, plus the tax on whichever side ETH is,
+        // is where the search for an input that covers the target starts
+        let (untaxed_out, extra_in) =
+            if direction { (amount_out, tax) } else { (amount_out.saturating_add(tax), 0) };
+        let untaxed = self.swap(
+            SwapParams::amount(-I256::unchecked_from(untaxed_out), direction).bundle(is_bundle)
+        )?;
+        let untaxed_in = if direction { untaxed.total_d_t0 } else { untaxed.total_d_t1 };
+
+        // ETH input at or below the tax never reaches the AMM
+        let mut low = extra_in;
+        let mut high = untaxed_in.saturating_add(extra_in).max(low + 1);
+        loop {
+            let swap = taxed_swap(high)?;
+            if net_out(&swap) >= amount_out {
+                break;
+            }
+            if swap.amount_filled_before_limit < high {
+                return Err(PoolSwapError::InsufficientLiquidity.into());
+            }
+            low = high;
+            high = high
+                .checked_mul(2)
+                .ok_or(PoolSwapError::InsufficientLiquidity)?;
+        }
+
+        // output grows with input, so bisect down to the smallest input that
+        // still covers the target
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            if net_out(&taxed_swap(mid)?) >= amount_out {
+                high = mid;
+            } else {
+                low = mid;
+            }
+        }
+        taxed_swap(high)
     }
 
     /// Swap to current price is designed to represent all swap outcomes as an
@@ -244,6 +529,144 @@ impl<T: V4Network> BaselinePoolState<T> {
         self.swap_current_to_price_with_mev_tax(price_limit, is_bundle, None)
     }
 
+    /// Same as [`Self::swap_current_to_price`] but takes a decimal adjusted
+    /// spot price (token1 per token0) instead of a SqrtPriceX96
+    pub fn swap_current_to_spot_price(
+        &self,
+        spot_price: Ray,
+        is_bundle: bool
+    ) -> eyre::Result<PoolSwapResult<'_, T>> {
+        self.swap_current_to_price(self.sqrt_price_from_spot(spot_price)?, is_bundle)
+    }
+
+    /// Swaps the pool to an external decimal adjusted spot price (token1 per
+    /// token0), buying T0 if the pool is cheaper and selling it if the pool is
+    /// more expensive. The returned profit is what the swap would net, in T1,
+    /// if its output was offloaded at `external_spot`.
+    pub fn arb_to_price(
+        &self,
+        external_spot: Ray,
+        is_bundle: bool
+    ) -> eyre::Result<ArbSwapResult<'_, T>> {
+        let price_limit = self.sqrt_price_from_spot(external_spot)?;
+        let swap = self.swap_current_to_price(price_limit, is_bundle)?;
+
+        let external_price = Ray::from(price_limit);
+        let (t1_in, t1_out) = if swap.zero_for_one() {
+            (external_price.quantity(swap.total_d_t0, true), swap.total_d_t1)
+        } else {
+            (swap.total_d_t1, external_price.quantity(swap.total_d_t0, false))
+        };
+        let profit = I256::unchecked_from(t1_out) - I256::unchecked_from(t1_in);
+
+        Ok(ArbSwapResult { swap, profit })
+    }
+
+    /// Converts a decimal adjusted spot price (token1 per token0) into the raw
+    /// SqrtPriceX96 of this pool using the stored token decimals
+    pub fn sqrt_price_from_spot(&self, spot_price: Ray) -> eyre::Result<SqrtPriceX96> {
+        let raw_price =
+            spot_price.mul_wad(10u128.pow(self.token1_decimals as u32), self.token0_decimals);
+        if !raw_price.within_sqrt_price_bounds() {
+            return Err(eyre::eyre!(
+                "spot price {spot_price:?} is outside of the sqrt price bounds"
+            ));
+        }
+
+        Ok(SqrtPriceX96::from(raw_price))
+    }
+
+    /// Replays a swap event on the current state and compares where the pool
+    /// ends up with the slot0 the event reports.
+    ///
+    /// The swap is replayed as exact output of what the swapper received, since
+    /// the price a swap moves to depends only on the amount taken out of the
+    /// pool and not on the fees charged on the way in.
+    pub fn verify_swap_event(&self, event: &SwapEventData) -> eyre::Result<SwapVerification> {
+        let reported_price = SqrtPriceX96::from(event.sqrt_price_x96);
+        // amounts are from the swapper's side, the token paid in is negative
+        let zero_for_one = event.amount0 < 0;
+        let amount_out = if zero_for_one { event.amount1 } else { event.amount0 };
+
+        let (simulated_price, simulated_tick) = if amount_out <= 0 {
+            (self.current_price(), self.current_tick())
+        } else {
+            let swap =
+                self.swap_current_with_amount(-I256::try_from(amount_out)?, zero_for_one, true)?;
+            (swap.end_price, swap.end_tick)
+        };
+
+        Ok(SwapVerification {
+            simulated_price,
+            simulated_tick,
+            reported_price,
+            reported_tick: event.tick
+        })
+    }
+
+    /// Decimal adjusted spot price (token1 per token0) at the current sqrt
+    /// price
+    pub fn spot_price(&self) -> Ray {
+        self.decimal_adjusted(Ray::from(self.current_price()))
+    }
+
+    /// Quotes an exact input swap of `amount_in`, bundling the amounts, the
+    /// decimal adjusted execution price, the price impact, the LP fee and
+    /// where the pool ends up.
+    pub fn quote(&self, amount_in: u128, direction: bool, is_bundle: bool) -> eyre::Result<Quote> {
+        let swap =
+            self.swap_current_with_amount(I256::try_from(amount_in)?, direction, is_bundle)?;
+
+        let effective_price = if swap.was_empty_swap() {
+            self.spot_price()
+        } else {
+            self.decimal_adjusted(Ray::calc_price(
+                U256::from(swap.total_d_t0),
+                U256::from(swap.total_d_t1)
+            ))
+        };
+        let start_price = Ray::from(swap.start_price).as_f64();
+        let end_price = Ray::from(swap.end_price).as_f64();
+
+        Ok(Quote {
+            amount_in: swap.input(),
+            amount_out: swap.output(),
+            effective_price,
+            price_impact_bps: (end_price - start_price).abs() / start_price * 10_000.0,
+            fee_paid: swap.steps.iter().map(|step| step.fee_amount).sum(),
+            end_tick: swap.end_tick,
+            end_price: swap.end_price
+        })
+    }
+
+    /// Quotes the same exact input swap in bundle and in unlocked mode, as
+    /// `(bundle, unlocked)`. The bundle quote is `None` for pools without a
+    /// bundle mode, which is every L2 pool. It is fee-less whatever the
+    /// pool's [`Self::bundle_fee`]: the pool charges nothing on bundle swaps
+    /// and the bundle fee is settled by the bundle, outside this quote.
+    pub fn quote_both_modes(
+        &self,
+        amount_in: u128,
+        direction: bool
+    ) -> eyre::Result<(Option<Quote>, Quote)> {
+        let bundle = self
+            .bundle_fee()
+            .map(|_| self.quote(amount_in, direction, true))
+            .transpose()?;
+        Ok((bundle, self.quote(amount_in, direction, false)?))
+    }
+
+    fn decimal_adjusted(&self, raw_price: Ray) -> Ray {
+        raw_price.mul_wad(10u128.pow(self.token0_decimals as u32), self.token1_decimals)
+    }
+
+    /// The current spot price with both tokens labelled. Pass the wrapped
+    /// native token (e.g. WETH) to have it reported as the native token and
+    /// used as the base of the price
+    pub fn labelled_spot_price(&self, native_token: Option<Address>) -> LabelledSpotPrice {
+        LabelledSpotPrice::new(self.token0, self.token1, self.spot_price(), native_token)
+    }
+
     /// L2 swap to price with MEV tax applied to token0 (ETH) delta.
     /// Pass the priority fee (tx.gasprice - block.basefee) in wei to calculate
     /// the MEV tax.
@@ -253,38 +676,25 @@ impl<T: V4Network> BaselinePoolState<T> {
         is_bundle: bool,
         priority_fee_wei: Option<u128>
     ) -> eyre::Result<PoolSwapResult<'_, T>> {
-        let liq = self.liquidity.current();
-        let direction = liq.current_sqrt_price >= price_limit;
-        let mev_tax_amount = priority_fee_wei
-            .map(|fee| self.fee_config.mev_tax(fee))
-            .filter(|&tax| tax > 0);
-
-        let price_swap: PoolSwapResult<'_, T> = PoolSwap {
-            liquidity: liq,
-            target_amount: I256::MAX,
-            target_price: Some(price_limit),
-            direction,
-            fee_config: self.fee_config,
-            is_bundle,
-            mev_tax_amount: None // Don't apply MEV tax to price discovery swap
-        }
-        .swap()?;
+        let direction = self.liquidity.current().current_sqrt_price >= price_limit;
+        // Don't apply MEV tax to price discovery swap
+        let price_swap = self.swap(SwapParams {
+            direction: Some(direction),
+            ..SwapParams::to_price(price_limit).bundle(is_bundle)
+        })?;
 
         let amount_in = if direction { price_swap.total_d_t0 } else { price_swap.total_d_t1 };
         let amount = I256::unchecked_from(amount_in);
 
-        let liq = self.liquidity.current();
-
-        PoolSwap {
-            liquidity: liq,
-            target_amount: amount,
-            target_price: None,
-            direction,
-            fee_config: self.fee_config,
-            is_bundle,
-            mev_tax_amount
-        }
-        .swap()
+        // the rounded up amount in can carry the price a few wei past the limit
+        // on deep pools, so keep the limit on the amount in swap as well. Any
+        // of the amount left over at the limit is simply not used.
+        self.swap(
+            SwapParams::amount(amount, direction)
+                .with_limit(price_limit)
+                .bundle(is_bundle)
+                .with_priority_fee(priority_fee_wei)
+        )
     }
 
     /// Angstrom operates everything on amount in, If we don't need this
@@ -294,20 +704,7 @@ impl<T: V4Network> BaselinePoolState<T> {
         price_limit: SqrtPriceX96,
         is_bundle: bool
     ) -> eyre::Result<PoolSwapResult<'_, T>> {
-        let liq = self.liquidity.current();
-
-        let direction = liq.current_sqrt_price >= price_limit;
-
-        PoolSwap {
-            liquidity: liq,
-            target_amount: I256::MAX,
-            target_price: Some(price_limit),
-            direction,
-            fee_config: self.fee_config,
-            is_bundle,
-            mev_tax_amount: None
-        }
-        .swap()
+        self.swap(SwapParams::to_price(price_limit).bundle(is_bundle))
     }
 
     /// L2 raw swap to price with MEV tax applied to token0 (ETH) delta.
@@ -319,23 +716,11 @@ impl<T: V4Network> BaselinePoolState<T> {
         is_bundle: bool,
         priority_fee_wei: Option<u128>
     ) -> eyre::Result<PoolSwapResult<'_, T>> {
-        let liq = self.liquidity.current();
-        let mev_tax_amount = priority_fee_wei
-            .map(|fee| self.fee_config.mev_tax(fee))
-            .filter(|&tax| tax > 0);
-
-        let direction = liq.current_sqrt_price >= price_limit;
-
-        PoolSwap {
-            liquidity: liq,
-            target_amount: I256::MAX,
-            target_price: Some(price_limit),
-            direction,
-            fee_config: self.fee_config,
-            is_bundle,
-            mev_tax_amount
-        }
-        .swap()
+        self.swap(
+            SwapParams::to_price(price_limit)
+                .bundle(is_bundle)
+                .with_priority_fee(priority_fee_wei)
+        )
     }
 
     pub fn liquidity(&self) -> &BaselineLiquidity {
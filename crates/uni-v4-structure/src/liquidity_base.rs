@@ -13,7 +13,7 @@ use uniswap_v3_math::{
 };
 
 use super::tick_info::TickInfo;
-use crate::sqrt_pricex96::SqrtPriceX96;
+use crate::{ConversionError, sqrt_pricex96::SqrtPriceX96};
 
 /// baseline holder for
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -22,6 +22,8 @@ pub struct BaselineLiquidity {
     pub(super) start_tick:       i32,
     pub(super) start_sqrt_price: SqrtPriceX96,
     pub(super) start_liquidity:  u128,
+    #[serde(default)]
+    pub(super) liquidity_source: LiquiditySource,
     /// should only have ticks that are initialized.
     initialized_ticks:           HashMap<i32, TickInfo>,
     /// should only have ticks that are initialized, i.e have liquidity
@@ -41,18 +43,21 @@ impl BaselineLiquidity {
             start_tick,
             start_sqrt_price,
             start_liquidity,
+            liquidity_source: LiquiditySource::Slot0,
             initialized_ticks,
             tick_bitmap,
             tick_spacing
         }
     }
 
+    /// Applies a `ModifyLiquidity` delta. Deltas that don't fit an i128 are
+    /// rejected before anything is touched.
     pub fn update_liquidity_from_event(
         &mut self,
         tick_lower: i32,
         tick_upper: i32,
         liquidity_delta: I256
-    ) {
+    ) -> Result<(), ConversionError> {
         let min_tick_init = self
             .initialized_ticks
             .keys()
@@ -67,20 +72,16 @@ impl BaselineLiquidity {
             .copied()
             .expect("No max-initialized tick. This will break the update event algo");
 
-        // should never overflow
-        let liquidity_delta = i128::try_from(liquidity_delta).unwrap();
+        let liquidity_delta = i128::try_from(liquidity_delta)
+            .map_err(|_| ConversionError::OverflowErrorI28(liquidity_delta))?;
 
         // Case where we surround the current position (greater than the position on
         // both sides);
         if tick_lower < min_tick_init && tick_upper > max_tick_init {
             // we don't flip any ticks here as there outside of our loaded band
-            if liquidity_delta.is_negative() {
-                self.start_liquidity -= liquidity_delta.unsigned_abs();
-            } else {
-                self.start_liquidity += liquidity_delta as u128;
-            }
+            self.apply_active_delta(liquidity_delta);
 
-            return;
+            return Ok(());
         }
 
         // Case were we are fully inside the range
@@ -110,21 +111,17 @@ impl BaselineLiquidity {
             }
 
             if self.start_tick > tick_lower && self.start_tick < tick_upper {
-                if liquidity_delta.is_negative() {
-                    self.start_liquidity -= liquidity_delta.unsigned_abs();
-                } else {
-                    self.start_liquidity += liquidity_delta as u128;
-                }
+                self.apply_active_delta(liquidity_delta);
             }
 
-            return;
+            return Ok(());
         }
 
         // lower tick is in range, upper out of range
         if tick_lower >= min_tick_init && tick_upper > max_tick_init {
             // we are fully out of range here.
             if tick_lower > max_tick_init {
-                return;
+                return Ok(());
             }
 
             // update the tick
@@ -148,20 +145,16 @@ impl BaselineLiquidity {
 
             // if we are less than start tick, means that we effect slot0 and need to add.
             if tick_lower <= self.start_tick {
-                if liquidity_delta.is_negative() {
-                    self.start_liquidity -= liquidity_delta.unsigned_abs();
-                } else {
-                    self.start_liquidity += liquidity_delta as u128;
-                }
+                self.apply_active_delta(liquidity_delta);
             }
-            return;
+            return Ok(());
         }
 
         // upper tick in range,
         if tick_lower < min_tick_init && tick_upper <= max_tick_init {
             // we are fully out of range here.
             if tick_upper < min_tick_init {
-                return;
+                return Ok(());
             }
 
             // update the tick
@@ -186,13 +179,21 @@ impl BaselineLiquidity {
             // if our upper tick is ge the start tick, and lower is out of range, means that
             // the current liq needs to be updated.
             if tick_upper >= self.start_tick {
-                if liquidity_delta.is_negative() {
-                    self.start_liquidity -= liquidity_delta.unsigned_abs();
-                } else {
-                    self.start_liquidity += liquidity_delta as u128;
-                }
+                self.apply_active_delta(liquidity_delta);
             }
         }
+
+        Ok(())
+    }
+
+    /// Adds a delta from a liquidity event covering the current tick
+    fn apply_active_delta(&mut self, liquidity_delta: i128) {
+        if liquidity_delta.is_negative() {
+            self.start_liquidity -= liquidity_delta.unsigned_abs();
+        } else {
+            self.start_liquidity += liquidity_delta as u128;
+        }
+        self.liquidity_source = LiquiditySource::TickDerived;
     }
 
     /// returns a liquidity ref were the current liquidity is properly
@@ -290,6 +291,21 @@ impl BaselineLiquidity {
         }
     }
 
+    /// Get the initialized ticks bounding the current tick, `[lower, upper)`.
+    /// Falls back to `MIN_TICK`/`MAX_TICK` when nothing is loaded on a side.
+    pub fn active_range(&self) -> (i32, i32) {
+        self.initialized_ticks
+            .iter()
+            .filter(|(_, info)| info.liquidity_gross > 0)
+            .fold((MIN_TICK, MAX_TICK), |(lower, upper), (&tick, _)| {
+                if tick <= self.start_tick {
+                    (lower.max(tick), upper)
+                } else {
+                    (lower, upper.min(tick))
+                }
+            })
+    }
+
     /// Get mutable reference to initialized ticks
     pub fn initialized_ticks_mut(&mut self) -> &mut HashMap<i32, TickInfo> {
         &mut self.initialized_ticks
@@ -305,11 +321,67 @@ impl BaselineLiquidity {
         &self.tick_bitmap
     }
 
+    /// The tick bitmap word holding `tick`
+    pub fn tick_bitmap_word_pos(&self, tick: i32) -> i16 {
+        (tick.div_euclid(self.tick_spacing) >> 8) as i16
+    }
+
+    /// The tick bitmap word at `word_pos`, zero when it isn't loaded
+    pub fn tick_bitmap_word(&self, word_pos: i16) -> U256 {
+        self.tick_bitmap.get(&word_pos).copied().unwrap_or_default()
+    }
+
     /// Update tick bitmap
     pub fn update_tick_bitmap(&mut self, word_pos: i16, word: U256) {
         self.tick_bitmap.insert(word_pos, word);
     }
 
+    /// Drops every initialized tick outside `[lower, upper]` along with its
+    /// bitmap bit, returning how many were removed. The initialized ticks
+    /// mark the loaded range, so swaps stop at the outermost tick kept and
+    /// the evicted ranges have to be loaded again before they can be crossed.
+    /// The nearest initialized tick on each side of the current tick is always
+    /// kept, so the current price never ends up outside the loaded range.
+    pub fn evict_ticks_outside(&mut self, lower: i32, upper: i32) -> usize {
+        let below = self
+            .initialized_ticks
+            .keys()
+            .filter(|tick| **tick <= self.start_tick)
+            .max()
+            .copied();
+        let above = self
+            .initialized_ticks
+            .keys()
+            .filter(|tick| **tick > self.start_tick)
+            .min()
+            .copied();
+        let lower = below.map_or(lower, |tick| tick.min(lower));
+        let upper = above.map_or(upper, |tick| tick.max(upper));
+
+        let evicted = self
+            .initialized_ticks
+            .keys()
+            .filter(|tick| !(lower..=upper).contains(*tick))
+            .copied()
+            .collect::<Vec<_>>();
+
+        for tick in &evicted {
+            self.initialized_ticks.remove(tick);
+            // cleared rather than flipped, a loaded tick without liquidity never
+            // had its bit set
+            if tick % self.tick_spacing != 0 {
+                continue;
+            }
+            let compressed = tick / self.tick_spacing;
+            if let Some(word) = self.tick_bitmap.get_mut(&((compressed >> 8) as i16)) {
+                *word &= !(U256::from(1) << compressed.rem_euclid(256));
+            }
+        }
+        self.tick_bitmap.retain(|_, word| !word.is_zero());
+
+        evicted.len()
+    }
+
     /// Get the minimum initialized tick
     pub fn get_min_initialized_tick(&self) -> Option<i32> {
         self.initialized_ticks.keys().min().copied()
@@ -337,6 +409,28 @@ impl BaselineLiquidity {
     }
 }
 
+/// Where the pool's active liquidity was last set from. Swaps always trade
+/// against `start_liquidity` as is, [`BaselineLiquidity::current`] doesn't
+/// recompute it from the ticks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LiquiditySource {
+    /// Read from chain: the loaded snapshot, a slot0 update or swap event, or
+    /// a reconciliation that corrected the local value
+    #[default]
+    Slot0,
+    /// Adjusted locally by liquidity events covering the current tick since
+    /// the last slot0 value
+    TickDerived
+}
+
+/// Active liquidity tracked locally disagreed with a fresh on-chain read,
+/// usually because a `ModifyLiquidity` event was missed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LiquidityDivergence {
+    pub local:    u128,
+    pub on_chain: u128
+}
+
 /// represents the liquidity at a specified point. All operations use this
 /// object.
 
@@ -377,6 +471,15 @@ impl<'a> LiquidityAtPoint<'a> {
         SqrtPriceX96::at_tick(self.max_tick_init - 1).unwrap()
     }
 
+    /// whether any loaded tick past the current one in the given direction
+    /// carries liquidity
+    pub fn has_liquidity_ahead(&self, direction: bool) -> bool {
+        self.initialized_ticks.iter().any(|(tick, info)| {
+            info.liquidity_gross > 0
+                && if direction { *tick <= self.current_tick } else { *tick > self.current_tick }
+        })
+    }
+
     /// moves to the next tick initialized within one word returning
     /// the tick and the liquidity to swap with
     pub fn get_to_next_initialized_tick_within_one_word(
@@ -446,3 +549,181 @@ impl<'a> LiquidityAtPoint<'a> {
         self.current_sqrt_price = sqrt_price.into();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use alloy_network::Ethereum;
+    use alloy_primitives::{I256, U256};
+
+    use super::{LiquidityDivergence, LiquiditySource};
+    use crate::{
+        ConversionError,
+        sqrt_pricex96::SqrtPriceX96,
+        testing::{l1_fee_config, l1_pool, pool_with_positions}
+    };
+
+    #[test]
+    fn active_range_is_bounded_by_nearest_ticks() {
+        // positions [-600, 600) and [-120, 120) both cover tick 0
+        assert_eq!(l1_pool().active_range_liquidity(), (-120, 120, 2 * 10u128.pow(18)));
+
+        // only the wide position covers tick 200
+        let pool = pool_with_positions::<Ethereum>(
+            200,
+            &[(-600, 600, 10u128.pow(18)), (-120, 120, 10u128.pow(18))],
+            l1_fee_config()
+        );
+        assert_eq!(pool.active_range_liquidity(), (120, 600, 10u128.pow(18)));
+
+        // sitting exactly on an initialized tick makes it the lower bound
+        let pool =
+            pool_with_positions::<Ethereum>(120, &[(-120, 120, 1), (120, 240, 5)], l1_fee_config());
+        assert_eq!(pool.active_range_liquidity(), (120, 240, 5));
+    }
+
+    #[test]
+    fn locked_amounts_of_a_single_range() {
+        // 1e18 liquidity over [-600, 600] at tick 0 holds L * (1 - 1.0001^-300)
+        // ~= 29553010879137169.68 of each token
+        let pool =
+            pool_with_positions::<Ethereum>(0, &[(-600, 600, 10u128.pow(18))], l1_fee_config());
+        let (amount0, amount1) = pool.locked_amounts().unwrap();
+
+        let expected = U256::from(29_553_010_879_137_169u128);
+        for amount in [amount0, amount1] {
+            assert!(amount.abs_diff(expected) <= U256::from(1_000), "{amount}");
+        }
+
+        // once the price leaves the range none of it is active
+        let pool =
+            pool_with_positions::<Ethereum>(600, &[(-600, 600, 10u128.pow(18))], l1_fee_config());
+        assert_eq!(pool.locked_amounts().unwrap(), (U256::ZERO, U256::ZERO));
+    }
+
+    #[test]
+    fn max_amount_before_tick_cross_ends_on_the_next_tick() {
+        let pool = pool_with_positions::<Ethereum>(
+            200,
+            &[(-600, 600, 10u128.pow(18)), (-120, 120, 10u128.pow(18))],
+            l1_fee_config()
+        );
+
+        for (direction, next_tick) in [(true, 120), (false, 600)] {
+            for is_bundle in [true, false] {
+                let amount = pool
+                    .max_amount_before_tick_cross(direction, is_bundle)
+                    .unwrap();
+                let swap = pool
+                    .swap_current_with_amount(I256::unchecked_from(amount), direction, is_bundle)
+                    .unwrap();
+
+                assert_eq!(swap.end_price, SqrtPriceX96::at_tick(next_tick).unwrap());
+                assert!(
+                    swap.steps
+                        .iter()
+                        .all(|step| step.liquidity == 10u128.pow(18))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn eviction_keeps_the_active_band() {
+        let mut pool = pool_with_positions::<Ethereum>(
+            0,
+            &[
+                (-600, 600, 10u128.pow(18)),
+                (-120, 120, 10u128.pow(18)),
+                (-60000, 60000, 10u128.pow(18))
+            ],
+            l1_fee_config()
+        );
+        let far_swap = I256::unchecked_from(10u128.pow(17));
+        assert!(pool.swap_current_with_amount(far_swap, true, false).is_ok());
+
+        assert_eq!(pool.evict_ticks_outside(-1200, 1200), 2);
+
+        let mut ticks = pool
+            .liquidity()
+            .initialized_ticks()
+            .keys()
+            .copied()
+            .collect::<Vec<_>>();
+        ticks.sort();
+        assert_eq!(ticks, vec![-600, -120, 120, 600]);
+        // the far ticks were the only ones in their bitmap words
+        assert!(
+            pool.liquidity()
+                .tick_bitmap()
+                .keys()
+                .all(|word| (-1..=0).contains(word))
+        );
+        assert_eq!(pool.current_liquidity(), 3 * 10u128.pow(18));
+
+        let swap = pool
+            .swap_current_with_amount(I256::unchecked_from(10u128.pow(15)), true, false)
+            .unwrap();
+        assert!(swap.end_tick > -120);
+        assert!(
+            pool.swap_current_with_amount(far_swap, true, false)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn oversized_liquidity_delta_is_rejected() {
+        let mut pool = l1_pool();
+        let before = pool.clone();
+        let delta = I256::try_from(i128::MAX).unwrap() + I256::ONE;
+
+        assert_eq!(
+            pool.update_liquidity(-120, 120, delta),
+            Err(ConversionError::OverflowErrorI28(delta))
+        );
+        assert_eq!(pool.current_liquidity(), before.current_liquidity());
+        assert_eq!(pool.liquidity().initialized_ticks(), before.liquidity().initialized_ticks());
+
+        assert!(pool.update_liquidity(-120, 120, I256::ONE).is_ok());
+        assert_eq!(pool.current_liquidity(), before.current_liquidity() + 1);
+    }
+
+    #[test]
+    fn reconcile_flags_and_corrects_divergence() {
+        let mut pool = l1_pool();
+        let local = pool.current_liquidity();
+
+        assert_eq!(pool.reconcile_liquidity(local, true), None);
+
+        let on_chain = local - 1_000;
+        assert_eq!(
+            pool.reconcile_liquidity(on_chain, false),
+            Some(LiquidityDivergence { local, on_chain })
+        );
+        assert_eq!(pool.current_liquidity(), local);
+
+        assert!(pool.reconcile_liquidity(on_chain, true).is_some());
+        assert_eq!(pool.current_liquidity(), on_chain);
+        assert_eq!(pool.reconcile_liquidity(on_chain, false), None);
+    }
+
+    #[test]
+    fn liquidity_source_follows_the_last_writer() {
+        let mut pool = l1_pool();
+        assert_eq!(pool.liquidity_source(), LiquiditySource::Slot0);
+
+        // an event away from the current tick leaves the active liquidity alone
+        pool.update_liquidity(240, 360, I256::ONE).unwrap();
+        assert_eq!(pool.liquidity_source(), LiquiditySource::Slot0);
+
+        pool.update_liquidity(-60, 60, I256::ONE).unwrap();
+        assert_eq!(pool.liquidity_source(), LiquiditySource::TickDerived);
+
+        let liquidity = pool.current_liquidity();
+        pool.update_slot0(0, SqrtPriceX96::at_tick(0).unwrap(), liquidity);
+        assert_eq!(pool.liquidity_source(), LiquiditySource::Slot0);
+
+        pool.update_liquidity(-60, 60, I256::MINUS_ONE).unwrap();
+        assert!(pool.reconcile_liquidity(liquidity, true).is_some());
+        assert_eq!(pool.liquidity_source(), LiquiditySource::Slot0);
+    }
+}
@@ -1,6 +1,15 @@
-use alloy_primitives::keccak256;
+use std::path::Path;
+
+use alloy_primitives::{
+    Address,
+    aliases::{I24, U24},
+    keccak256
+};
 use alloy_sol_types::SolValue;
-use serde::{Deserialize, Serialize};
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{DeserializeOwned, Error}
+};
 
 use crate::PoolId;
 
@@ -23,15 +32,252 @@ alloy_sol_types::sol!(
     }
 );
 
-/// Pool key with fee configuration
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Ord, PartialOrd, Serialize, Deserialize)]
+/// Pool key with fee configuration.
+///
+/// Serializes as a single flat object: `token0`, `token1`, `fee`,
+/// `tick_spacing` and `hooks` followed by the fields of the fee config, which
+/// is the format used for external pool config files.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Ord, PartialOrd)]
 pub struct PoolKeyWithFees<F> {
     pub pool_key: PoolKey,
     pub fee_cfg:  F
 }
 
+impl<F: DeserializeOwned> PoolKeyWithFees<F> {
+    /// Parses a JSON array of pool configs
+    pub fn list_from_json(json: &str) -> eyre::Result<Vec<Self>> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Reads a JSON array of pool configs from a file
+    pub fn list_from_json_file(path: impl AsRef<Path>) -> eyre::Result<Vec<Self>> {
+        Self::list_from_json(&std::fs::read_to_string(path)?)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct PoolKeyWithFeesRepr<F> {
+    token0:       Address,
+    token1:       Address,
+    fee:          u32,
+    tick_spacing: i32,
+    hooks:        Address,
+    #[serde(flatten)]
+    fee_cfg:      F
+}
+
+impl<F: Serialize> Serialize for PoolKeyWithFees<F> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        PoolKeyWithFeesRepr {
+            token0:       self.pool_key.currency0,
+            token1:       self.pool_key.currency1,
+            fee:          self.pool_key.fee.to(),
+            tick_spacing: self.pool_key.tickSpacing.as_i32(),
+            hooks:        self.pool_key.hooks,
+            fee_cfg:      &self.fee_cfg
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, F: Deserialize<'de>> Deserialize<'de> for PoolKeyWithFees<F> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = PoolKeyWithFeesRepr::<F>::deserialize(deserializer)?;
+
+        Ok(Self {
+            pool_key: PoolKey {
+                currency0:   repr.token0,
+                currency1:   repr.token1,
+                fee:         U24::try_from(repr.fee).map_err(D::Error::custom)?,
+                tickSpacing: I24::try_from(repr.tick_spacing).map_err(D::Error::custom)?,
+                hooks:       repr.hooks
+            },
+            fee_cfg:  repr.fee_cfg
+        })
+    }
+}
+
+/// Fee marking a pool as dynamic fee, which Angstrom pools are registered with
+/// in the uniswap pool manager
+pub const DYNAMIC_FEE_FLAG: u32 = 0x800000;
+/// Highest static LP fee the pool manager accepts, 100%
+pub const MAX_LP_FEE: u32 = 1_000_000;
+/// Tick spacing range the pool manager accepts
+pub const MIN_TICK_SPACING: i32 = 1;
+pub const MAX_TICK_SPACING: i32 = i16::MAX as i32;
+/// The low bits of a hook address that encode which hooks it implements
+const ALL_HOOK_MASK: u16 = (1 << 14) - 1;
+
+/// A pool key the uniswap pool manager would refuse to initialize
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoolKeyError {
+    #[error("pool currencies are the same token {0}")]
+    SameCurrency(Address),
+    #[error("fee {0} is above the max lp fee and isn't the dynamic fee flag")]
+    InvalidFee(u32),
+    #[error("tick spacing {0} is outside [{MIN_TICK_SPACING}, {MAX_TICK_SPACING}]")]
+    InvalidTickSpacing(i32),
+    /// No hooks with a dynamic fee, or hooks with neither permission bits nor
+    /// a dynamic fee
+    #[error("hooks {hooks} are inconsistent with fee {fee}")]
+    InvalidHooks { hooks: Address, fee: u32 }
+}
+
 impl From<PoolKey> for PoolId {
     fn from(value: PoolKey) -> Self {
         keccak256(value.abi_encode())
     }
 }
+
+impl PoolKey {
+    /// Builds a key from currencies in either order, validated like
+    /// [`Self::canonicalize`]
+    pub fn try_new(
+        currency_a: Address,
+        currency_b: Address,
+        fee: u32,
+        tick_spacing: i32,
+        hooks: Address
+    ) -> Result<Self, PoolKeyError> {
+        let mut key = PoolKey {
+            currency0: currency_a,
+            currency1: currency_b,
+            fee: U24::try_from(fee).map_err(|_| PoolKeyError::InvalidFee(fee))?,
+            tickSpacing: I24::try_from(tick_spacing)
+                .map_err(|_| PoolKeyError::InvalidTickSpacing(tick_spacing))?,
+            hooks
+        };
+        key.canonicalize()?;
+        Ok(key)
+    }
+
+    /// Sorts the currencies so `currency0 < currency1`, the order the pool
+    /// manager and the registries key pools by, then checks the fee, tick
+    /// spacing and hooks are ones the pool manager would accept
+    pub fn canonicalize(&mut self) -> Result<(), PoolKeyError> {
+        if self.currency0 == self.currency1 {
+            return Err(PoolKeyError::SameCurrency(self.currency0));
+        }
+        if self.currency0 > self.currency1 {
+            std::mem::swap(&mut self.currency0, &mut self.currency1);
+        }
+
+        let fee = self.fee.to::<u32>();
+        let dynamic_fee = fee == DYNAMIC_FEE_FLAG;
+        if fee > MAX_LP_FEE && !dynamic_fee {
+            return Err(PoolKeyError::InvalidFee(fee));
+        }
+
+        let tick_spacing = self.tickSpacing.as_i32();
+        if !(MIN_TICK_SPACING..=MAX_TICK_SPACING).contains(&tick_spacing) {
+            return Err(PoolKeyError::InvalidTickSpacing(tick_spacing));
+        }
+
+        let hook_flags = u16::from_be_bytes([self.hooks[18], self.hooks[19]]) & ALL_HOOK_MASK;
+        let valid_hooks =
+            if self.hooks.is_zero() { !dynamic_fee } else { hook_flags != 0 || dynamic_fee };
+        if !valid_hooks {
+            return Err(PoolKeyError::InvalidHooks { hooks: self.hooks, fee });
+        }
+
+        Ok(())
+    }
+
+    /// The id Angstrom knows the pool by, the key hashed with its configured
+    /// fee
+    pub fn angstrom_pool_id(&self) -> PoolId {
+        PoolId::from(*self)
+    }
+
+    /// The id of an Angstrom pool in the uniswap pool manager, the key hashed
+    /// with the dynamic fee flag as its fee
+    pub fn uniswap_pool_id(&self) -> PoolId {
+        PoolId::from(PoolKey { fee: U24::from(DYNAMIC_FEE_FLAG), ..*self })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::{
+        Address,
+        aliases::{I24, U24}
+    };
+
+    use super::{DYNAMIC_FEE_FLAG, PoolKey, PoolKeyError, PoolKeyWithFees};
+    use crate::L1FeeConfiguration;
+
+    #[test]
+    fn json_roundtrip() {
+        let key = PoolKeyWithFees {
+            pool_key: PoolKey {
+                currency0:   Address::with_last_byte(1),
+                currency1:   Address::with_last_byte(2),
+                fee:         U24::from(3000),
+                tickSpacing: I24::unchecked_from(-60),
+                hooks:       Address::with_last_byte(3)
+            },
+            fee_cfg:  L1FeeConfiguration {
+                bundle_fee:   100,
+                swap_fee:     3000,
+                protocol_fee: 500
+            }
+        };
+
+        let json = serde_json::to_value(&key).unwrap();
+        assert_eq!(json["token0"], "0x0000000000000000000000000000000000000001");
+        assert_eq!(json["hooks"], "0x0000000000000000000000000000000000000003");
+        assert_eq!(json["fee"], 3000);
+        assert_eq!(json["tick_spacing"], -60);
+        assert_eq!(json["swap_fee"], 3000);
+
+        let decoded: PoolKeyWithFees<L1FeeConfiguration> = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded, key);
+    }
+
+    #[test]
+    fn canonicalize_sorts_and_validates() {
+        let (low, high) = (Address::with_last_byte(1), Address::with_last_byte(2));
+        // an angstrom style hook, with permission bits set
+        let hooks = Address::repeat_byte(0xaa);
+
+        let key = PoolKey::try_new(high, low, DYNAMIC_FEE_FLAG, 60, hooks).unwrap();
+        assert_eq!((key.currency0, key.currency1), (low, high));
+        assert_eq!(key, PoolKey::try_new(low, high, DYNAMIC_FEE_FLAG, 60, hooks).unwrap());
+
+        let mut unsorted = PoolKey { currency0: high, currency1: low, ..key };
+        assert_ne!(unsorted.uniswap_pool_id(), key.uniswap_pool_id());
+        unsorted.canonicalize().unwrap();
+        assert_eq!(unsorted.uniswap_pool_id(), key.uniswap_pool_id());
+
+        assert_eq!(
+            PoolKey::try_new(low, low, 3000, 60, Address::ZERO),
+            Err(PoolKeyError::SameCurrency(low))
+        );
+        assert_eq!(
+            PoolKey::try_new(high, low, 1_000_001, 60, hooks),
+            Err(PoolKeyError::InvalidFee(1_000_001))
+        );
+        assert_eq!(
+            PoolKey::try_new(high, low, 3000, 0, hooks),
+            Err(PoolKeyError::InvalidTickSpacing(0))
+        );
+        assert_eq!(
+            PoolKey::try_new(high, low, 3000, 40_000, hooks),
+            Err(PoolKeyError::InvalidTickSpacing(40_000))
+        );
+
+        // a dynamic fee needs a hook to set it, and a hook without permission
+        // bits has to be setting a dynamic fee
+        assert_eq!(
+            PoolKey::try_new(high, low, DYNAMIC_FEE_FLAG, 60, Address::ZERO),
+            Err(PoolKeyError::InvalidHooks { hooks: Address::ZERO, fee: DYNAMIC_FEE_FLAG })
+        );
+        let no_flags = Address::left_padding_from(&[0xc0, 0, 0]);
+        assert_eq!(
+            PoolKey::try_new(high, low, 3000, 60, no_flags),
+            Err(PoolKeyError::InvalidHooks { hooks: no_flags, fee: 3000 })
+        );
+        assert!(PoolKey::try_new(high, low, DYNAMIC_FEE_FLAG, 60, no_flags).is_ok());
+        assert!(PoolKey::try_new(high, low, 3000, 60, Address::ZERO).is_ok());
+    }
+}
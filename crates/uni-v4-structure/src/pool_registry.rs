@@ -1,8 +1,10 @@
 use std::{collections::HashMap, fmt::Debug};
 
 use alloy_primitives::{Address, aliases::U24};
+#[cfg(feature = "registry-events")]
+use tokio::sync::broadcast;
 
-use crate::{PoolId, PoolKey};
+use crate::{L1FeeConfiguration, PoolId, PoolKey, PoolKeyWithFees, pool_key::DYNAMIC_FEE_FLAG};
 
 pub trait PoolRegistry: Clone + Send + Sync + Unpin + Debug {
     type PoolIdSet: UniswapPoolIdSet;
@@ -17,6 +19,8 @@ pub trait PoolRegistry: Clone + Send + Sync + Unpin + Debug {
 
     fn angstrom_pool_id_from_uniswap_pool_id(&self, pool_id: PoolId) -> Option<PoolId>;
 
+    fn uniswap_pool_id_from_angstrom_pool_id(&self, pool_id: PoolId) -> Option<PoolId>;
+
     fn all_uniswap_pool_ids(&self) -> impl Iterator<Item = PoolId> + '_;
 
     fn add_new_pool(&mut self, pool_key: PoolKey);
@@ -47,6 +51,63 @@ pub trait PoolRegistry: Clone + Send + Sync + Unpin + Debug {
     ) -> Option<PoolId>;
 
     fn make_pool_id_set(&self, pool_id: PoolId) -> Option<Self::PoolIdSet>;
+
+    /// Subscribes to pools being added to or removed from this registry.
+    /// Returns `None` if the registry was not created with events enabled
+    #[cfg(feature = "registry-events")]
+    fn subscribe_registry_events(&self) -> Option<broadcast::Receiver<RegistryEvent>>;
+}
+
+/// A change to the set of pools held by a [`PoolRegistry`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegistryEvent {
+    Added(PoolId),
+    Removed(PoolId)
+}
+
+/// The [`RegistryEvent`] channel of a registry, shared by its clones. Without
+/// the `registry-events` feature there is no channel and emitting is a no-op
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RegistryEvents {
+    #[cfg(feature = "registry-events")]
+    sender: Option<broadcast::Sender<RegistryEvent>>
+}
+
+impl RegistryEvents {
+    #[cfg(feature = "registry-events")]
+    pub(crate) fn enabled(capacity: usize) -> Self {
+        Self { sender: Some(broadcast::channel(capacity).0) }
+    }
+
+    #[cfg(feature = "registry-events")]
+    pub(crate) fn subscribe(&self) -> Option<broadcast::Receiver<RegistryEvent>> {
+        self.sender.as_ref().map(broadcast::Sender::subscribe)
+    }
+
+    /// Sends `event` if events are enabled, a send only fails when there are
+    /// no subscribers which is fine to ignore
+    pub(crate) fn emit(&self, event: RegistryEvent) {
+        #[cfg(feature = "registry-events")]
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(event);
+        }
+        #[cfg(not(feature = "registry-events"))]
+        let _ = event;
+    }
+}
+
+/// Liquidity math steps through ticks by the spacing, a pool with a spacing
+/// that isn't positive can't be simulated so it is never registered
+pub(crate) fn has_valid_tick_spacing(pool_key: &PoolKey) -> bool {
+    let valid = pool_key.tickSpacing.as_i32() > 0;
+    if !valid {
+        tracing::warn!(
+            ?pool_key,
+            "skipping pool with tick spacing {}, it must be positive",
+            pool_key.tickSpacing
+        );
+    }
+    valid
 }
 
 pub trait UniswapPoolIdSet: Copy + Clone + Send + Sync + Unpin + Debug {
@@ -57,7 +118,8 @@ pub trait UniswapPoolIdSet: Copy + Clone + Send + Sync + Unpin + Debug {
 pub struct L1PoolRegistry {
     angstrom_address:  Address,
     uni_pools:         HashMap<PoolId, PoolKey>,
-    angstrom_registry: AngstromRegistry
+    angstrom_registry: AngstromRegistry,
+    registry_events:   RegistryEvents
 }
 
 impl L1PoolRegistry {
@@ -65,10 +127,30 @@ impl L1PoolRegistry {
         Self {
             angstrom_address,
             uni_pools: Default::default(),
-            angstrom_registry: Default::default()
+            angstrom_registry: Default::default(),
+            registry_events: RegistryEvents::default()
         }
     }
 
+    /// Enables [`RegistryEvent`]s, which are shared by all clones of this
+    /// registry
+    #[cfg(feature = "registry-events")]
+    pub fn with_registry_events(mut self, capacity: usize) -> Self {
+        self.registry_events = RegistryEvents::enabled(capacity);
+        self
+    }
+
+    /// Builds a registry from a known set of pools instead of scanning the
+    /// chain for them
+    pub fn from_config(
+        angstrom_address: Address,
+        pools: Vec<PoolKeyWithFees<L1FeeConfiguration>>
+    ) -> Self {
+        let mut this = Self::new(angstrom_address);
+        this.add_new_pools(pools.into_iter().map(|pool| pool.pool_key));
+        this
+    }
+
     pub fn private_keys(&self) -> impl Iterator<Item = PoolId> + '_ {
         self.angstrom_registry.conversion_map.values().copied()
     }
@@ -118,16 +200,24 @@ impl PoolRegistry for L1PoolRegistry {
                 .conversion_map
                 .remove(&id_set.angstrom_id);
             self.angstrom_registry.pools.remove(&id_set.angstrom_id);
+            self.registry_events
+                .emit(RegistryEvent::Removed(id_set.uniswap_id));
         }
     }
 
     fn add_new_pool(&mut self, mut pool_key: PoolKey) {
+        if !has_valid_tick_spacing(&pool_key) {
+            return;
+        }
+
         if pool_key.hooks == self.angstrom_address {
             self.angstrom_registry.add_key(pool_key);
-            pool_key.fee = U24::from(0x800000);
+            pool_key.fee = U24::from(DYNAMIC_FEE_FLAG);
         }
 
-        self.uni_pools.insert(pool_key.into(), pool_key);
+        let pool_id = PoolId::from(pool_key);
+        self.uni_pools.insert(pool_id, pool_key);
+        self.registry_events.emit(RegistryEvent::Added(pool_id));
     }
 
     fn get_pools_by_token_pair(
@@ -200,6 +290,15 @@ impl PoolRegistry for L1PoolRegistry {
             .get(&pool_id)
             .copied()
     }
+
+    fn uniswap_pool_id_from_angstrom_pool_id(&self, pool_id: PoolId) -> Option<PoolId> {
+        self.angstrom_registry.conversion_map.get(&pool_id).copied()
+    }
+
+    #[cfg(feature = "registry-events")]
+    fn subscribe_registry_events(&self) -> Option<broadcast::Receiver<RegistryEvent>> {
+        self.registry_events.subscribe()
+    }
 }
 
 #[derive(Debug, Default, Clone)]
@@ -210,14 +309,13 @@ struct AngstromRegistry {
 }
 
 impl AngstromRegistry {
-    fn add_key(&mut self, mut pool_key: PoolKey) {
-        self.pools.insert(pool_key.into(), pool_key);
-        let copyed_pub: PoolId = pool_key.into();
+    fn add_key(&mut self, pool_key: PoolKey) {
+        let pub_key = pool_key.angstrom_pool_id();
+        self.pools.insert(pub_key, pool_key);
 
-        pool_key.fee = U24::from(0x800000);
-        let priv_key = PoolId::from(pool_key);
-        self.conversion_map.insert(copyed_pub, priv_key);
-        self.reverse_conversion_map.insert(priv_key, copyed_pub);
+        let priv_key = pool_key.uniswap_pool_id();
+        self.conversion_map.insert(pub_key, priv_key);
+        self.reverse_conversion_map.insert(priv_key, pub_key);
     }
 }
 
@@ -236,12 +334,8 @@ impl AngstromPoolIdPair {
 }
 
 impl From<PoolKey> for AngstromPoolIdPair {
-    fn from(mut pool_key: PoolKey) -> Self {
-        let angstrom_id: PoolId = pool_key.into();
-        pool_key.fee = U24::from(0x800000);
-        let uniswap_id = PoolId::from(pool_key);
-
-        Self { angstrom_id, uniswap_id }
+    fn from(pool_key: PoolKey) -> Self {
+        Self { angstrom_id: pool_key.angstrom_pool_id(), uniswap_id: pool_key.uniswap_pool_id() }
     }
 }
 
@@ -250,3 +344,136 @@ impl UniswapPoolIdSet for AngstromPoolIdPair {
         self.uniswap_id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use alloy_primitives::{Address, aliases::I24};
+
+    use super::{AngstromPoolIdPair, L1PoolRegistry, PoolRegistry};
+    use crate::{L1FeeConfiguration, PoolId, PoolKey, PoolKeyWithFees};
+
+    const CONFIG: &str = r#"[
+        {
+            "token0": "0x0000000000000000000000000000000000000001",
+            "token1": "0x0000000000000000000000000000000000000002",
+            "fee": 500,
+            "tick_spacing": 10,
+            "hooks": "0x0000000000000000000000000000000000000000",
+            "bundle_fee": 0,
+            "swap_fee": 500,
+            "protocol_fee": 0
+        },
+        {
+            "token0": "0x0000000000000000000000000000000000000001",
+            "token1": "0x0000000000000000000000000000000000000002",
+            "fee": 3000,
+            "tick_spacing": 60,
+            "hooks": "0x0000000000000000000000000000000000000000",
+            "bundle_fee": 0,
+            "swap_fee": 3000,
+            "protocol_fee": 0
+        }
+    ]"#;
+
+    #[test]
+    fn from_config_resolves_pools() {
+        let pools = PoolKeyWithFees::<L1FeeConfiguration>::list_from_json(CONFIG).unwrap();
+        let keys = pools.iter().map(|pool| pool.pool_key).collect::<Vec<_>>();
+        let registry = L1PoolRegistry::from_config(Address::with_last_byte(0xaa), pools);
+
+        let (token0, token1) = (Address::with_last_byte(1), Address::with_last_byte(2));
+        for (key, fee) in keys.into_iter().zip([500, 3000]) {
+            assert_eq!(
+                registry.get_pool_id_by_tokens_and_fee(token1, token0, fee, None),
+                Some(PoolId::from(key))
+            );
+        }
+        assert_eq!(registry.get_pools_by_token_pair(token0, token1, None).len(), 2);
+    }
+
+    #[cfg(feature = "registry-events")]
+    #[test]
+    fn add_and_remove_emit_registry_events() {
+        use super::RegistryEvent;
+
+        let angstrom_address = Address::with_last_byte(0xaa);
+        let mut pools = PoolKeyWithFees::<L1FeeConfiguration>::list_from_json(CONFIG).unwrap();
+        let mut pool_key = pools.pop().unwrap().pool_key;
+        pool_key.hooks = angstrom_address;
+        let uniswap_id = AngstromPoolIdPair::from(pool_key).uniswap_id;
+
+        let mut registry = L1PoolRegistry::new(angstrom_address).with_registry_events(8);
+        let mut events = registry.subscribe_registry_events().unwrap();
+
+        // clones share the channel
+        registry.clone().add_new_pool(pool_key);
+        assert_eq!(events.try_recv().unwrap(), RegistryEvent::Added(uniswap_id));
+
+        registry.add_new_pool(pool_key);
+        registry.remove(&uniswap_id);
+        assert_eq!(events.try_recv().unwrap(), RegistryEvent::Added(uniswap_id));
+        assert_eq!(events.try_recv().unwrap(), RegistryEvent::Removed(uniswap_id));
+    }
+
+    #[test]
+    fn pool_key_ids_match_the_registry() {
+        let angstrom_address = Address::with_last_byte(0xaa);
+        let mut pools = PoolKeyWithFees::<L1FeeConfiguration>::list_from_json(CONFIG).unwrap();
+        let plain_key = pools.pop().unwrap().pool_key;
+        let angstrom_key = PoolKey { hooks: angstrom_address, ..plain_key };
+
+        let mut registry = L1PoolRegistry::new(angstrom_address);
+        registry.add_new_pool(angstrom_key);
+        registry.add_new_pool(plain_key);
+
+        let uniswap_ids = registry.all_uniswap_pool_ids().collect::<HashSet<_>>();
+        assert!(uniswap_ids.contains(&angstrom_key.uniswap_pool_id()));
+        // pools on other hooks keep their own fee in the pool manager
+        assert!(uniswap_ids.contains(&plain_key.angstrom_pool_id()));
+        assert_eq!(
+            registry.all_angstrom_pool_ids().collect::<Vec<_>>(),
+            vec![angstrom_key.angstrom_pool_id()]
+        );
+        assert_eq!(
+            registry.uniswap_pool_id_from_angstrom_pool_id(angstrom_key.angstrom_pool_id()),
+            Some(angstrom_key.uniswap_pool_id())
+        );
+    }
+
+    #[test]
+    fn zero_tick_spacing_pool_is_rejected() {
+        let mut pools = PoolKeyWithFees::<L1FeeConfiguration>::list_from_json(CONFIG).unwrap();
+        let mut pool_key = pools.pop().unwrap().pool_key;
+        pool_key.tickSpacing = I24::ZERO;
+
+        let mut registry = L1PoolRegistry::new(Address::with_last_byte(0xaa));
+        registry.add_new_pool(pool_key);
+
+        assert!(registry.get(&PoolId::from(pool_key)).is_none());
+        assert_eq!(registry.all_uniswap_pool_ids().count(), 0);
+    }
+
+    #[test]
+    fn converts_ids_both_ways() {
+        let angstrom_address = Address::with_last_byte(0xaa);
+        let mut pools = PoolKeyWithFees::<L1FeeConfiguration>::list_from_json(CONFIG).unwrap();
+        let mut pool_key = pools.pop().unwrap().pool_key;
+        pool_key.hooks = angstrom_address;
+        let ids = AngstromPoolIdPair::from(pool_key);
+
+        let mut registry = L1PoolRegistry::new(angstrom_address);
+        registry.add_new_pool(pool_key);
+
+        assert_eq!(
+            registry.uniswap_pool_id_from_angstrom_pool_id(ids.angstrom_id),
+            Some(ids.uniswap_id)
+        );
+        assert_eq!(
+            registry.angstrom_pool_id_from_uniswap_pool_id(ids.uniswap_id),
+            Some(ids.angstrom_id)
+        );
+        assert_eq!(registry.uniswap_pool_id_from_angstrom_pool_id(ids.uniswap_id), None);
+    }
+}
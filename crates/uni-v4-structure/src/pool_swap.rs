@@ -9,6 +9,51 @@ use crate::{V4Network, fee_config::FeeConfig, ray::Ray, sqrt_pricex96::SqrtPrice
 
 const U256_1: U256 = U256::from_limbs([1, 0, 0, 0]);
 
+/// Rough gas for a swap through the pool manager that crosses no initialized
+/// ticks, see [`PoolSwapResult::estimated_gas`]
+pub const SWAP_BASE_GAS: u64 = 120_000;
+/// Rough extra gas for each initialized tick a swap crosses
+pub const TICK_CROSSING_GAS: u64 = 25_000;
+
+/// Swap failures callers may want to tell apart. They are returned inside the
+/// `eyre::Report`, use `downcast_ref` to match on them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum PoolSwapError {
+    /// There is no active liquidity and no loaded position in the swap
+    /// direction, e.g. a pool whose only position sits on the other side of
+    /// the current tick
+    #[error("No liquidity in the swap direction")]
+    NoLiquidity,
+    /// The pool only accepts withdrawals (L2 `withdraw_only`), a swap would
+    /// revert on-chain
+    #[error("Pool is withdraw only")]
+    WithdrawOnly,
+    /// A swap was built with neither an amount nor a price limit
+    #[error("Swap needs an amount or a price limit")]
+    MissingTarget,
+    /// A swap was built with an amount but no direction
+    #[error("Swap direction is required when swapping an amount")]
+    MissingDirection,
+    /// The price limit lies behind the current price for the swap direction
+    #[error("Price limit is on the wrong side of the current price")]
+    LimitAgainstDirection,
+    /// A tick limit at or beyond `MIN_TICK`/`MAX_TICK`, whose price can't be
+    /// used as a limit
+    #[error("Tick limit is outside the valid tick range")]
+    TickLimitOutOfRange,
+    /// A sqrt price limit at or beyond the min/max sqrt ratio, see
+    /// [`SqrtPriceX96::is_valid_limit`]
+    #[error("Invalid sqrt price limit")]
+    InvalidSqrtPriceLimit,
+    /// MEV tax was set on a swap that never pays it: bundle mode, exact output
+    /// or an L1 pool
+    #[error("MEV tax only applies to unlocked exact input L2 swaps")]
+    MevTaxNotApplicable,
+    /// The loaded liquidity can't fill the requested output
+    #[error("Not enough liquidity for the requested output")]
+    InsufficientLiquidity
+}
+
 #[derive(Debug, Clone)]
 pub struct PoolSwap<'a, T: V4Network> {
     pub(super) liquidity:      LiquidityAtPoint<'a>,
@@ -42,6 +87,19 @@ impl<'a, T: V4Network> PoolSwap<'a, T> {
             return Err(eyre::eyre!("direction and sqrt_price diverge"));
         }
 
+        if self
+            .target_price
+            .is_some_and(|limit| !limit.is_valid_limit())
+        {
+            return Err(PoolSwapError::InvalidSqrtPriceLimit.into());
+        }
+
+        if self.liquidity.current_liquidity == 0
+            && !self.liquidity.has_liquidity_ahead(self.direction)
+        {
+            return Err(PoolSwapError::NoLiquidity.into());
+        }
+
         let range_start = self.liquidity.current_sqrt_price;
         let range_start_tick = self.liquidity.current_tick;
 
@@ -105,6 +163,7 @@ impl<'a, T: V4Network> PoolSwap<'a, T> {
 
         while amount_remaining != I256::ZERO && sqrt_price_x96 != sqrt_price_limit_x96 {
             let sqrt_price_start_x_96 = sqrt_price_x96;
+            let tick_start = self.liquidity.current_tick;
 
             let (next_tick, liquidity, init) = self
                 .liquidity
@@ -144,16 +203,6 @@ impl<'a, T: V4Network> PoolSwap<'a, T> {
                 amount_remaining = amount_remaining.saturating_add(I256::from_raw(amount_out));
             }
 
-            let (d_t0, d_t1) = if self.direction {
-                // zero-for-one swap: token0 in, token1 out
-                // fee is always on the input (token0) side
-                ((amount_in + fee_amount).to(), amount_out.to())
-            } else {
-                // one-for-zero swap: token1 in, token0 out
-                // fee is always on the input (token1) side
-                (amount_out.to(), (amount_in + fee_amount).to())
-            };
-
             self.liquidity.move_to_next_tick(
                 sqrt_price_x96,
                 self.direction,
@@ -161,18 +210,38 @@ impl<'a, T: V4Network> PoolSwap<'a, T> {
                 sqrt_price_x96 != sqrt_price_start_x_96
             )?;
 
-            steps.push(PoolSwapStep { end_tick: next_tick, init, liquidity, d_t0, d_t1 });
+            steps.push(SwapStep {
+                zero_for_one: self.direction,
+                tick_start,
+                tick_end: self.liquidity.current_tick,
+                sqrt_price_start: sqrt_price_start_x_96.into(),
+                sqrt_price_end: sqrt_price_x96.into(),
+                init,
+                liquidity,
+                amount_in: amount_in.to(),
+                amount_out: amount_out.to(),
+                fee_amount: fee_amount.to()
+            });
         }
 
         // the final sqrt price
         self.liquidity.set_sqrt_price(sqrt_price_x96);
 
         let (total_d_t0, total_d_t1) = steps.iter().fold((0u128, 0u128), |(mut t0, mut t1), x| {
-            t0 += x.d_t0;
-            t1 += x.d_t1;
+            t0 += x.d_t0();
+            t1 += x.d_t1();
             (t0, t1)
         });
 
+        // the tax is on the ETH delta, an ETH output smaller than it is taken whole
+        let mev_tax_charged = self
+            .mev_tax_amount
+            .filter(|_| self.fee_config.l2_fees() && !self.is_bundle && exact_input)
+            .map(|tax| if self.direction { tax } else { tax.min(total_d_t0) });
+        let amount_filled_before_limit = (self.target_amount - amount_remaining)
+            .unsigned_abs()
+            .saturating_to::<u128>();
+
         let (final_d_t0, final_d_t1) = if self.fee_config.l2_fees() {
             // L2: add back beforeSwap deductions to final deltas.
             // The AMM ran on reduced input, so we re-add fees to the input side
@@ -243,26 +312,253 @@ impl<'a, T: V4Network> PoolSwap<'a, T> {
             total_d_t1: final_d_t1,
             steps,
             end_liquidity: self.liquidity,
-            is_bundle: self.is_bundle
+            is_bundle: self.is_bundle,
+            mev_tax_charged,
+            amount_filled_before_limit
+        })
+    }
+}
+
+/// Builds a [`PoolSwap`] from its parts, rejecting combinations the swap
+/// would otherwise quietly mis-handle.
+///
+/// Either an amount (positive exact in, negative exact out) or a price limit
+/// is required. Without a direction one is inferred from the price limit.
+/// Withdraw only pools aren't rejected here, see
+/// [`BaselinePoolState::can_swap`](crate::BaselinePoolState::can_swap).
+#[derive(Debug, Clone)]
+pub struct PoolSwapBuilder<'a, T: V4Network> {
+    liquidity:      LiquidityAtPoint<'a>,
+    fee_config:     T::FeeConfig,
+    target_amount:  Option<I256>,
+    target_price:   Option<SqrtPriceX96>,
+    direction:      Option<bool>,
+    is_bundle:      bool,
+    mev_tax_amount: Option<u128>
+}
+
+impl<'a, T: V4Network> PoolSwap<'a, T> {
+    pub fn builder(
+        liquidity: LiquidityAtPoint<'a>,
+        fee_config: T::FeeConfig
+    ) -> PoolSwapBuilder<'a, T> {
+        PoolSwapBuilder {
+            liquidity,
+            fee_config,
+            target_amount: None,
+            target_price: None,
+            direction: None,
+            is_bundle: false,
+            mev_tax_amount: None
+        }
+    }
+}
+
+impl<'a, T: V4Network> PoolSwapBuilder<'a, T> {
+    pub fn amount(mut self, amount: I256) -> Self {
+        self.target_amount = Some(amount);
+        self
+    }
+
+    pub fn price_limit(mut self, price: SqrtPriceX96) -> Self {
+        self.target_price = Some(price);
+        self
+    }
+
+    /// zero for one when true
+    pub fn direction(mut self, zero_for_one: bool) -> Self {
+        self.direction = Some(zero_for_one);
+        self
+    }
+
+    pub fn bundle(mut self, is_bundle: bool) -> Self {
+        self.is_bundle = is_bundle;
+        self
+    }
+
+    /// MEV tax in wei, see [`FeeConfig::mev_tax`]
+    pub fn mev_tax(mut self, mev_tax: u128) -> Self {
+        self.mev_tax_amount = Some(mev_tax).filter(|&tax| tax > 0);
+        self
+    }
+
+    pub fn build(self) -> Result<PoolSwap<'a, T>, PoolSwapError> {
+        let current_price = self.liquidity.current_sqrt_price;
+        let direction = match (self.direction, self.target_price) {
+            (Some(direction), _) => direction,
+            (None, Some(price)) => current_price >= price,
+            (None, None) if self.target_amount.is_some() => {
+                return Err(PoolSwapError::MissingDirection);
+            }
+            (None, None) => return Err(PoolSwapError::MissingTarget)
+        };
+
+        if let Some(price) = self.target_price
+            && (if direction { price > current_price } else { price < current_price })
+        {
+            return Err(PoolSwapError::LimitAgainstDirection);
+        }
+
+        let target_amount = self.target_amount.unwrap_or(I256::MAX);
+        if self.mev_tax_amount.is_some()
+            && (self.is_bundle || !self.fee_config.l2_fees() || !target_amount.is_positive())
+        {
+            return Err(PoolSwapError::MevTaxNotApplicable);
+        }
+
+        Ok(PoolSwap {
+            liquidity: self.liquidity,
+            target_price: self.target_price,
+            target_amount,
+            direction,
+            fee_config: self.fee_config,
+            is_bundle: self.is_bundle,
+            mev_tax_amount: self.mev_tax_amount
         })
     }
+
+    /// Builds and runs the swap
+    pub fn swap(self) -> eyre::Result<PoolSwapResult<'a, T>> {
+        self.build()?.swap()
+    }
+}
+
+/// Everything a swap against a pool's current state can be asked to do, run
+/// with [`BaselinePoolState::swap`](crate::BaselinePoolState::swap). Any
+/// amount can be combined with a price limit, and exact input amounts with a
+/// priority fee.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SwapParams {
+    /// positive for exact input, negative for exact output. Without one the
+    /// swap runs until the price limit
+    pub amount:           Option<I256>,
+    /// zero for one when true, inferred from the price limit when `None`
+    pub direction:        Option<bool>,
+    pub price_limit:      Option<SqrtPriceX96>,
+    pub is_bundle:        bool,
+    /// priority fee (tx.gasprice - block.basefee) in wei the L2 MEV tax is
+    /// charged on. Only unlocked exact input swaps on L2 pools pay it, on any
+    /// other swap a fee above the tax floor is rejected
+    pub priority_fee_wei: Option<u128>
+}
+
+impl SwapParams {
+    /// Swaps `amount`, positive for exact input and negative for exact output
+    pub fn amount(amount: I256, zero_for_one: bool) -> Self {
+        Self { amount: Some(amount), direction: Some(zero_for_one), ..Default::default() }
+    }
+
+    /// Swaps until the pool reaches `price_limit`
+    pub fn to_price(price_limit: SqrtPriceX96) -> Self {
+        Self { price_limit: Some(price_limit), ..Default::default() }
+    }
+
+    pub fn with_limit(mut self, price_limit: SqrtPriceX96) -> Self {
+        self.price_limit = Some(price_limit);
+        self
+    }
+
+    pub fn with_priority_fee(mut self, priority_fee_wei: Option<u128>) -> Self {
+        self.priority_fee_wei = priority_fee_wei;
+        self
+    }
+
+    pub fn bundle(mut self, is_bundle: bool) -> Self {
+        self.is_bundle = is_bundle;
+        self
+    }
+
+    /// A [`PoolSwapBuilder`] for these parameters against `liquidity`, with
+    /// the priority fee turned into the MEV tax `fee_config` charges on it
+    pub(crate) fn builder<'a, T: V4Network>(
+        self,
+        liquidity: LiquidityAtPoint<'a>,
+        fee_config: T::FeeConfig
+    ) -> PoolSwapBuilder<'a, T> {
+        let mut builder = PoolSwap::builder(liquidity, fee_config).bundle(self.is_bundle);
+        if let Some(amount) = self.amount {
+            builder = builder.amount(amount);
+        }
+        if let Some(direction) = self.direction {
+            builder = builder.direction(direction);
+        }
+        if let Some(price_limit) = self.price_limit {
+            builder = builder.price_limit(price_limit);
+        }
+        if let Some(priority_fee_wei) = self.priority_fee_wei {
+            builder = builder.mev_tax(fee_config.mev_tax(priority_fee_wei));
+        }
+        builder
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct PoolSwapResult<'a, T: V4Network> {
-    pub fee_config:    T::FeeConfig,
-    pub start_price:   SqrtPriceX96,
-    pub start_tick:    i32,
-    pub end_price:     SqrtPriceX96,
-    pub end_tick:      i32,
-    pub total_d_t0:    u128,
-    pub total_d_t1:    u128,
-    pub steps:         Vec<PoolSwapStep>,
+    pub fee_config: T::FeeConfig,
+    pub start_price: SqrtPriceX96,
+    pub start_tick: i32,
+    pub end_price: SqrtPriceX96,
+    pub end_tick: i32,
+    pub total_d_t0: u128,
+    pub total_d_t1: u128,
+    pub steps: Vec<SwapStep>,
     pub end_liquidity: LiquidityAtPoint<'a>,
-    pub is_bundle:     bool
+    pub is_bundle: bool,
+    /// The L2 MEV tax taken from the ETH side of the swap. Set for unlocked
+    /// exact input swaps at a priority fee above the pool's tax floor. Taken
+    /// from the input in full, but capped at the ETH actually filled when ETH
+    /// is the output.
+    pub mev_tax_charged: Option<u128>,
+    /// How much of the specified amount (input for exact in, output for exact
+    /// out) was used, including any fees and tax taken before the swap. Less
+    /// than the amount asked for when the price limit stopped the swap.
+    pub amount_filled_before_limit: u128
 }
 
 impl<'a, T: V4Network> PoolSwapResult<'a, T> {
+    /// The MEV tax a swap on this pool pays at `priority_fee_wei`
+    /// (tx.gasprice - block.basefee), charged with the pool's
+    /// [`MevTaxParams`](crate::MevTaxParams).
+    /// Always zero for L1 pools.
+    pub fn estimated_l2_tax(&self, priority_fee_wei: u128) -> u128 {
+        self.fee_config.mev_tax(priority_fee_wei)
+    }
+
+    /// Initialized ticks the swap crossed, in the order they were crossed.
+    /// Each crossing costs gas on chain, so this is a rough gauge of the
+    /// swap's cost.
+    pub fn crossed_ticks(&self) -> Vec<i32> {
+        self.steps
+            .iter()
+            .filter_map(SwapStep::crossed_tick)
+            .collect()
+    }
+
+    /// Heuristic gas cost of the swap for sizing bundles,
+    /// [`SWAP_BASE_GAS`] plus [`TICK_CROSSING_GAS`] per crossed tick
+    pub fn estimated_gas(&self) -> u64 {
+        self.estimated_gas_with(SWAP_BASE_GAS, TICK_CROSSING_GAS)
+    }
+
+    /// [`Self::estimated_gas`] with custom costs
+    pub fn estimated_gas_with(&self, base_gas: u64, tick_crossing_gas: u64) -> u64 {
+        let crossings = self.crossed_ticks().len() as u64;
+        base_gas.saturating_add(tick_crossing_gas.saturating_mul(crossings))
+    }
+
+    /// Runs `params` from where this swap ended, in the same mode. Fails on
+    /// the same pools and parameters as
+    /// [`BaselinePoolState::swap`](crate::BaselinePoolState::swap).
+    fn swap_from_end(&'a self, params: SwapParams) -> eyre::Result<PoolSwapResult<'a, T>> {
+        if self.fee_config.withdraw_only() {
+            return Err(PoolSwapError::WithdrawOnly.into());
+        }
+        params
+            .bundle(self.is_bundle)
+            .builder(self.end_liquidity.clone(), self.fee_config)
+            .swap()
+    }
+
     /// initialize a swap from the end of this swap into a new swap.
     pub fn swap_to_amount(
         &'a self,
@@ -281,19 +577,9 @@ impl<'a, T: V4Network> PoolSwapResult<'a, T> {
         direction: bool,
         priority_fee_wei: Option<u128>
     ) -> eyre::Result<PoolSwapResult<'a, T>> {
-        let mev_tax_amount = priority_fee_wei
-            .map(|fee| self.fee_config.mev_tax(fee))
-            .filter(|&tax| tax > 0);
-        PoolSwap {
-            liquidity: self.end_liquidity.clone(),
-            target_price: None,
-            direction,
-            target_amount: amount,
-            fee_config: self.fee_config,
-            is_bundle: self.is_bundle,
-            mev_tax_amount
-        }
-        .swap()
+        self.swap_from_end(
+            SwapParams::amount(amount, direction).with_priority_fee(priority_fee_wei)
+        )
     }
 
     pub fn swap_to_price(
@@ -313,21 +599,22 @@ impl<'a, T: V4Network> PoolSwapResult<'a, T> {
     ) -> eyre::Result<PoolSwapResult<'a, T>> {
         let direction = self.end_price >= price_limit;
 
-        let price_swap: PoolSwapResult<'_, T> = PoolSwap {
-            liquidity: self.end_liquidity.clone(),
-            target_price: Some(price_limit),
-            direction,
-            target_amount: I256::MAX,
-            fee_config: self.fee_config,
-            is_bundle: self.is_bundle,
-            mev_tax_amount: None // Don't apply MEV tax to price discovery swap
-        }
-        .swap()?;
+        // Don't apply MEV tax to price discovery swap
+        let price_swap = self.swap_from_end(SwapParams {
+            direction: Some(direction),
+            ..SwapParams::to_price(price_limit)
+        })?;
 
         let amount_in = if direction { price_swap.total_d_t0 } else { price_swap.total_d_t1 };
         let amount = I256::unchecked_from(amount_in);
 
-        self.swap_to_amount_with_mev_tax(amount, direction, priority_fee_wei)
+        // keep the limit on the amount in swap too, like
+        // `BaselinePoolState::swap_current_to_price_with_mev_tax`
+        self.swap_from_end(
+            SwapParams::amount(amount, direction)
+                .with_limit(price_limit)
+                .with_priority_fee(priority_fee_wei)
+        )
     }
 
     pub fn was_empty_swap(&self) -> bool {
@@ -369,26 +656,789 @@ impl<'a, T: V4Network> PoolSwapResult<'a, T> {
     }
 }
 
-/// the step of swapping across this pool
+/// A swap that moves the pool to an external reference price, along with the
+/// profit in T1 of offloading the swap's output at that price
+#[derive(Debug, Clone)]
+pub struct ArbSwapResult<'a, T: V4Network> {
+    pub swap:   PoolSwapResult<'a, T>,
+    pub profit: I256
+}
+
+/// An exact input swap quote, see `BaselinePoolState::quote`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quote {
+    /// input token taken by the swap, fees included
+    pub amount_in:        u128,
+    /// output token received after fees
+    pub amount_out:       u128,
+    /// decimal adjusted execution price, token1 per token0
+    pub effective_price:  Ray,
+    /// how far the swap moves the pool price, in basis points
+    pub price_impact_bps: f64,
+    /// LP fee charged on the input token. Protocol fees are already taken out
+    /// of the amounts
+    pub fee_paid:         u128,
+    pub end_tick:         i32,
+    pub end_price:        SqrtPriceX96
+}
+
+/// Result of replaying a swap event against the local pool state, see
+/// `BaselinePoolState::verify_swap_event`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SwapVerification {
+    pub simulated_price: SqrtPriceX96,
+    pub simulated_tick:  i32,
+    pub reported_price:  SqrtPriceX96,
+    pub reported_tick:   i32
+}
+
+impl SwapVerification {
+    /// Relative difference between the simulated and reported price (not sqrt
+    /// price)
+    pub fn price_delta(&self) -> f64 {
+        let simulated = self.simulated_price.as_f64();
+        let reported = self.reported_price.as_f64();
+        if reported == 0.0 {
+            return if simulated == 0.0 { 0.0 } else { f64::INFINITY };
+        }
+        (simulated - reported).abs() / reported
+    }
+
+    /// Whether the reported price is within `tolerance` (relative) of the
+    /// simulated one
+    pub fn is_consistent(&self, tolerance: f64) -> bool {
+        self.price_delta() <= tolerance
+    }
+}
+
+/// A single step of a swap. Each step swaps against a constant liquidity
+/// range, ending either at the next initialized tick (or word boundary) or
+/// wherever the amount / price limit ran out.
 #[derive(Clone, Debug)]
-pub struct PoolSwapStep {
-    pub end_tick:  i32,
-    pub init:      bool,
-    pub liquidity: u128,
-    pub d_t0:      u128,
-    pub d_t1:      u128
+pub struct SwapStep {
+    /// the direction of the swap this step belongs to.
+    pub zero_for_one:     bool,
+    pub tick_start:       i32,
+    pub tick_end:         i32,
+    pub sqrt_price_start: SqrtPriceX96,
+    pub sqrt_price_end:   SqrtPriceX96,
+    /// whether the tick this step was targeting is initialized.
+    pub init:             bool,
+    /// the liquidity that was swapped against for this step.
+    pub liquidity:        u128,
+    /// amount of the input token going into the pool, excluding the fee.
+    pub amount_in:        u128,
+    /// amount of the output token leaving the pool.
+    pub amount_out:       u128,
+    /// the lp fee charged on the input token for this step.
+    pub fee_amount:       u128
 }
 
-impl PoolSwapStep {
+impl SwapStep {
+    /// the token0 delta for this step, fee inclusive if token0 is the input.
+    pub fn d_t0(&self) -> u128 {
+        if self.zero_for_one { self.amount_in + self.fee_amount } else { self.amount_out }
+    }
+
+    /// the token1 delta for this step, fee inclusive if token1 is the input.
+    pub fn d_t1(&self) -> u128 {
+        if self.zero_for_one { self.amount_out } else { self.amount_in + self.fee_amount }
+    }
+
     pub fn avg_price(&self) -> Option<Ray> {
         if self.empty() {
             None
         } else {
-            Some(Ray::calc_price(U256::from(self.d_t0), U256::from(self.d_t1)))
+            Some(Ray::calc_price(U256::from(self.d_t0()), U256::from(self.d_t1())))
         }
     }
 
     pub fn empty(&self) -> bool {
-        self.d_t0 == 0 || self.d_t1 == 0
+        self.d_t0() == 0 || self.d_t1() == 0
+    }
+
+    /// the initialized tick this step ended by crossing, if it reached one.
+    pub fn crossed_tick(&self) -> Option<i32> {
+        if !self.init {
+            return None;
+        }
+        // crossing down leaves the pool one tick below the one crossed
+        let tick = if self.zero_for_one { self.tick_end + 1 } else { self.tick_end };
+        (SqrtPriceX96::at_tick(tick).ok()? == self.sqrt_price_end).then_some(tick)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_network::Ethereum;
+    use alloy_primitives::{Address, I256, U160};
+
+    use super::{PoolSwap, PoolSwapError, SWAP_BASE_GAS, TICK_CROSSING_GAS};
+    use crate::{
+        fee_config::L1FeeConfiguration,
+        pool_updates::SwapEventData,
+        ray::Ray,
+        sqrt_pricex96::SqrtPriceX96,
+        testing::{l1_fee_config, l1_pool, pool_with_positions},
+        tick_info::{MAX_TICK, MIN_TICK}
+    };
+
+    #[test]
+    fn zero_for_one_steps_sum_to_total() {
+        // no protocol fee, so the totals are exactly the steps' deltas
+        let fee_config =
+            L1FeeConfiguration { bundle_fee: 1000, swap_fee: 3000, protocol_fee: 0 };
+        let pool = pool_with_positions::<Ethereum>(
+            0,
+            &[(-600, 600, 1_000_000_000_000_000_000), (-120, 120, 1_000_000_000_000_000_000)],
+            fee_config
+        );
+        let swap = pool
+            .swap_current_with_amount(I256::unchecked_from(20_000_000_000_000_000u128), true, false)
+            .unwrap();
+
+        // crosses the narrow position at -120.
+        assert!(swap.steps.iter().any(|step| step.tick_end < -120));
+        assert!(swap.steps.iter().map(|step| step.fee_amount).sum::<u128>() > 0);
+
+        let total_in: u128 = swap
+            .steps
+            .iter()
+            .map(|step| step.amount_in + step.fee_amount)
+            .sum();
+        let total_out: u128 = swap.steps.iter().map(|step| step.amount_out).sum();
+        assert_eq!(total_in, swap.total_d_t0);
+        assert_eq!(total_out, swap.total_d_t1);
+
+        for window in swap.steps.windows(2) {
+            assert_eq!(window[0].sqrt_price_end, window[1].sqrt_price_start);
+        }
+    }
+
+    #[test]
+    fn one_sided_pool_only_swaps_towards_its_liquidity() {
+        // the only position sits above the current tick
+        let pool =
+            pool_with_positions::<Ethereum>(0, &[(600, 1200, 10u128.pow(18))], l1_fee_config());
+        assert_eq!(pool.current_liquidity(), 0);
+        let amount = I256::unchecked_from(10u128.pow(15));
+
+        let swap = pool.swap_current_with_amount(amount, false, true).unwrap();
+        assert!(swap.end_tick >= 600);
+        assert!(swap.total_d_t0 > 0);
+
+        let err = pool
+            .swap_current_with_amount(amount, true, true)
+            .unwrap_err();
+        assert_eq!(err.downcast_ref::<PoolSwapError>(), Some(&PoolSwapError::NoLiquidity));
+    }
+
+    #[test]
+    fn verifies_swap_events_against_simulation() {
+        let pool = l1_pool();
+        let swap = pool
+            .swap_current_with_amount(I256::unchecked_from(10u128.pow(16)), true, false)
+            .unwrap();
+        let amount_out: u128 = swap.steps.iter().map(|step| step.amount_out).sum();
+
+        let mut event = SwapEventData {
+            sender:         Address::ZERO,
+            amount0:        -(swap.total_d_t0 as i128),
+            amount1:        amount_out as i128,
+            sqrt_price_x96: swap.end_price.into(),
+            liquidity:      swap.end_liquidity.current_liquidity,
+            tick:           swap.end_tick,
+            fee:            3000
+        };
+
+        let verification = pool.verify_swap_event(&event).unwrap();
+        assert_eq!(verification.simulated_tick, swap.end_tick);
+        assert!(verification.price_delta() < 1e-12, "{verification:?}");
+        assert!(verification.is_consistent(1e-9));
+
+        event.sqrt_price_x96 = SqrtPriceX96::at_tick(swap.end_tick + 100).unwrap().into();
+        let verification = pool.verify_swap_event(&event).unwrap();
+        assert!(verification.price_delta() > 5e-3, "{verification:?}");
+        assert!(!verification.is_consistent(1e-9));
+    }
+
+    #[test]
+    fn rejects_out_of_range_limits() {
+        let pool = l1_pool();
+        let amount = I256::unchecked_from(1_000_000_000_000_000u128);
+        let zero = SqrtPriceX96::default();
+        let above_max = SqrtPriceX96::from(U160::MAX);
+
+        let err = pool
+            .swap_current_with_amount_and_limit(amount, true, true, zero)
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<PoolSwapError>(),
+            Some(&PoolSwapError::InvalidSqrtPriceLimit)
+        );
+        assert!(
+            pool.swap_current_with_amount_and_limit(amount, false, true, above_max)
+                .is_err()
+        );
+
+        assert!(
+            pool.swap_current_with_amount_and_limit(amount, true, true, zero.clamp_to_valid())
+                .is_ok()
+        );
+        assert!(
+            pool.swap_current_with_amount_and_limit(
+                amount,
+                false,
+                true,
+                above_max.clamp_to_valid()
+            )
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn tick_limit_matches_the_sqrt_price_limit() {
+        let pool = l1_pool();
+        let amount = I256::unchecked_from(100_000_000_000_000_000u128);
+
+        for (direction, tick) in [(true, -300), (false, 300)] {
+            let by_tick = pool
+                .swap_current_with_amount_and_tick_limit(amount, direction, false, tick)
+                .unwrap();
+            let by_price = pool
+                .swap_current_with_amount_and_limit(
+                    amount,
+                    direction,
+                    false,
+                    SqrtPriceX96::at_tick(tick).unwrap()
+                )
+                .unwrap();
+
+            assert_eq!(by_tick.end_price, SqrtPriceX96::at_tick(tick).unwrap());
+            assert_eq!(by_tick.end_price, by_price.end_price);
+            assert_eq!(
+                (by_tick.total_d_t0, by_tick.total_d_t1),
+                (by_price.total_d_t0, by_price.total_d_t1)
+            );
+        }
+
+        let err = |direction, tick| {
+            *pool
+                .swap_current_with_amount_and_tick_limit(amount, direction, false, tick)
+                .unwrap_err()
+                .downcast_ref::<PoolSwapError>()
+                .unwrap()
+        };
+        assert_eq!(err(true, 300), PoolSwapError::LimitAgainstDirection);
+        assert_eq!(err(false, -300), PoolSwapError::LimitAgainstDirection);
+        assert_eq!(err(true, MIN_TICK), PoolSwapError::TickLimitOutOfRange);
+        assert_eq!(err(false, MAX_TICK + 1), PoolSwapError::TickLimitOutOfRange);
+    }
+
+    #[test]
+    fn swaps_weth_usdc_to_spot_price() {
+        // token0 WETH (18 decimals), token1 USDC (6 decimals), starting around $1900.
+        let mut pool = pool_with_positions::<alloy_network::Ethereum>(
+            -200820,
+            &[(-201600, -199200, 1_000_000_000_000_000_000)],
+            l1_fee_config()
+        );
+        pool.token1_decimals = 6;
+
+        let swap = pool
+            .swap_current_to_spot_price(Ray::from(2000.0), false)
+            .unwrap();
+
+        assert!(!swap.zero_for_one());
+        let spot = swap.end_price.as_f64() * 1e12;
+        assert!((spot - 2000.0).abs() < 0.01, "ended at {spot}");
+    }
+
+    #[test]
+    fn stable_pool_swap_to_price_stays_within_a_1bps_limit() {
+        let pool = pool_with_positions::<Ethereum>(
+            0,
+            &[(-600, 600, 1_000_000_000_000_000_000_000_000_000)],
+            L1FeeConfiguration { bundle_fee: 0, swap_fee: 100, protocol_fee: 0 }
+        );
+
+        for spot in [0.9999, 1.0001] {
+            let limit = pool.sqrt_price_from_spot(Ray::from(spot)).unwrap();
+            for is_bundle in [true, false] {
+                let swap = pool.swap_current_to_price(limit, is_bundle).unwrap();
+                // and chained from a dust swap that leaves the price in place
+                let dust = pool
+                    .swap_current_with_amount(I256::unchecked_from(1_000), true, is_bundle)
+                    .unwrap();
+                let chained = dust.swap_to_price(limit).unwrap();
+
+                for swap in [swap, chained] {
+                    assert!(swap.total_d_t0 > 0);
+                    if swap.zero_for_one() {
+                        assert!(swap.end_price >= limit, "crossed {spot} bundle {is_bundle}");
+                    } else {
+                        assert!(swap.end_price <= limit, "crossed {spot} bundle {is_bundle}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn arb_buys_token0_below_external_price() {
+        let pool = l1_pool();
+
+        let arb = pool.arb_to_price(Ray::from(1.005), true).unwrap();
+
+        assert!(!arb.swap.zero_for_one());
+        assert!(arb.swap.total_d_t0 > 0);
+        assert!(arb.profit.is_positive());
+        let spot = arb.swap.end_price.as_f64();
+        assert!((spot - 1.005).abs() < 1e-9, "ended at {spot}");
+    }
+
+    #[test]
+    fn quote_matches_the_underlying_swap() {
+        let pool = l1_pool();
+        let amount_in = 20_000_000_000_000_000u128;
+
+        let quote = pool.quote(amount_in, true, false).unwrap();
+        let swap = pool
+            .swap_current_with_amount(I256::unchecked_from(amount_in), true, false)
+            .unwrap();
+
+        assert_eq!(quote.amount_in, swap.total_d_t0);
+        assert_eq!(quote.amount_out, swap.total_d_t1);
+        assert_eq!(quote.end_tick, swap.end_tick);
+        assert_eq!(quote.end_price, swap.end_price);
+        assert_eq!(quote.fee_paid, swap.steps.iter().map(|step| step.fee_amount).sum::<u128>());
+        assert!(quote.fee_paid > 0);
+
+        // selling token0 fills below the spot price, and the pool ends lower still
+        let spot = pool.spot_price().as_f64();
+        let effective = quote.effective_price.as_f64();
+        let end_spot = Ray::from(swap.end_price).as_f64();
+        assert!(end_spot < effective && effective < spot, "{end_spot} {effective} {spot}");
+        let impact = (spot - end_spot) / spot * 10_000.0;
+        assert!((quote.price_impact_bps - impact).abs() < 1e-6);
+    }
+
+    #[test]
+    fn quote_both_modes_charges_each_modes_fees() {
+        let pool = pool_with_positions::<Ethereum>(
+            0,
+            &[(-600, 600, 1_000_000_000_000_000_000)],
+            L1FeeConfiguration { bundle_fee: 1000, swap_fee: 3000, protocol_fee: 500 }
+        );
+        let amount_in = 1_000_000_000_000_000u128;
+
+        let (bundle, unlocked) = pool.quote_both_modes(amount_in, true).unwrap();
+        let bundle = bundle.unwrap();
+        assert_eq!(bundle, pool.quote(amount_in, true, true).unwrap());
+        assert_eq!(unlocked, pool.quote(amount_in, true, false).unwrap());
+
+        // the bundle fee is settled by the bundle, not charged inside the pool
+        assert_eq!(bundle.fee_paid, 0);
+        let higher_bundle_fee = pool_with_positions::<Ethereum>(
+            0,
+            &[(-600, 600, 1_000_000_000_000_000_000)],
+            L1FeeConfiguration { bundle_fee: 5000, swap_fee: 3000, protocol_fee: 500 }
+        );
+        let (other_bundle, _) = higher_bundle_fee.quote_both_modes(amount_in, true).unwrap();
+        assert_eq!(other_bundle, Some(bundle));
+        // unlocked swaps pay the swap fee on the input and the protocol fee on
+        // the output
+        assert_eq!(unlocked.fee_paid, amount_in * 3000 / 1_000_000);
+        let fee_share = 1.0 - unlocked.amount_out as f64 / bundle.amount_out as f64;
+        assert!((fee_share - 0.0035).abs() < 1e-4, "{fee_share}");
+    }
+
+    #[cfg(feature = "l2")]
+    #[test]
+    fn quote_both_modes_has_no_bundle_quote_on_l2() {
+        let pool = pool_with_positions::<op_alloy_network::Optimism>(
+            0,
+            &[(-600, 600, 1_000_000_000_000_000_000)],
+            crate::testing::l2_fee_config(3000)
+        );
+
+        let (bundle, unlocked) = pool.quote_both_modes(1_000_000_000_000_000, false).unwrap();
+
+        assert!(bundle.is_none());
+        assert_eq!(unlocked, pool.quote(1_000_000_000_000_000, false, false).unwrap());
+    }
+
+    #[test]
+    fn crossed_ticks_follow_initialized_ticks() {
+        let pool = pool_with_positions::<Ethereum>(
+            0,
+            &[
+                (-600, 600, 1_000_000_000_000_000_000),
+                (-300, 300, 1_000_000_000_000_000_000),
+                (-120, 120, 1_000_000_000_000_000_000)
+            ],
+            l1_fee_config()
+        );
+
+        let small = pool
+            .swap_current_with_amount(I256::unchecked_from(1_000_000_000_000_000u128), true, false)
+            .unwrap();
+        assert!(small.crossed_ticks().is_empty());
+
+        // stops between -300 and -600
+        let large = pool
+            .swap_current_with_amount(I256::unchecked_from(45_000_000_000_000_000u128), true, false)
+            .unwrap();
+        assert_eq!(large.crossed_ticks(), vec![-120, -300]);
+        assert!(large.end_tick < -300 && large.end_tick > -600);
+
+        let up = pool
+            .swap_current_with_amount(
+                I256::unchecked_from(45_000_000_000_000_000u128),
+                false,
+                false
+            )
+            .unwrap();
+        assert_eq!(up.crossed_ticks(), vec![120, 300]);
+
+        // each crossing adds to the gas estimate
+        assert_eq!(small.estimated_gas(), SWAP_BASE_GAS);
+        assert_eq!(large.estimated_gas(), SWAP_BASE_GAS + 2 * TICK_CROSSING_GAS);
+        let medium = pool
+            .swap_current_with_amount(I256::unchecked_from(25_000_000_000_000_000u128), true, false)
+            .unwrap();
+        assert_eq!(medium.crossed_ticks(), vec![-120]);
+        assert!(small.estimated_gas() < medium.estimated_gas());
+        assert!(medium.estimated_gas() < large.estimated_gas());
+        assert_eq!(large.estimated_gas_with(100, 10), 120);
+    }
+
+    #[test]
+    fn liquidity_override_leaves_the_pool_alone() {
+        let pool = l1_pool();
+        let doubled = pool_with_positions::<Ethereum>(
+            0,
+            &[(-600, 600, 2_000_000_000_000_000_000), (-120, 120, 2_000_000_000_000_000_000)],
+            l1_fee_config()
+        );
+        let amount = I256::unchecked_from(20_000_000_000_000_000u128);
+
+        let real = pool.swap_current_with_amount(amount, true, false).unwrap();
+        let same = pool
+            .swap_with_liquidity_override(pool.liquidity(), amount, true, false)
+            .unwrap();
+        assert_eq!(same.total_d_t1, real.total_d_t1);
+
+        // twice the depth moves the price less and fills more of the output
+        let deeper = pool
+            .swap_with_liquidity_override(doubled.liquidity(), amount, true, false)
+            .unwrap();
+        assert_eq!(deeper.total_d_t0, real.total_d_t0);
+        assert!(deeper.total_d_t1 > real.total_d_t1);
+        assert!(deeper.end_price > real.end_price);
+        assert_eq!(pool.current_tick(), 0);
+    }
+
+    #[cfg(feature = "l2")]
+    #[test]
+    fn l2_swap_carries_its_mev_tax() {
+        use crate::{
+            fee_config::{L2_SWAP_MEV_TAX_FACTOR, L2_SWAP_TAXED_GAS},
+            testing::l2_fee_config
+        };
+
+        let mut fee_config = l2_fee_config(3000);
+        fee_config.priority_fee_tax_floor = 1_000;
+        let pool = pool_with_positions::<op_alloy_network::Optimism>(
+            0,
+            &[(-600, 600, 1_000_000_000_000_000_000)],
+            fee_config
+        );
+        let priority_fee = 2_000_000u128;
+        let amount = I256::unchecked_from(10_000_000_000_000_000u128);
+
+        let taxed = pool
+            .swap_current_with_amount_and_mev_tax(amount, true, false, Some(priority_fee))
+            .unwrap();
+        let expected = L2_SWAP_MEV_TAX_FACTOR * L2_SWAP_TAXED_GAS * (priority_fee - 1_000);
+        assert_eq!(taxed.mev_tax_charged, Some(expected));
+        assert_eq!(taxed.estimated_l2_tax(priority_fee), expected);
+
+        // the tax comes out of the input before the AMM
+        let untaxed = pool.swap_current_with_amount(amount, true, false).unwrap();
+        assert_eq!(untaxed.mev_tax_charged, None);
+        assert!(taxed.total_d_t1 < untaxed.total_d_t1);
+
+        // at or below the floor there is nothing to attach
+        for priority_fee in [1_000, 999, 0] {
+            let below_floor = pool
+                .swap_current_with_amount_and_mev_tax(amount, true, false, Some(priority_fee))
+                .unwrap();
+            assert_eq!(below_floor.mev_tax_charged, None);
+            assert_eq!(below_floor.estimated_l2_tax(priority_fee), 0);
+            assert_eq!(below_floor.total_d_t1, untaxed.total_d_t1);
+        }
+    }
+
+    #[cfg(feature = "l2")]
+    #[test]
+    fn swap_params_combine_exact_out_limit_and_mev_tax() {
+        use super::SwapParams;
+
+        let pool = pool_with_positions::<op_alloy_network::Optimism>(
+            0,
+            &[(-600, 600, 1_000_000_000_000_000_000)],
+            crate::testing::l2_fee_config(3000)
+        );
+        let priority_fee = Some(2_000_000u128);
+        let exact_out = I256::unchecked_from(-5_000_000_000_000_000i128);
+
+        // exact output never pays the tax, so a fee that would be charged is
+        // rejected rather than ignored
+        assert_eq!(
+            pool.swap(SwapParams::amount(exact_out, true).with_priority_fee(priority_fee))
+                .unwrap_err()
+                .downcast_ref::<PoolSwapError>(),
+            Some(&PoolSwapError::MevTaxNotApplicable)
+        );
+        let plain = pool.swap(SwapParams::amount(exact_out, true)).unwrap();
+        assert_eq!(plain.total_d_t1, 5_000_000_000_000_000);
+
+        // a limit short of the full output stops it early
+        let limit = SqrtPriceX96::at_tick(-30).unwrap();
+        let limited = pool
+            .swap(SwapParams::amount(exact_out, true).with_limit(limit))
+            .unwrap();
+        assert_eq!(limited.end_price, limit);
+        assert!(limited.amount_filled_before_limit < 5_000_000_000_000_000);
+
+        // the named methods are the same swaps
+        let exact_in = I256::unchecked_from(10_000_000_000_000_000u128);
+        let named = pool
+            .swap_current_with_amount_and_mev_tax(exact_in, true, false, priority_fee)
+            .unwrap();
+        let params = pool
+            .swap(SwapParams::amount(exact_in, true).with_priority_fee(priority_fee))
+            .unwrap();
+        assert!(params.mev_tax_charged.is_some());
+        assert_eq!(params.mev_tax_charged, named.mev_tax_charged);
+        assert_eq!(params.total_d_t1, named.total_d_t1);
+
+        assert_eq!(
+            pool.swap(SwapParams::default())
+                .unwrap_err()
+                .downcast_ref::<PoolSwapError>(),
+            Some(&PoolSwapError::MissingTarget)
+        );
+    }
+
+    #[cfg(feature = "l2")]
+    #[test]
+    fn exact_output_after_mev_tax_hits_the_target() {
+        use super::PoolSwapResult;
+        use crate::fee_config::FeeConfig;
+
+        let mut fee_config = crate::testing::l2_fee_config(3000);
+        fee_config.protocol_swap_fee_e6 = 500;
+        let pool = pool_with_positions::<op_alloy_network::Optimism>(
+            0,
+            &[(-600, 600, 1_000_000_000_000_000_000)],
+            fee_config
+        );
+        let priority_fee = 2_000_000u128;
+        let tax = pool.fee_config().mev_tax(priority_fee);
+        let target = 5_000_000_000_000_000u128;
+
+        // ETH in pays the tax from the input, ETH out from the output
+        for direction in [true, false] {
+            let swap = pool
+                .swap_for_exact_output_with_mev_tax(target, direction, false, priority_fee)
+                .unwrap();
+            let net_out = |swap: &PoolSwapResult<'_, _>| {
+                if direction { swap.total_d_t1 } else { swap.total_d_t0 }
+            };
+            assert_eq!(swap.mev_tax_charged, Some(tax));
+            assert_eq!(net_out(&swap), target);
+
+            // and one wei less input falls short
+            let amount_in = I256::unchecked_from(swap.amount_filled_before_limit - 1);
+            let short = pool
+                .swap_current_with_amount_and_mev_tax(
+                    amount_in,
+                    direction,
+                    false,
+                    Some(priority_fee)
+                )
+                .unwrap();
+            assert!(net_out(&short) < target);
+        }
+
+        // without a tax to pay it is a plain exact output swap
+        let untaxed = pool
+            .swap_for_exact_output_with_mev_tax(target, true, false, 0)
+            .unwrap();
+        assert_eq!(untaxed.mev_tax_charged, None);
+        assert_eq!(untaxed.total_d_t1, target);
+
+        // more than the loaded ticks hold can't be filled
+        assert!(
+            pool.swap_for_exact_output_with_mev_tax(target * 1_000, true, false, priority_fee)
+                .is_err()
+        );
+    }
+
+    #[cfg(feature = "l2")]
+    #[test]
+    fn withdraw_only_pool_refuses_swaps() {
+        let mut fee_config = crate::testing::l2_fee_config(3000);
+        fee_config.withdraw_only = true;
+        let pool = pool_with_positions::<op_alloy_network::Optimism>(
+            0,
+            &[(-600, 600, 1_000_000_000_000_000_000)],
+            fee_config
+        );
+        let amount = I256::unchecked_from(1_000_000_000_000_000u128);
+        let is_withdraw_only = |err: eyre::Report| {
+            err.downcast_ref::<PoolSwapError>() == Some(&PoolSwapError::WithdrawOnly)
+        };
+
+        assert_eq!(pool.can_swap(), Err(PoolSwapError::WithdrawOnly));
+        assert!(is_withdraw_only(
+            pool.swap_current_with_amount(amount, true, false)
+                .unwrap_err()
+        ));
+        assert!(is_withdraw_only(
+            pool.swap_current_with_amount_and_mev_tax(amount, false, false, Some(1))
+                .unwrap_err()
+        ));
+        assert!(is_withdraw_only(
+            pool.swap_current_to_price(SqrtPriceX96::at_tick(-60).unwrap(), false)
+                .unwrap_err()
+        ));
+        assert!(is_withdraw_only(
+            pool.swap_with_liquidity_override(pool.liquidity(), amount, true, false)
+                .unwrap_err()
+        ));
+        // chained from where an earlier swap ended
+        let noop = pool.noop();
+        assert!(is_withdraw_only(noop.swap_to_amount(amount, true).unwrap_err()));
+        assert!(is_withdraw_only(
+            noop.swap_to_price(SqrtPriceX96::at_tick(-60).unwrap())
+                .unwrap_err()
+        ));
+    }
+
+    #[test]
+    fn builder_matches_the_pool_entry_points() {
+        let pool = l1_pool();
+        let amount = I256::unchecked_from(10_000_000_000_000_000u128);
+        let limit = SqrtPriceX96::at_tick(-30).unwrap();
+
+        let built = PoolSwap::<Ethereum>::builder(pool.liquidity().current(), *pool.fee_config())
+            .amount(amount)
+            .direction(true)
+            .price_limit(limit)
+            .swap()
+            .unwrap();
+        let direct = pool
+            .swap_current_with_amount_and_limit(amount, true, false, limit)
+            .unwrap();
+        assert_eq!((built.total_d_t0, built.total_d_t1), (direct.total_d_t0, direct.total_d_t1));
+        assert_eq!(built.end_price, limit);
+
+        // a price limit alone infers the direction and swaps all the way to it
+        let to_price =
+            PoolSwap::<Ethereum>::builder(pool.liquidity().current(), *pool.fee_config())
+                .price_limit(SqrtPriceX96::at_tick(30).unwrap())
+                .swap()
+                .unwrap();
+        assert!(!to_price.zero_for_one());
+        assert_eq!(to_price.end_tick, 30);
+    }
+
+    #[test]
+    fn builder_rejects_invalid_combinations() {
+        let pool = l1_pool();
+        let builder =
+            || PoolSwap::<Ethereum>::builder(pool.liquidity().current(), *pool.fee_config());
+        let exact_out = I256::unchecked_from(-1_000_000_000_000_000i128);
+
+        assert_eq!(builder().build().unwrap_err(), PoolSwapError::MissingTarget);
+        assert_eq!(
+            builder().amount(exact_out).build().unwrap_err(),
+            PoolSwapError::MissingDirection
+        );
+        // exact out selling token0 with a limit above the current price
+        assert_eq!(
+            builder()
+                .amount(exact_out)
+                .direction(true)
+                .price_limit(SqrtPriceX96::at_tick(60).unwrap())
+                .build()
+                .unwrap_err(),
+            PoolSwapError::LimitAgainstDirection
+        );
+        // L1 pools never pay MEV tax
+        assert_eq!(
+            builder()
+                .amount(exact_out.abs())
+                .direction(true)
+                .mev_tax(1)
+                .build()
+                .unwrap_err(),
+            PoolSwapError::MevTaxNotApplicable
+        );
+
+        assert!(
+            builder()
+                .amount(exact_out)
+                .direction(true)
+                .price_limit(SqrtPriceX96::at_tick(-60).unwrap())
+                .build()
+                .is_ok()
+        );
+    }
+
+    #[cfg(feature = "l2")]
+    #[test]
+    fn limit_bound_swap_taxes_the_filled_eth() {
+        use crate::fee_config::FeeConfig;
+
+        let pool = pool_with_positions::<op_alloy_network::Optimism>(
+            0,
+            &[(-600, 600, 1_000_000_000_000_000_000)],
+            crate::testing::l2_fee_config(3000)
+        );
+        let amount = I256::unchecked_from(100_000_000_000_000_000u128);
+        let limit = SqrtPriceX96::at_tick(30).unwrap();
+        let swap = |priority_fee| {
+            pool.swap_current_with_amount_limit_and_mev_tax(
+                amount,
+                false,
+                false,
+                limit,
+                Some(priority_fee)
+            )
+            .unwrap()
+        };
+
+        // the limit binds well before the whole amount of token1 is sold
+        let taxed = swap(1_000_000);
+        let eth_filled: u128 = taxed.steps.iter().map(|step| step.d_t0()).sum();
+        assert_eq!(taxed.end_price, limit);
+        assert!(taxed.amount_filled_before_limit < amount.unsigned_abs().to::<u128>());
+        assert_eq!(taxed.amount_filled_before_limit, taxed.total_d_t1);
+
+        let tax = pool.fee_config().mev_tax(1_000_000);
+        assert!(tax < eth_filled);
+        assert_eq!(taxed.mev_tax_charged, Some(tax));
+        assert_eq!(taxed.total_d_t0, eth_filled - tax);
+
+        // a tax larger than the ETH the partial fill produced takes all of it
+        let over_taxed = swap(1_000_000_000);
+        let eth_filled: u128 = over_taxed.steps.iter().map(|step| step.d_t0()).sum();
+        assert!(pool.fee_config().mev_tax(1_000_000_000) > eth_filled);
+        assert_eq!(over_taxed.mev_tax_charged, Some(eth_filled));
+        assert_eq!(over_taxed.total_d_t0, 0);
     }
 }
@@ -1,4 +1,4 @@
-use std::{cmp::Ordering, collections::HashMap};
+use std::{cmp::Ordering, collections::HashMap, fmt};
 
 use alloy_network::Ethereum;
 use alloy_primitives::{Address, B256, I256, U160, U256};
@@ -23,6 +23,13 @@ pub trait UpdatePool<T: V4Network>: Clone + Send + Sync + Unpin {
 
     /// whether event initialization-related updates
     fn is_initialization_event(&self) -> bool;
+
+    /// whether the event removes the pool
+    fn is_removal_event(&self) -> bool;
+
+    /// `(block, tx_index, log_index)` of the log this update came from, `None`
+    /// when it doesn't come from an onchain log
+    fn position(&self) -> Option<(u64, u64, u64)>;
 }
 
 /// Different types of pool updates
@@ -68,9 +75,20 @@ pub enum PoolUpdate<T: V4Network> {
 
     /// Fee update event. the pool_id here is the uniswap pool_id
     FeeUpdate {
-        pool_id: PoolId,
-        block:   u64,
-        update:  <T::FeeConfig as FeeConfig>::Update
+        pool_id:   PoolId,
+        block:     u64,
+        tx_index:  u64,
+        log_index: u64,
+        update:    <T::FeeConfig as FeeConfig>::Update
+    },
+
+    /// A tick bitmap word changed from `old` to `new`. Emitted for replicas
+    /// mirroring tick state, applying it sets the word to `new`.
+    TickBitmapDelta {
+        pool_id:  PoolId,
+        word_pos: i16,
+        old:      U256,
+        new:      U256
     },
 
     /// Updated slot0 data after reorg
@@ -86,22 +104,65 @@ pub enum PoolUpdate<T: V4Network> {
 }
 
 impl<T: V4Network> PoolUpdate<T> {
+    /// Total ordering used when applying a batch of updates:
+    ///
+    /// 1. `NewBlock` and `Reorg` markers come first, ahead of the events of
+    ///    their block.
+    /// 2. Swap, liquidity and fee events, plus chain specific updates that
+    ///    carry a log position, follow in chain order, by block, then
+    ///    transaction index, then log index.
+    /// 3. Everything else (slot0 refreshes, new ticks, new pool state and chain
+    ///    specific updates without a position) comes last.
+    ///
+    /// Updates that compare equal keep their relative order under a stable
+    /// sort, which is what keeps a reorg's inverse events ahead of the
+    /// re-fetched events that replace them.
     pub fn sort(&self, b: &Self) -> Ordering {
-        let (this_tx_index, this_log_index) = match self {
-            PoolUpdate::SwapEvent { tx_index, log_index, .. } => (*tx_index, *log_index),
-            PoolUpdate::LiquidityEvent { tx_index, log_index, .. } => (*tx_index, *log_index),
-            _ => (u64::MAX, u64::MAX)
-        };
+        self.sort_key().cmp(&b.sort_key())
+    }
 
-        let (other_tx_index, other_log_index) = match b {
-            PoolUpdate::SwapEvent { tx_index, log_index, .. } => (*tx_index, *log_index),
-            PoolUpdate::LiquidityEvent { tx_index, log_index, .. } => (*tx_index, *log_index),
-            _ => (u64::MAX, u64::MAX)
-        };
+    fn sort_key(&self) -> (u8, u64, u64, u64) {
+        match self {
+            PoolUpdate::NewBlock(_) | PoolUpdate::Reorg { .. } => (0, 0, 0, 0),
+            PoolUpdate::SwapEvent { block, tx_index, log_index, .. }
+            | PoolUpdate::LiquidityEvent { block, tx_index, log_index, .. }
+            | PoolUpdate::FeeUpdate { block, tx_index, log_index, .. } => {
+                (1, *block, *tx_index, *log_index)
+            }
+            PoolUpdate::ChainSpecific { update, .. } => update
+                .position()
+                .map_or((2, 0, 0, 0), |(block, tx_index, log_index)| {
+                    (1, block, tx_index, log_index)
+                }),
+            _ => (2, 0, 0, 0)
+        }
+    }
 
-        this_tx_index
-            .cmp(&other_tx_index)
-            .then_with(|| this_log_index.cmp(&other_log_index))
+    /// The pool this update belongs to, `None` for block level markers
+    pub fn pool_id(&self) -> Option<PoolId> {
+        match self {
+            PoolUpdate::NewBlock(_) | PoolUpdate::Reorg { .. } => None,
+            PoolUpdate::SwapEvent { pool_id, .. }
+            | PoolUpdate::LiquidityEvent { pool_id, .. }
+            | PoolUpdate::NewTicks { pool_id, .. }
+            | PoolUpdate::TickBitmapDelta { pool_id, .. }
+            | PoolUpdate::NewPoolState { pool_id, .. }
+            | PoolUpdate::FeeUpdate { pool_id, .. }
+            | PoolUpdate::UpdatedSlot0 { pool_id, .. }
+            | PoolUpdate::ChainSpecific { pool_id, .. } => Some(*pool_id)
+        }
+    }
+
+    /// The block of the log this update came from, `None` when it doesn't
+    /// come from an onchain log
+    pub fn block(&self) -> Option<u64> {
+        match self {
+            PoolUpdate::SwapEvent { block, .. }
+            | PoolUpdate::LiquidityEvent { block, .. }
+            | PoolUpdate::FeeUpdate { block, .. } => Some(*block),
+            PoolUpdate::ChainSpecific { update, .. } => update.position().map(|(block, ..)| block),
+            _ => None
+        }
     }
 
     // Helper constructors
@@ -128,9 +189,63 @@ impl<T: V4Network> PoolUpdate<T> {
     pub fn from_fee_update(
         pool_id: PoolId,
         block: u64,
+        tx_index: u64,
+        log_index: u64,
         update: <T::FeeConfig as FeeConfig>::Update
     ) -> Self {
-        PoolUpdate::FeeUpdate { pool_id, block, update }
+        PoolUpdate::FeeUpdate { pool_id, block, tx_index, log_index, update }
+    }
+}
+
+/// A one line summary for logs. Pool ids are shortened and log positions are
+/// written as `block:tx_index:log_index`.
+impl<T: V4Network> fmt::Display for PoolUpdate<T>
+where
+    T::PoolUpdate: fmt::Debug
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolUpdate::NewBlock(block) => write!(f, "new block {block}"),
+            PoolUpdate::SwapEvent { pool_id, block, tx_index, log_index, event } => write!(
+                f,
+                "swap in pool {pool_id:#} at {block}:{tx_index}:{log_index}, amounts ({}, {}), \
+                 tick {}",
+                event.amount0, event.amount1, event.tick
+            ),
+            PoolUpdate::LiquidityEvent { pool_id, block, tx_index, log_index, event } => write!(
+                f,
+                "liquidity change in pool {pool_id:#} at {block}:{tx_index}:{log_index}, delta {} \
+                 over [{}, {}]",
+                event.liquidity_delta, event.tick_lower, event.tick_upper
+            ),
+            PoolUpdate::Reorg { from_block, to_block } => {
+                write!(f, "reorg of blocks {from_block} to {to_block}")
+            }
+            PoolUpdate::NewTicks { pool_id, ticks, .. } => {
+                write!(f, "{} ticks loaded for pool {pool_id:#}", ticks.len())
+            }
+            PoolUpdate::NewPoolState { pool_id, state } => write!(
+                f,
+                "new pool {pool_id:#} at block {}, tick {}",
+                state.block_number(),
+                state.current_tick()
+            ),
+            PoolUpdate::TickBitmapDelta { pool_id, word_pos, .. } => {
+                write!(f, "tick bitmap word {word_pos} of pool {pool_id:#} changed")
+            }
+            PoolUpdate::FeeUpdate { pool_id, block, tx_index, log_index, update } => write!(
+                f,
+                "fee update for pool {pool_id:#} at {block}:{tx_index}:{log_index}: {update:?}"
+            ),
+            PoolUpdate::UpdatedSlot0 { pool_id, data } => write!(
+                f,
+                "slot0 of pool {pool_id:#}: tick {}, sqrt price {}, liquidity {}",
+                data.tick, data.sqrt_price_x96, data.liquidity
+            ),
+            PoolUpdate::ChainSpecific { pool_id, update } => {
+                write!(f, "chain specific update for pool {pool_id:#}: {update:?}")
+            }
+        }
     }
 }
 
@@ -168,14 +283,19 @@ pub struct Slot0Data {
 #[derive(Debug, Clone)]
 pub enum L1PoolUpdate {
     NewPool {
-        pool_id:      B256,
-        token0:       Address,
-        token1:       Address,
-        bundle_fee:   u32,
-        swap_fee:     u32,
-        protocol_fee: u32,
-        tick_spacing: i32,
-        block:        u64
+        pool_id:       B256,
+        token0:        Address,
+        token1:        Address,
+        bundle_fee:    u32,
+        swap_fee:      u32,
+        protocol_fee:  u32,
+        tick_spacing:  i32,
+        /// The price the pool was initialized at, when its `Initialize` event
+        /// was fetched along with the configuration
+        initial_slot0: Option<Slot0Data>,
+        block:         u64,
+        tx_index:      u64,
+        log_index:     u64
     },
 
     // From slot0 stream
@@ -183,7 +303,7 @@ pub enum L1PoolUpdate {
     Slot0Update(Slot0Update),
 
     /// Pool removed via controller
-    PoolRemoved { pool_id: B256, block: u64 }
+    PoolRemoved { pool_id: B256, block: u64, tx_index: u64, log_index: u64 }
 }
 
 /// Slot0 update from real-time feed
@@ -216,6 +336,7 @@ impl UpdatePool<Ethereum> for L1PoolUpdate {
     fn update_pool(&self, pool: &mut BaselinePoolState<Ethereum>) {
         if let L1PoolUpdate::Slot0Update(update) = self {
             pool.update_slot0(update.tick, update.sqrt_price_x96.into(), update.liquidity);
+            pool.last_slot0_update = Some(Box::new(update.clone()));
         }
     }
 
@@ -233,4 +354,222 @@ impl UpdatePool<Ethereum> for L1PoolUpdate {
             L1PoolUpdate::Slot0Update(_) => false
         }
     }
+
+    fn is_removal_event(&self) -> bool {
+        matches!(self, L1PoolUpdate::PoolRemoved { .. })
+    }
+
+    fn position(&self) -> Option<(u64, u64, u64)> {
+        match self {
+            L1PoolUpdate::NewPool { block, tx_index, log_index, .. }
+            | L1PoolUpdate::PoolRemoved { block, tx_index, log_index, .. } => {
+                Some((*block, *tx_index, *log_index))
+            }
+            L1PoolUpdate::Slot0Update(_) => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_network::Ethereum;
+    use alloy_primitives::{Address, B256, I256, U160};
+
+    use super::{
+        L1PoolUpdate, ModifyLiquidityEventData, PoolUpdate, Slot0Data, Slot0Update, SwapEventData,
+        UpdatePool
+    };
+    use crate::{fee_config::L1FeeUpdate, testing::l1_pool};
+
+    const POOL: B256 = B256::ZERO;
+
+    fn swap(block: u64, tx_index: u64, log_index: u64) -> PoolUpdate<Ethereum> {
+        PoolUpdate::from_swap(
+            POOL,
+            block,
+            tx_index,
+            log_index,
+            SwapEventData {
+                sender:         Address::ZERO,
+                amount0:        0,
+                amount1:        0,
+                sqrt_price_x96: U160::ZERO,
+                liquidity:      0,
+                tick:           0,
+                fee:            0
+            }
+        )
+    }
+
+    fn liquidity(block: u64, tx_index: u64, log_index: u64, delta: i64) -> PoolUpdate<Ethereum> {
+        PoolUpdate::from_liquidity(
+            POOL,
+            block,
+            tx_index,
+            log_index,
+            ModifyLiquidityEventData {
+                sender:          Address::ZERO,
+                tick_lower:      0,
+                tick_upper:      0,
+                liquidity_delta: I256::try_from(delta).unwrap(),
+                salt:            [0; 32]
+            }
+        )
+    }
+
+    fn fee_update(block: u64, tx_index: u64, log_index: u64) -> PoolUpdate<Ethereum> {
+        PoolUpdate::from_fee_update(
+            POOL,
+            block,
+            tx_index,
+            log_index,
+            L1FeeUpdate { bundle_fee: 0, swap_fee: 0, protocol_fee: 0 }
+        )
+    }
+
+    fn position(update: &PoolUpdate<Ethereum>) -> Option<(u64, u64, u64)> {
+        match update {
+            PoolUpdate::SwapEvent { block, tx_index, log_index, .. }
+            | PoolUpdate::LiquidityEvent { block, tx_index, log_index, .. }
+            | PoolUpdate::FeeUpdate { block, tx_index, log_index, .. } => {
+                Some((*block, *tx_index, *log_index))
+            }
+            PoolUpdate::ChainSpecific { update, .. } => update.position(),
+            _ => None
+        }
+    }
+
+    #[test]
+    fn sort_orders_markers_events_then_state() {
+        let mut updates = [
+            PoolUpdate::UpdatedSlot0 {
+                pool_id: POOL,
+                data:    Slot0Data {
+                    sqrt_price_x96: U160::ZERO,
+                    tick:           0,
+                    liquidity:      0
+                }
+            },
+            swap(11, 0, 1),
+            fee_update(10, 4, 0),
+            liquidity(10, 3, 7, -5),
+            swap(10, 0, 0),
+            liquidity(10, 3, 7, 5),
+            PoolUpdate::Reorg { from_block: 1, to_block: 11 },
+            PoolUpdate::NewBlock(11)
+        ];
+        updates.sort_by(|a, b| a.sort(b));
+
+        assert!(matches!(updates[0], PoolUpdate::Reorg { .. }));
+        assert!(matches!(updates[1], PoolUpdate::NewBlock(11)));
+
+        let positions = updates[2..5]
+            .iter()
+            .map(|update| position(update).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(positions, vec![(10, 0, 0), (10, 3, 7), (10, 3, 7)]);
+
+        // equal positions keep their order, so an inverse event stays ahead of
+        // its replacement
+        let PoolUpdate::LiquidityEvent { event, .. } = &updates[3] else { unreachable!() };
+        assert!(event.liquidity_delta.is_negative());
+
+        // the fee update's transaction comes after the block's other events
+        assert!(matches!(updates[5], PoolUpdate::FeeUpdate { block: 10, .. }));
+        assert!(matches!(updates[6], PoolUpdate::SwapEvent { block: 11, .. }));
+        assert!(matches!(updates[7], PoolUpdate::UpdatedSlot0 { .. }));
+    }
+
+    #[test]
+    fn fee_and_pool_updates_interleave_with_swaps() {
+        let new_pool = PoolUpdate::ChainSpecific {
+            pool_id: POOL,
+            update:  L1PoolUpdate::NewPool {
+                pool_id:       POOL,
+                token0:        Address::ZERO,
+                token1:        Address::ZERO,
+                bundle_fee:    0,
+                swap_fee:      0,
+                protocol_fee:  0,
+                tick_spacing:  60,
+                initial_slot0: None,
+                block:         10,
+                tx_index:      0,
+                log_index:     0
+            }
+        };
+        let mut updates =
+            [swap(10, 3, 9), fee_update(10, 2, 5), swap(10, 1, 2), new_pool, fee_update(10, 3, 4)];
+        updates.sort_by(|a, b| a.sort(b));
+
+        let positions = updates.iter().map(|update| position(update).unwrap());
+        assert!(positions.is_sorted());
+        assert!(matches!(updates[0], PoolUpdate::ChainSpecific { .. }));
+        assert!(matches!(updates[1], PoolUpdate::SwapEvent { tx_index: 1, .. }));
+        assert!(matches!(updates[2], PoolUpdate::FeeUpdate { tx_index: 2, .. }));
+        assert!(matches!(updates[3], PoolUpdate::FeeUpdate { tx_index: 3, .. }));
+        assert!(matches!(updates[4], PoolUpdate::SwapEvent { tx_index: 3, .. }));
+    }
+
+    #[test]
+    fn last_slot0_update_tracks_the_feed() {
+        let mut pool = l1_pool();
+        assert_eq!(pool.last_slot0_update(), None);
+
+        let slot0 = |seq_id, tick| Slot0Update {
+            seq_id,
+            current_block: 12,
+            angstrom_pool_id: POOL,
+            uni_pool_id: POOL,
+            sqrt_price_x96: U160::from(1u128 << 96),
+            liquidity: 5,
+            tick
+        };
+        for update in [slot0(3, 60), slot0(4, -60)] {
+            pool.update_chain_specific(L1PoolUpdate::Slot0Update(update));
+        }
+
+        assert_eq!(pool.last_slot0_update(), Some(&slot0(4, -60)));
+        assert_eq!(pool.current_tick(), -60);
+    }
+
+    #[test]
+    fn display_summarizes_each_update() {
+        let summary = |update: PoolUpdate<Ethereum>| update.to_string();
+
+        assert_eq!(summary(PoolUpdate::NewBlock(7)), "new block 7");
+        assert_eq!(
+            summary(PoolUpdate::Reorg { from_block: 5, to_block: 7 }),
+            "reorg of blocks 5 to 7"
+        );
+
+        let swap = summary(swap(10, 1, 2));
+        assert!(swap.starts_with("swap in pool 0x0000…0000"));
+        assert!(swap.contains("at 10:1:2"));
+
+        let liquidity = summary(liquidity(11, 0, 3, -500));
+        assert!(liquidity.contains("at 11:0:3"));
+        assert!(liquidity.contains("delta -500"));
+
+        let slot0 = summary(PoolUpdate::UpdatedSlot0 {
+            pool_id: B256::repeat_byte(0xab),
+            data:    Slot0Data {
+                sqrt_price_x96: U160::from(42),
+                tick:           -60,
+                liquidity:      9
+            }
+        });
+        assert_eq!(slot0, "slot0 of pool 0xabab…abab: tick -60, sqrt price 42, liquidity 9");
+
+        let removed = summary(PoolUpdate::ChainSpecific {
+            pool_id: POOL,
+            update:  L1PoolUpdate::PoolRemoved {
+                pool_id:   POOL,
+                block:     3,
+                tx_index:  0,
+                log_index: 0
+            }
+        });
+        assert!(removed.contains("PoolRemoved"));
+    }
 }
@@ -0,0 +1,99 @@
+use alloy_primitives::Address;
+
+use crate::ray::Ray;
+
+/// One side of a labelled spot price
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PriceToken {
+    /// The chain's native token, standing in for its wrapped version (WETH)
+    Native,
+    Erc20(Address)
+}
+
+impl PriceToken {
+    fn from_address(token: Address, native_token: Option<Address>) -> Self {
+        if native_token == Some(token) { Self::Native } else { Self::Erc20(token) }
+    }
+}
+
+/// A decimal adjusted spot price, `price` units of `quote` per unit of `base`.
+///
+/// When the pool holds the configured native token it is always the base, so
+/// an ETH pair reads as "X per ETH" regardless of which side WETH sorts on.
+/// Otherwise the pool's token0 is the base.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LabelledSpotPrice {
+    pub base:  PriceToken,
+    pub quote: PriceToken,
+    pub price: Ray
+}
+
+impl LabelledSpotPrice {
+    /// Labels `price` (token1 per token0), flipping it when `native_token` is
+    /// token1
+    pub fn new(
+        token0: Address,
+        token1: Address,
+        price: Ray,
+        native_token: Option<Address>
+    ) -> Self {
+        let base = PriceToken::from_address(token0, native_token);
+        let quote = PriceToken::from_address(token1, native_token);
+
+        if quote == PriceToken::Native {
+            Self { base: quote, quote: base, price: price.inv_ray() }
+        } else {
+            Self { base, quote, price }
+        }
+    }
+
+    /// Whether the native token is one side of the price
+    pub fn is_native(&self) -> bool {
+        self.base == PriceToken::Native
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_network::Ethereum;
+    use alloy_primitives::Address;
+
+    use super::{LabelledSpotPrice, PriceToken};
+    use crate::{
+        ray::Ray,
+        testing::{l1_fee_config, pool_with_positions}
+    };
+
+    #[test]
+    fn weth_token0_pool_is_priced_per_eth() {
+        // token0 WETH (18 decimals), token1 USDC (6 decimals), around $1900
+        let mut pool = pool_with_positions::<Ethereum>(
+            -200820,
+            &[(-201600, -199200, 1_000_000_000_000_000_000)],
+            l1_fee_config()
+        );
+        pool.token1_decimals = 6;
+        let (weth, usdc) = (pool.token0, pool.token1);
+
+        let labelled = pool.labelled_spot_price(Some(weth));
+        assert_eq!(labelled.base, PriceToken::Native);
+        assert_eq!(labelled.quote, PriceToken::Erc20(usdc));
+        assert!(labelled.is_native());
+        assert!((labelled.price.as_f64() - 1900.0).abs() < 5.0, "{}", labelled.price.as_f64());
+
+        let unlabelled = pool.labelled_spot_price(None);
+        assert_eq!(unlabelled.base, PriceToken::Erc20(weth));
+        assert_eq!(unlabelled.price, labelled.price);
+    }
+
+    #[test]
+    fn native_token1_is_flipped_to_base() {
+        let (token0, weth) = (Address::with_last_byte(1), Address::with_last_byte(2));
+
+        let labelled = LabelledSpotPrice::new(token0, weth, Ray::from(0.5), Some(weth));
+
+        assert_eq!(labelled.base, PriceToken::Native);
+        assert_eq!(labelled.quote, PriceToken::Erc20(token0));
+        assert!((labelled.price.as_f64() - 2.0).abs() < 1e-12);
+    }
+}
@@ -9,6 +9,7 @@ use malachite::{
     }
 };
 use serde::{Deserialize, Serialize};
+pub use uniswap_v3_math::tick_math::{MAX_SQRT_RATIO, MIN_SQRT_RATIO};
 use uniswap_v3_math::tick_math::{get_sqrt_ratio_at_tick, get_tick_at_sqrt_ratio};
 
 use super::ray::{Ray, const_1e27, const_2_192};
@@ -17,6 +18,29 @@ use super::ray::{Ray, const_1e27, const_2_192};
 pub struct SqrtPriceX96(U160);
 
 impl SqrtPriceX96 {
+    /// The lowest SqrtPriceX96 the protocol can represent, the price at
+    /// `MIN_TICK`
+    pub fn min() -> Self {
+        Self::from(MIN_SQRT_RATIO)
+    }
+
+    /// The highest SqrtPriceX96 the protocol can represent, the price at
+    /// `MAX_TICK`
+    pub fn max() -> Self {
+        Self::from(MAX_SQRT_RATIO)
+    }
+
+    /// Whether this price can be used as a swap's price limit, which requires
+    /// it to sit strictly between `MIN_SQRT_RATIO` and `MAX_SQRT_RATIO`
+    pub fn is_valid_limit(&self) -> bool {
+        Self::min() < *self && *self < Self::max()
+    }
+
+    /// Clamps this price into the range accepted as a swap price limit
+    pub fn clamp_to_valid(self) -> Self {
+        self.clamp(Self::from(MIN_SQRT_RATIO + U256::ONE), Self::from(MAX_SQRT_RATIO - U256::ONE))
+    }
+
     /// Uses malachite.rs to approximate this value as a floating point number.
     /// Converts from the internal U160 representation of `sqrt(P)` to an
     /// approximated f64 representation of `P`, which is a change to the
@@ -103,9 +127,11 @@ impl From<Ray> for SqrtPriceX96 {
 
 #[cfg(test)]
 mod tests {
+    use alloy_primitives::U160;
     use uniswap_v3_math::tick_math::get_tick_at_sqrt_ratio;
 
     use super::SqrtPriceX96;
+    use crate::tick_info::{MAX_TICK, MIN_TICK};
 
     #[test]
     fn min_and_max_for_tick() {
@@ -123,4 +149,25 @@ mod tests {
             "Next tick outside range"
         );
     }
+
+    #[test]
+    fn bounds_match_tick_bounds() {
+        assert_eq!(SqrtPriceX96::at_tick(MIN_TICK).unwrap(), SqrtPriceX96::min());
+        assert_eq!(SqrtPriceX96::at_tick(MAX_TICK).unwrap(), SqrtPriceX96::max());
+        assert_eq!(SqrtPriceX96::min().to_tick().unwrap(), MIN_TICK);
+    }
+
+    #[test]
+    fn clamp_to_valid_limit() {
+        assert!(!SqrtPriceX96::min().is_valid_limit());
+        assert!(!SqrtPriceX96::max().is_valid_limit());
+
+        let low = SqrtPriceX96::default().clamp_to_valid();
+        let high = SqrtPriceX96::from(U160::MAX).clamp_to_valid();
+        assert!(low.is_valid_limit() && high.is_valid_limit());
+        assert!(low > SqrtPriceX96::min() && high < SqrtPriceX96::max());
+
+        let mid = SqrtPriceX96::at_tick(0).unwrap();
+        assert_eq!(mid.clamp_to_valid(), mid);
+    }
 }
@@ -0,0 +1,132 @@
+//! Helpers for tests, also built for downstream tests with the `test-utils`
+//! feature
+
+use std::collections::HashMap;
+
+use alloy_network::Ethereum;
+use alloy_primitives::{Address, U256, keccak256};
+use uniswap_v3_math::tick_bitmap::flip_tick;
+
+use crate::{
+    BaselinePoolState, L1FeeConfiguration, PoolId, V4Network, liquidity_base::BaselineLiquidity,
+    sqrt_pricex96::SqrtPriceX96, tick_info::TickInfo
+};
+#[cfg(feature = "l2")]
+use crate::{L2FeeConfiguration, MevTaxParams};
+
+/// Pool ids from small seeds for property tests, without building the
+/// [`PoolKey`](crate::PoolKey) they would normally be hashed from. Not an id
+/// any real pool has.
+pub trait PoolIdFromSeed {
+    fn from_seed(seed: u64) -> Self;
+}
+
+impl PoolIdFromSeed for PoolId {
+    /// The hash of the seed with its last 8 bytes replaced by the seed itself,
+    /// so distinct seeds always give distinct ids
+    fn from_seed(seed: u64) -> Self {
+        let mut id = keccak256(seed.to_be_bytes());
+        id[24..].copy_from_slice(&seed.to_be_bytes());
+        id
+    }
+}
+
+/// Tick spacing of the pools built by [`pool_with_positions`]
+pub const TICK_SPACING: i32 = 60;
+
+/// A pool at `start_tick` with the given `(tick_lower, tick_upper, liquidity)`
+/// positions loaded
+pub fn pool_with_positions<T: V4Network>(
+    start_tick: i32,
+    positions: &[(i32, i32, u128)],
+    fee_config: T::FeeConfig
+) -> BaselinePoolState<T> {
+    let mut ticks: HashMap<i32, TickInfo> = HashMap::new();
+    let mut start_liquidity = 0u128;
+
+    for &(lower, upper, liquidity) in positions {
+        let lower_info = ticks.entry(lower).or_default();
+        lower_info.liquidity_net += liquidity as i128;
+        lower_info.liquidity_gross += liquidity;
+        lower_info.initialized = true;
+
+        let upper_info = ticks.entry(upper).or_default();
+        upper_info.liquidity_net -= liquidity as i128;
+        upper_info.liquidity_gross += liquidity;
+        upper_info.initialized = true;
+
+        if lower <= start_tick && start_tick < upper {
+            start_liquidity += liquidity;
+        }
+    }
+
+    let mut tick_bitmap: HashMap<i16, U256> = HashMap::new();
+    for tick in ticks.keys() {
+        flip_tick(&mut tick_bitmap, *tick, TICK_SPACING).unwrap();
+    }
+
+    let liquidity = BaselineLiquidity::new(
+        TICK_SPACING,
+        start_tick,
+        SqrtPriceX96::at_tick(start_tick).unwrap(),
+        start_liquidity,
+        ticks,
+        tick_bitmap
+    );
+
+    BaselinePoolState::new(
+        liquidity,
+        1,
+        fee_config,
+        Address::with_last_byte(1),
+        Address::with_last_byte(2),
+        18,
+        18
+    )
+}
+
+/// L1 fees with a 0.3% swap fee and a 0.05% protocol fee
+pub fn l1_fee_config() -> L1FeeConfiguration {
+    L1FeeConfiguration { bundle_fee: 0, swap_fee: 3000, protocol_fee: 500 }
+}
+
+/// L2 fees with the given LP fee and no taxes
+#[cfg(feature = "l2")]
+pub fn l2_fee_config(lp_fee: u32) -> L2FeeConfiguration {
+    L2FeeConfiguration {
+        is_initialized: true,
+        lp_fee,
+        creator_tax_fee_e6: 0,
+        protocol_tax_fee_e6: 0,
+        creator_swap_fee_e6: 0,
+        protocol_swap_fee_e6: 0,
+        priority_fee_tax_floor: 0,
+        jit_tax_enabled: false,
+        withdraw_only: false,
+        mev_tax_params: MevTaxParams::default()
+    }
+}
+
+/// An L1 pool at tick 0 with a wide and a narrow position around the price
+pub fn l1_pool() -> BaselinePoolState<Ethereum> {
+    pool_with_positions(
+        0,
+        &[(-600, 600, 1_000_000_000_000_000_000), (-120, 120, 1_000_000_000_000_000_000)],
+        l1_fee_config()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::PoolIdFromSeed;
+    use crate::PoolId;
+
+    #[test]
+    fn seeds_give_distinct_stable_ids() {
+        let ids = (0..10_000).map(PoolId::from_seed).collect::<HashSet<_>>();
+        assert_eq!(ids.len(), 10_000);
+        assert_eq!(PoolId::from_seed(7), PoolId::from_seed(7));
+    }
+}
@@ -1,4 +1,6 @@
 use serde::{Deserialize, Serialize};
+pub use uniswap_v3_math::tick_math::{MAX_TICK, MIN_TICK};
+
 pub type Tick = i32;
 
 #[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
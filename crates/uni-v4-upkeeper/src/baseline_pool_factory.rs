@@ -8,17 +8,20 @@ use alloy_provider::Provider;
 use dashmap::DashMap;
 use futures::{Stream, StreamExt, future::BoxFuture, stream::FuturesUnordered};
 use thiserror::Error;
-use uni_v4_common::V4Network;
+use uni_v4_common::{PoolUpdate, V4Network};
 use uni_v4_structure::{
     BaselinePoolState, PoolId, PoolKey,
     liquidity_base::BaselineLiquidity,
     pool_registry::{PoolRegistry, UniswapPoolIdSet},
+    pool_updates::Slot0Data,
     tick_info::TickInfo
 };
 
 use crate::{
+    ConversionError, i32_to_i24,
     pool_data_loader::{DataLoader, PoolDataLoader, TickData},
-    pool_providers::ProviderChainInitialization
+    pool_providers::ProviderChainInitialization,
+    tick_trend::{PredictiveTickLoading, TickTrends}
 };
 
 pub const INITIAL_TICKS_PER_SIDE: u16 = 300;
@@ -31,7 +34,9 @@ pub enum BaselinePoolFactoryError {
     #[error("Provider error: {0}")]
     Provider(String),
     #[error("Pool initialization error: {0}")]
-    Initialization(String)
+    Initialization(String),
+    #[error(transparent)]
+    Conversion(#[from] ConversionError)
 }
 
 pub enum UpdateMessage<T: V4Network> {
@@ -39,6 +44,17 @@ pub enum UpdateMessage<T: V4Network> {
     NewPool(PoolId, BaselinePoolState<T>)
 }
 
+impl<T: V4Network> From<UpdateMessage<T>> for PoolUpdate<T> {
+    fn from(update: UpdateMessage<T>) -> Self {
+        match update {
+            UpdateMessage::NewTicks(pool_id, ticks, tick_bitmap) => {
+                PoolUpdate::NewTicks { pool_id, ticks, tick_bitmap }
+            }
+            UpdateMessage::NewPool(pool_id, state) => PoolUpdate::NewPoolState { pool_id, state }
+        }
+    }
+}
+
 /// Factory for creating BaselinePoolState instances with full tick loading
 pub struct BaselinePoolFactory<P: Provider<T>, T: V4Network> {
     provider:            Arc<P>,
@@ -48,6 +64,7 @@ pub struct BaselinePoolFactory<P: Provider<T>, T: V4Network> {
     tick_band:           u16,
     tick_edge_threshold: u16,
     ticks_per_batch:     usize,
+    tick_trends:         TickTrends,
     tick_loading:
         FuturesUnordered<BoxFuture<'static, (PoolId, HashMap<i32, TickInfo>, HashMap<i16, U256>)>>,
     pool_generator: FuturesUnordered<
@@ -94,6 +111,7 @@ where
             tick_band: tick_band.unwrap_or(INITIAL_TICKS_PER_SIDE),
             tick_edge_threshold: tick_edge_threshold.unwrap_or(100),
             ticks_per_batch: ticks_per_batch.unwrap_or(DEFAULT_TICKS_PER_BATCH),
+            tick_trends: TickTrends::default(),
             tick_loading: FuturesUnordered::default(),
             pool_generator: FuturesUnordered::default()
         };
@@ -132,6 +150,16 @@ where
         (this, Arc::new(pools))
     }
 
+    /// Load wider tick bands on the side a pool's price keeps moving towards
+    pub fn set_predictive_tick_loading(&mut self, config: PredictiveTickLoading) {
+        self.tick_trends.set_config(config);
+    }
+
+    /// Number of ticks loaded on each side of a pool's current tick
+    pub fn tick_band(&self) -> u16 {
+        self.tick_band
+    }
+
     pub fn is_processing(&self) -> bool {
         !(self.tick_loading.is_empty() && self.pool_generator.is_empty())
     }
@@ -168,6 +196,24 @@ where
         Ok(baseline_state)
     }
 
+    /// Loads a registered pool's slot0 and ticks as of `block` without
+    /// touching the tracked state. Reads at old blocks need an archive node.
+    pub async fn pool_state_at_block(
+        &self,
+        pool_id: PoolId,
+        block: u64,
+        fee_cfg: T::FeeConfig
+    ) -> Result<BaselinePoolState<T>, BaselinePoolFactoryError> {
+        let pool_id_set = self.registry.make_pool_id_set(pool_id).ok_or_else(|| {
+            BaselinePoolFactoryError::Initialization(format!(
+                "Pool ID {pool_id:?} not found in registry"
+            ))
+        })?;
+
+        self.create_baseline_pool_from_registry(pool_id_set, block, fee_cfg)
+            .await
+    }
+
     /// Core method that creates BaselinePoolState with complete tick loading
     async fn create_baseline_pool_from_registry(
         &self,
@@ -208,8 +254,8 @@ where
             tick_bitmap
         );
 
-        // Create and return BaselinePoolState
-        Ok(BaselinePoolState::new(
+        // Create and return BaselinePoolState, its tick band is already in
+        let mut pool = BaselinePoolState::new(
             baseline_liquidity,
             block,
             fee_config,
@@ -217,7 +263,9 @@ where
             pool_data.tokenB,
             pool_data.tokenADecimals,
             pool_data.tokenBDecimals
-        ))
+        );
+        pool.mark_ticks_loaded();
+        Ok(pool)
     }
 
     /// Loads complete tick data in both directions around the current tick
@@ -278,7 +326,7 @@ where
             let (batch_ticks, next_tick) = self
                 .get_tick_data_batch_request(
                     data_loader,
-                    I24::unchecked_from(tick_start),
+                    i32_to_i24(tick_start)?,
                     zero_for_one,
                     ticks_to_load,
                     tick_spacing,
@@ -315,7 +363,7 @@ where
                 tick_start,
                 zero_for_one,
                 num_ticks,
-                I24::unchecked_from(tick_spacing),
+                i32_to_i24(tick_spacing)?,
                 block_number,
                 self.provider.clone()
             )
@@ -454,7 +502,7 @@ where
 
             let (batch_ticks, next_tick) = Self::get_tick_data_batch_request_static(
                 data_loader,
-                I24::unchecked_from(tick_start),
+                i32_to_i24(tick_start)?,
                 zero_for_one,
                 ticks_to_load,
                 tick_spacing,
@@ -492,7 +540,7 @@ where
                 tick_start,
                 zero_for_one,
                 num_ticks,
-                I24::unchecked_from(tick_spacing),
+                i32_to_i24(tick_spacing)?,
                 block_number,
                 provider
             )
@@ -565,6 +613,7 @@ where
 
     pub fn remove_pool_by_id(&mut self, pool_id: PoolId) {
         self.registry.remove(&pool_id);
+        self.tick_trends.remove(&pool_id);
     }
 
     /// Remove pool from registry
@@ -584,14 +633,67 @@ where
         num_ticks: u16,
         block_number: Option<u64>
     ) {
+        match self.tick_loading_future(
+            pool_id,
+            zero_for_one,
+            current_tick,
+            tick_spacing,
+            num_ticks,
+            block_number
+        ) {
+            Ok(future) => self.tick_loading.push(Box::pin(future)),
+            Err(e) => tracing::warn!("Not loading more ticks: {e}")
+        }
+    }
+
+    /// Loads ticks the same way as [`Self::request_more_ticks`] but hands them
+    /// back instead of yielding them from the stream. Errors if the pool isn't
+    /// in the registry.
+    pub async fn load_more_ticks(
+        &self,
+        pool_id: PoolId,
+        zero_for_one: bool,
+        current_tick: i32,
+        tick_spacing: i32,
+        num_ticks: u16,
+        block_number: Option<u64>
+    ) -> Result<(HashMap<i32, TickInfo>, HashMap<i16, U256>), BaselinePoolFactoryError> {
+        let (_, ticks, tick_bitmap) = self
+            .tick_loading_future(
+                pool_id,
+                zero_for_one,
+                current_tick,
+                tick_spacing,
+                num_ticks,
+                block_number
+            )?
+            .await;
+        Ok((ticks, tick_bitmap))
+    }
+
+    fn tick_loading_future(
+        &self,
+        pool_id: PoolId,
+        zero_for_one: bool,
+        current_tick: i32,
+        tick_spacing: i32,
+        num_ticks: u16,
+        block_number: Option<u64>
+    ) -> Result<
+        impl Future<Output = (PoolId, HashMap<i32, TickInfo>, HashMap<i16, U256>)> + Send + 'static,
+        BaselinePoolFactoryError
+    > {
         let provider = self.provider.clone();
         let pool_manager = self.pool_manager;
         let registry = self.registry.clone();
 
-        let pool_id_set = self.registry.make_pool_id_set(pool_id).unwrap();
+        let pool_id_set = self.registry.make_pool_id_set(pool_id).ok_or_else(|| {
+            BaselinePoolFactoryError::Initialization(format!(
+                "Pool ID {pool_id:?} not found in registry"
+            ))
+        })?;
 
-        // Create the future for loading ticks
-        let future = async move {
+        Ok(async move {
             let data_loader = DataLoader::new_with_registry(pool_id_set, registry, pool_manager);
 
             let initial_tick_start = if zero_for_one {
@@ -609,12 +711,20 @@ where
                 let ticks_to_load =
                     std::cmp::min(DEFAULT_TICKS_PER_BATCH as u16, num_ticks - ticks_loaded);
 
+                let (Ok(start), Ok(spacing)) = (i32_to_i24(tick_start), i32_to_i24(tick_spacing))
+                else {
+                    tracing::warn!(
+                        "Tick {tick_start} of pool {pool_id:?} is outside the i24 range, stopping \
+                         tick loading"
+                    );
+                    break;
+                };
                 let (batch_ticks, _) = data_loader
                     .load_tick_data(
-                        I24::unchecked_from(tick_start),
+                        start,
                         zero_for_one,
                         ticks_to_load,
-                        I24::unchecked_from(tick_spacing),
+                        spacing,
                         block_number,
                         provider.clone()
                     )
@@ -660,13 +770,19 @@ where
             }
 
             (pool_id, tick_map, tick_bitmap)
-        };
-
-        self.tick_loading.push(Box::pin(future));
+        })
     }
 
-    /// Queue a new pool for creation
-    pub fn queue_pool_creation(&mut self, pool_key: PoolKey, block: u64, fee_config: T::FeeConfig) {
+    /// Queue a new pool for creation. `initial_slot0`, taken from the pool's
+    /// `Initialize` event, stands in for the loaded price if the read at
+    /// `block` doesn't see the pool initialized yet.
+    pub fn queue_pool_creation(
+        &mut self,
+        pool_key: PoolKey,
+        block: u64,
+        fee_config: T::FeeConfig,
+        initial_slot0: Option<Slot0Data>
+    ) {
         let pool_id = pool_key.into();
         self.registry.add_new_pool(pool_key);
 
@@ -692,8 +808,12 @@ where
 
             // Extract basic pool state
             let liquidity = pool_data.liquidity;
-            let sqrt_price_x96 = pool_data.sqrtPrice.into();
-            let tick = pool_data.tick.as_i32();
+            let (sqrt_price_x96, tick) = match initial_slot0 {
+                Some(slot0) if pool_data.sqrtPrice.is_zero() => {
+                    (slot0.sqrt_price_x96.into(), slot0.tick)
+                }
+                _ => (pool_data.sqrtPrice.into(), pool_data.tick.as_i32())
+            };
             let tick_spacing = pool_data.tickSpacing.as_i32();
 
             // Load ticks in both directions
@@ -719,18 +839,17 @@ where
             );
 
             // Create and return BaselinePoolState with Uniswap pool_id
-            Ok((
-                pool_id, // Use Uniswap pool ID
-                BaselinePoolState::new(
-                    baseline_liquidity,
-                    block,
-                    fee_config,
-                    pool_data.tokenA,
-                    pool_data.tokenB,
-                    pool_data.tokenADecimals,
-                    pool_data.tokenBDecimals
-                )
-            ))
+            let mut pool = BaselinePoolState::new(
+                baseline_liquidity,
+                block,
+                fee_config,
+                pool_data.tokenA,
+                pool_data.tokenB,
+                pool_data.tokenADecimals,
+                pool_data.tokenBDecimals
+            );
+            pool.mark_ticks_loaded();
+            Ok((pool_id, pool)) // Use Uniswap pool ID
         };
 
         self.pool_generator.push(Box::pin(future));
@@ -758,60 +877,39 @@ where
             return false;
         };
 
-        // Calculate the distance threshold
-        let threshold = (tick_spacing * self.tick_edge_threshold as i32).abs();
-
-        let mut requested = false;
+        let requests = self.tick_trends.plan_requests(
+            pool_id,
+            block_number,
+            current_tick,
+            min_tick,
+            max_tick,
+            tick_spacing,
+            self.tick_edge_threshold
+        );
 
-        // Check if we need more ticks below current price
-        if current_tick - min_tick < threshold {
-            // Load more ticks below
-            let start_tick = min_tick - tick_spacing;
+        for request in &requests {
             self.request_more_ticks(
                 pool_id,
-                true, // zero_for_one = true means loading ticks below
-                start_tick,
+                request.zero_for_one,
+                request.start_tick,
                 tick_spacing,
-                self.tick_edge_threshold,
+                request.num_ticks,
                 block_number
             );
-            requested = true;
             tracing::info!(
-                "Requesting more ticks below for pool {:?}, current_tick: {}, min_tick: {}, \
-                 threshold: {}, loading {} ticks",
+                "Requesting more ticks {} for pool {:?}, current_tick: {}, min_tick: {}, \
+                 max_tick: {}, threshold: {}, loading {} ticks",
+                if request.zero_for_one { "below" } else { "above" },
                 pool_id,
                 current_tick,
                 min_tick,
-                threshold,
-                self.tick_edge_threshold
-            );
-        }
-
-        // Check if we need more ticks above current price
-        if max_tick - current_tick < threshold {
-            // Load more ticks above
-            let start_tick = max_tick + tick_spacing;
-            self.request_more_ticks(
-                pool_id,
-                false, // zero_for_one = false means loading ticks above
-                start_tick,
-                tick_spacing,
-                self.tick_edge_threshold,
-                block_number
-            );
-            requested = true;
-            tracing::info!(
-                "Requesting more ticks above for pool {:?}, current_tick: {}, max_tick: {}, \
-                 threshold: {}, loading {} ticks",
-                pool_id,
-                current_tick,
                 max_tick,
-                threshold,
-                self.tick_edge_threshold
+                request.threshold,
+                request.num_ticks
             );
         }
 
-        requested
+        !requests.is_empty()
     }
 }
 
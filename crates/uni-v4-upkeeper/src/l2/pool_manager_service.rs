@@ -1,4 +1,4 @@
-use alloy_primitives::aliases::{I24, U24};
+use alloy_primitives::aliases::U24;
 use alloy_provider::Provider;
 use futures::Stream;
 use op_alloy_network::Optimism;
@@ -10,6 +10,7 @@ use uni_v4_structure::{
 
 use crate::{
     baseline_pool_factory::{BaselinePoolFactory, UpdateMessage},
+    checked_tick_spacing,
     pool_manager_service::{PoolEventProcessor, PoolManagerService},
     pool_providers::{PoolEventStream, ProviderChainInitialization},
     slot0::Slot0Stream
@@ -38,36 +39,39 @@ where
                 creator_swap_fee_e6,
                 protocol_swap_fee_e6,
                 priority_fee_tax_floor,
+                mev_tax_params,
                 jit_tax_enabled,
                 withdraw_only,
                 ..
             } => {
-                if self.auto_pool_creation {
-                    let pool_key = PoolKey {
-                        currency0:   *token0,
-                        currency1:   *token1,
-                        fee:         U24::from(*hook_fee),
-                        tickSpacing: I24::unchecked_from(*tick_spacing),
-                        hooks:       *hook
-                    };
-                    let fee_cfg = L2FeeConfiguration {
-                        is_initialized:         true,
-                        lp_fee:                 *hook_fee,
-                        creator_tax_fee_e6:     *creator_tax_fee_e6,
-                        protocol_tax_fee_e6:    *protocol_tax_fee_e6,
-                        creator_swap_fee_e6:    *creator_swap_fee_e6,
-                        protocol_swap_fee_e6:   *protocol_swap_fee_e6,
-                        priority_fee_tax_floor: *priority_fee_tax_floor,
-                        jit_tax_enabled:        *jit_tax_enabled,
-                        withdraw_only:          *withdraw_only
-                    };
-                    self.handle_new_pool(pool_key, *block, fee_cfg);
+                let Some(tick_spacing) = checked_tick_spacing(*tick_spacing) else {
+                    return;
+                };
+                let pool_key = PoolKey {
+                    currency0:   *token0,
+                    currency1:   *token1,
+                    fee:         U24::from(*hook_fee),
+                    tickSpacing: tick_spacing,
+                    hooks:       *hook
+                };
+                let fee_cfg = L2FeeConfiguration {
+                    is_initialized:         true,
+                    lp_fee:                 *hook_fee,
+                    creator_tax_fee_e6:     *creator_tax_fee_e6,
+                    protocol_tax_fee_e6:    *protocol_tax_fee_e6,
+                    creator_swap_fee_e6:    *creator_swap_fee_e6,
+                    protocol_swap_fee_e6:   *protocol_swap_fee_e6,
+                    priority_fee_tax_floor: *priority_fee_tax_floor,
+                    jit_tax_enabled:        *jit_tax_enabled,
+                    withdraw_only:          *withdraw_only,
+                    mev_tax_params:         *mev_tax_params
+                };
+
+                if self.should_create_pool(&pool_key, &fee_cfg) {
+                    self.handle_new_pool(pool_key, *block, fee_cfg, None);
                     tracing::info!("Pool configured: {pool_id:?}:\n{fee_cfg:?}");
                 } else {
-                    tracing::info!(
-                        "Ignoring pool configured event (auto creation disabled): {:?}",
-                        pool_id
-                    );
+                    tracing::info!("Ignoring pool configured event: {:?}", pool_id);
                 }
             }
         }
@@ -1,9 +1,7 @@
 use std::collections::{HashMap, HashSet};
 
-use alloy_primitives::{
-    Address,
-    aliases::{I24, U24}
-};
+use alloy_network::Network;
+use alloy_primitives::{Address, aliases::U24};
 use alloy_provider::Provider;
 use alloy_rpc_types::Filter;
 use alloy_sol_types::SolEvent;
@@ -13,13 +11,16 @@ use op_alloy_network::Optimism;
 pub use types::*;
 use uni_v4_common::{PoolUpdate, V4Network};
 use uni_v4_structure::{
-    L2FeeConfiguration, PoolId, PoolKey, PoolKeyWithFees, fee_config::L2FeeUpdate,
+    L2FeeConfiguration, MevTaxParams, PoolId, PoolKey, PoolKeyWithFees, fee_config::L2FeeUpdate,
     l2_structure::pool_updates::L2PoolUpdate, pool_registry::PoolRegistry
 };
 
-use crate::pool_providers::{
-    ProviderChainInitialization, ProviderChainUpdate,
-    update_provider::{PoolUpdateError, PoolUpdateProvider}
+use crate::{
+    checked_fee, checked_tick_spacing,
+    pool_providers::{
+        ProviderChainInitialization, ProviderChainUpdate,
+        update_provider::{PoolUpdateError, PoolUpdateProvider}
+    }
 };
 
 mod types {
@@ -62,6 +63,8 @@ mod types {
         #[sol(rpc)]
         contract AngstromL2Hook {
             function priorityFeeTaxFloor() external view returns (uint256);
+            function SWAP_TAXED_GAS() external view returns (uint256);
+            function SWAP_MEV_TAX_FACTOR() external view returns (uint256);
         }
     }
 
@@ -81,9 +84,9 @@ mod types {
 /// Batch-fetch `priorityFeeTaxFloor` for a set of hook addresses.
 async fn fetch_hook_floors<P: Provider<Optimism>>(
     provider: &P,
-    hooks: HashSet<Address>
+    hooks: &HashSet<Address>
 ) -> HashMap<Address, u128> {
-    let futures = hooks.into_iter().map(|hook_addr| async move {
+    let futures = hooks.iter().map(|&hook_addr| async move {
         let hook = AngstromL2Hook::new(hook_addr, provider);
         let result = hook.priorityFeeTaxFloor().call().await.unwrap_or_else(|e| {
             panic!("Failed to read priorityFeeTaxFloor from hook {hook_addr:?}: {e}")
@@ -97,6 +100,34 @@ async fn fetch_hook_floors<P: Provider<Optimism>>(
         .collect()
 }
 
+/// Batch-fetch the MEV tax gas and factor for a set of hook addresses. Hooks
+/// that don't expose them keep the AngstromL2.sol defaults.
+async fn fetch_hook_mev_tax_params<N: Network, P: Provider<N>>(
+    provider: &P,
+    hooks: &HashSet<Address>
+) -> HashMap<Address, MevTaxParams> {
+    let futures = hooks.iter().map(|&hook_addr| async move {
+        let hook = AngstromL2Hook::new(hook_addr, provider);
+        let params =
+            match (hook.SWAP_TAXED_GAS().call().await, hook.SWAP_MEV_TAX_FACTOR().call().await) {
+                (Ok(taxed_gas), Ok(mev_tax_factor)) => MevTaxParams {
+                    taxed_gas:      taxed_gas.to(),
+                    mev_tax_factor: mev_tax_factor.to()
+                },
+                (Err(e), _) | (_, Err(e)) => {
+                    tracing::debug!("Using default MEV tax params for hook {hook_addr:?}: {e}");
+                    MevTaxParams::default()
+                }
+            };
+        (hook_addr, params)
+    });
+
+    futures::future::join_all(futures)
+        .await
+        .into_iter()
+        .collect()
+}
+
 impl<P> ProviderChainUpdate<Optimism> for PoolUpdateProvider<P, Optimism>
 where
     P: Provider<Optimism>
@@ -104,7 +135,8 @@ where
     async fn fetch_chain_data(
         &mut self,
         from_block: u64,
-        to_block: u64
+        to_block: u64,
+        _initialize_logs: &[alloy_rpc_types::Log]
     ) -> Result<Vec<PoolUpdate<Optimism>>, PoolUpdateError> {
         let logs = self.fetch_l2_factory_logs(from_block, to_block).await?;
 
@@ -118,9 +150,10 @@ where
             })
             .collect();
 
-        let hook_floors = fetch_hook_floors(self.provider(), hook_addrs).await;
+        let hook_floors = fetch_hook_floors(self.provider(), &hook_addrs).await;
+        let hook_mev_tax_params = fetch_hook_mev_tax_params(self.provider(), &hook_addrs).await;
 
-        let updates = self.process_l2_factory_logs(logs, &hook_floors);
+        let updates = self.process_l2_factory_logs(logs, &hook_floors, &hook_mev_tax_params);
         Ok(updates)
     }
 }
@@ -155,7 +188,8 @@ where
     fn process_l2_factory_logs(
         &mut self,
         logs: Vec<alloy_rpc_types::Log>,
-        hook_floors: &HashMap<Address, u128>
+        hook_floors: &HashMap<Address, u128>,
+        hook_mev_tax_params: &HashMap<Address, MevTaxParams>
     ) -> Vec<PoolUpdate<Optimism>> {
         // Pre-scan: collect hook-level state that may precede PoolCreated in
         // the same block. Without this, JITTaxStatusUpdated / WithdrawOnly
@@ -177,9 +211,27 @@ where
 
         for log in logs {
             let block_number = log.block_number.unwrap();
+            let tx_index = log.transaction_index.unwrap();
+            let log_index = log.log_index.unwrap();
 
             if let Ok(event) = AngstromL2Factory::PoolCreated::decode_log(&log.inner) {
-                let pool_key = event.key.clone().into();
+                let pool_key: PoolKey = event.key.clone().into();
+                let (
+                    Some(hook_fee),
+                    Some(creator_tax_fee_e6),
+                    Some(protocol_tax_fee_e6),
+                    Some(creator_swap_fee_e6),
+                    Some(protocol_swap_fee_e6)
+                ) = (
+                    checked_fee(pool_key.fee.to(), "hook fee"),
+                    checked_fee(event.creatorTaxFeeE6.to(), "creator tax fee"),
+                    checked_fee(event.protocolTaxFeeE6.to(), "protocol tax fee"),
+                    checked_fee(event.creatorSwapFeeE6.to(), "creator swap fee"),
+                    checked_fee(event.protocolSwapFeeE6.to(), "protocol swap fee")
+                )
+                else {
+                    continue;
+                };
 
                 registry.add_new_pool(pool_key);
 
@@ -198,14 +250,20 @@ where
                         token0: pool_key.currency0,
                         token1: pool_key.currency1,
                         hook: event.hook,
-                        hook_fee: pool_key.fee.to(),
+                        hook_fee,
                         tick_spacing: pool_key.tickSpacing.as_i32(),
                         block: block_number,
-                        creator_tax_fee_e6: event.creatorTaxFeeE6.to(),
-                        protocol_tax_fee_e6: event.protocolTaxFeeE6.to(),
-                        creator_swap_fee_e6: event.creatorSwapFeeE6.to(),
-                        protocol_swap_fee_e6: event.protocolSwapFeeE6.to(),
+                        tx_index,
+                        log_index,
+                        creator_tax_fee_e6,
+                        protocol_tax_fee_e6,
+                        creator_swap_fee_e6,
+                        protocol_swap_fee_e6,
                         priority_fee_tax_floor: floor,
+                        mev_tax_params: hook_mev_tax_params
+                            .get(&event.hook)
+                            .copied()
+                            .unwrap_or_default(),
                         jit_tax_enabled: hook_jit_tax.get(&event.hook).copied().unwrap_or(false),
                         withdraw_only: global_withdraw_only
                     }
@@ -213,14 +271,19 @@ where
             } else if let Ok(event) =
                 AngstromL2Factory::ProtocolSwapFeeUpdated::decode_log(&log.inner)
             {
+                let Some(fee) = checked_fee(event.data.newFeeE6.to(), "protocol swap fee") else {
+                    continue;
+                };
                 let pool_id = PoolId::from(PoolKey::from(event.key.clone()));
 
                 updates.push(PoolUpdate::FeeUpdate {
                     pool_id,
                     block: block_number,
+                    tx_index,
+                    log_index,
                     update: L2FeeUpdate {
                         protocol_tax_fee_e6:    None,
-                        protocol_swap_fee_e6:   Some(event.data.newFeeE6.to()),
+                        protocol_swap_fee_e6:   Some(fee),
                         priority_fee_tax_floor: None,
                         jit_tax_enabled:        None,
                         withdraw_only:          None
@@ -229,13 +292,18 @@ where
             } else if let Ok(event) =
                 AngstromL2Factory::ProtocolTaxFeeUpdated::decode_log(&log.inner)
             {
+                let Some(fee) = checked_fee(event.data.newFeeE6.to(), "protocol tax fee") else {
+                    continue;
+                };
                 let pool_id = PoolId::from(PoolKey::from(event.key.clone()));
 
                 updates.push(PoolUpdate::FeeUpdate {
                     pool_id,
                     block: block_number,
+                    tx_index,
+                    log_index,
                     update: L2FeeUpdate {
-                        protocol_tax_fee_e6:    Some(event.data.newFeeE6.to()),
+                        protocol_tax_fee_e6:    Some(fee),
                         protocol_swap_fee_e6:   None,
                         priority_fee_tax_floor: None,
                         jit_tax_enabled:        None,
@@ -249,6 +317,8 @@ where
                     updates.push(PoolUpdate::FeeUpdate {
                         pool_id,
                         block: block_number,
+                        tx_index,
+                        log_index,
                         update: L2FeeUpdate {
                             protocol_tax_fee_e6:    None,
                             protocol_swap_fee_e6:   None,
@@ -266,6 +336,8 @@ where
                     updates.push(PoolUpdate::FeeUpdate {
                         pool_id,
                         block: block_number,
+                        tx_index,
+                        log_index,
                         update: L2FeeUpdate {
                             protocol_tax_fee_e6:    None,
                             protocol_swap_fee_e6:   None,
@@ -280,6 +352,8 @@ where
                     updates.push(PoolUpdate::FeeUpdate {
                         pool_id,
                         block: block_number,
+                        tx_index,
+                        log_index,
                         update: L2FeeUpdate {
                             protocol_tax_fee_e6:    None,
                             protocol_swap_fee_e6:   None,
@@ -350,7 +424,8 @@ where
         })
         .collect();
 
-    let hook_floors = fetch_hook_floors(db, hook_addrs).await;
+    let hook_floors = fetch_hook_floors(db, &hook_addrs).await;
+    let hook_mev_tax_params = fetch_hook_mev_tax_params(db, &hook_addrs).await;
 
     // Track per-hook state for JIT tax and priority fee floor from events
     let mut hook_jit_tax: HashMap<Address, bool> = HashMap::new();
@@ -367,10 +442,28 @@ where
 
     let all_updates = logs.into_iter().filter_map(|log| {
         let block_number = log.block_number.unwrap();
+        let tx_index = log.transaction_index.unwrap();
+        let log_index = log.log_index.unwrap();
 
         if let Ok(event) = AngstromL2Factory::PoolCreated::decode_log(&log.inner) {
             let pool_key = event.key.clone();
 
+            let (
+                Some(hook_fee),
+                Some(creator_tax_fee_e6),
+                Some(protocol_tax_fee_e6),
+                Some(creator_swap_fee_e6),
+                Some(protocol_swap_fee_e6)
+            ) = (
+                checked_fee(pool_key.fee.to(), "hook fee"),
+                checked_fee(event.creatorTaxFeeE6.to(), "creator tax fee"),
+                checked_fee(event.protocolTaxFeeE6.to(), "protocol tax fee"),
+                checked_fee(event.creatorSwapFeeE6.to(), "creator swap fee"),
+                checked_fee(event.protocolSwapFeeE6.to(), "protocol swap fee")
+            )
+            else {
+                return None;
+            };
             let pool_id = PoolId::from(PoolKey::from(pool_key.clone()));
             let floor = hook_floors.get(&event.hook).copied().unwrap_or_else(|| {
                 panic!(
@@ -388,12 +481,18 @@ where
                     hook: event.hook,
                     tick_spacing: pool_key.tickSpacing.as_i32(),
                     block: block_number,
-                    hook_fee: pool_key.fee.to(),
-                    creator_tax_fee_e6: event.creatorTaxFeeE6.to(),
-                    protocol_tax_fee_e6: event.protocolTaxFeeE6.to(),
-                    creator_swap_fee_e6: event.creatorSwapFeeE6.to(),
-                    protocol_swap_fee_e6: event.protocolSwapFeeE6.to(),
+                    tx_index,
+                    log_index,
+                    hook_fee,
+                    creator_tax_fee_e6,
+                    protocol_tax_fee_e6,
+                    creator_swap_fee_e6,
+                    protocol_swap_fee_e6,
                     priority_fee_tax_floor: floor,
+                    mev_tax_params: hook_mev_tax_params
+                        .get(&event.hook)
+                        .copied()
+                        .unwrap_or_default(),
                     jit_tax_enabled: hook_jit_tax.get(&event.hook).copied().unwrap_or(false),
                     withdraw_only: global_withdraw_only
                 }
@@ -401,13 +500,16 @@ where
         } else if let Ok(event) = AngstromL2Factory::ProtocolSwapFeeUpdated::decode_log(&log.inner)
         {
             let pool_id = PoolId::from(PoolKey::from(event.key.clone()));
+            let fee = checked_fee(event.data.newFeeE6.to(), "protocol swap fee")?;
 
             Some(PoolUpdate::FeeUpdate {
                 pool_id,
                 block: block_number,
+                tx_index,
+                log_index,
                 update: L2FeeUpdate {
                     protocol_tax_fee_e6:    None,
-                    protocol_swap_fee_e6:   Some(event.data.newFeeE6.to()),
+                    protocol_swap_fee_e6:   Some(fee),
                     priority_fee_tax_floor: None,
                     jit_tax_enabled:        None,
                     withdraw_only:          None
@@ -415,12 +517,15 @@ where
             })
         } else if let Ok(event) = AngstromL2Factory::ProtocolTaxFeeUpdated::decode_log(&log.inner) {
             let pool_id = PoolId::from(PoolKey::from(event.key.clone()));
+            let fee = checked_fee(event.data.newFeeE6.to(), "protocol tax fee")?;
 
             Some(PoolUpdate::FeeUpdate {
                 pool_id,
                 block: block_number,
+                tx_index,
+                log_index,
                 update: L2FeeUpdate {
-                    protocol_tax_fee_e6:    Some(event.data.newFeeE6.to()),
+                    protocol_tax_fee_e6:    Some(fee),
                     protocol_swap_fee_e6:   None,
                     priority_fee_tax_floor: None,
                     jit_tax_enabled:        None,
@@ -444,13 +549,7 @@ where
             },
             _ => false
         })
-        .sorted_by_key(|update| match update {
-            PoolUpdate::FeeUpdate { block, .. } => *block as i64,
-            PoolUpdate::ChainSpecific { update, .. } => match update {
-                L2PoolUpdate::NewPool { block, .. } => *block as i64
-            },
-            _ => unreachable!()
-        });
+        .sorted_by(|a, b| a.sort(b));
 
     let mut pool_keys: HashMap<PoolId, PoolKeyWithFees<L2FeeConfiguration>> = HashMap::new();
 
@@ -483,16 +582,20 @@ where
                 hook_fee,
                 hook,
                 priority_fee_tax_floor,
+                mev_tax_params,
                 jit_tax_enabled,
                 withdraw_only,
                 ..
             } => {
+                let Some(tick_spacing) = checked_tick_spacing(tick_spacing) else {
+                    return;
+                };
                 let pool_key_with_fees = PoolKeyWithFees {
                     pool_key: PoolKey {
                         currency0:   token0,
                         currency1:   token1,
                         fee:         U24::from(hook_fee),
-                        tickSpacing: I24::unchecked_from(tick_spacing),
+                        tickSpacing: tick_spacing,
                         hooks:       hook
                     },
                     fee_cfg:  L2FeeConfiguration {
@@ -504,7 +607,8 @@ where
                         protocol_swap_fee_e6,
                         priority_fee_tax_floor,
                         jit_tax_enabled,
-                        withdraw_only
+                        withdraw_only,
+                        mev_tax_params
                     }
                 };
                 pool_keys.insert(pool_id, pool_key_with_fees);
@@ -513,7 +617,12 @@ where
         _ => unreachable!()
     });
 
-    pool_keys.values().cloned().collect()
+    // sorted so startup sees the pools in the same order every run
+    pool_keys
+        .into_iter()
+        .sorted_by_key(|(pool_id, _)| *pool_id)
+        .map(|(_, pool)| pool)
+        .collect()
 }
 
 impl<P> ProviderChainInitialization<Optimism> for P
@@ -529,3 +638,44 @@ where
         Ok(fetch_l2_pools(start_block, end_block, address_book.angstrom_v2_factory, self).await)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use alloy_network::Ethereum;
+    use alloy_primitives::{Bytes, U256};
+    use alloy_provider::{ProviderBuilder, RootProvider, mock::Asserter};
+
+    use super::*;
+
+    fn mocked_provider(asserter: &Asserter) -> RootProvider<Ethereum> {
+        ProviderBuilder::new()
+            .disable_recommended_fillers()
+            .connect_mocked_client(asserter.clone())
+    }
+
+    #[tokio::test]
+    async fn mev_tax_params_are_read_from_the_hook() {
+        let asserter = Asserter::new();
+        asserter.push_success(&Bytes::from(U256::from(200_000).to_be_bytes::<32>()));
+        asserter.push_success(&Bytes::from(U256::from(49).to_be_bytes::<32>()));
+        let hook = Address::with_last_byte(1);
+
+        let params =
+            fetch_hook_mev_tax_params(&mocked_provider(&asserter), &HashSet::from([hook])).await;
+
+        assert_eq!(params[&hook], MevTaxParams { taxed_gas: 200_000, mev_tax_factor: 49 });
+    }
+
+    #[tokio::test]
+    async fn hooks_without_mev_tax_getters_keep_the_defaults() {
+        let asserter = Asserter::new();
+        asserter.push_failure_msg("execution reverted");
+        asserter.push_failure_msg("execution reverted");
+        let hook = Address::with_last_byte(1);
+
+        let params =
+            fetch_hook_mev_tax_params(&mocked_provider(&asserter), &HashSet::from([hook])).await;
+
+        assert_eq!(params[&hook], MevTaxParams::default());
+    }
+}
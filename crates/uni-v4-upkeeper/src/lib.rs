@@ -1,15 +1,20 @@
 #![allow(clippy::type_complexity)]
 
-use alloy_primitives::I256;
-use thiserror::Error;
+use alloy_primitives::{I256, aliases::I24};
+pub use uni_v4_structure::ConversionError;
+use uni_v4_structure::pool_key::MAX_LP_FEE;
 
 pub mod baseline_pool_factory;
 pub mod bindings;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod mock_provider;
 pub mod pool_data_loader;
 pub mod pool_manager_service;
 pub mod pool_manager_service_builder;
 pub mod pool_providers;
 pub mod slot0;
+pub mod tick_trend;
+pub mod update_channel;
 
 #[cfg(feature = "l2")]
 mod l2;
@@ -18,14 +23,30 @@ pub mod l2_upkeeper {
     pub use super::l2::*;
 }
 
-fn i128_to_i256(value: i128) -> I256 {
-    I256::try_from(value).unwrap()
+fn i128_to_i256(value: i128) -> Result<I256, ConversionError> {
+    I256::try_from(value).map_err(|_| ConversionError::OverflowErrorI256(value))
 }
 
-#[derive(Error, Debug)]
-pub enum ConversionError {
-    #[error("overflow from i32 to i24 {0:?}")]
-    OverflowErrorI24(i32),
-    #[error("overflow from I256 to I128 {0:?}")]
-    OverflowErrorI28(I256)
+/// Ticks and tick spacings are i24 on chain, anything past that is rejected
+/// rather than wrapped
+fn i32_to_i24(value: i32) -> Result<I24, ConversionError> {
+    I24::try_from(value).map_err(|_| ConversionError::OverflowErrorI24(value))
+}
+
+/// Fees decoded from logs are millionths, one above 100% would break the swap
+/// math so the log is dropped with a warning instead
+fn checked_fee(fee: u32, name: &str) -> Option<u32> {
+    if fee > MAX_LP_FEE {
+        tracing::warn!("Ignoring log with {name} {fee}, above the fee cap of {MAX_LP_FEE}");
+        return None;
+    }
+    Some(fee)
+}
+
+/// Tick spacing decoded from chain data, a log or pool whose spacing doesn't
+/// fit an i24 is dropped with a warning instead
+fn checked_tick_spacing(tick_spacing: i32) -> Option<I24> {
+    i32_to_i24(tick_spacing)
+        .inspect_err(|e| tracing::warn!("Ignoring pool with tick spacing {tick_spacing}: {e}"))
+        .ok()
 }
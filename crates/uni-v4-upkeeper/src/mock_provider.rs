@@ -0,0 +1,255 @@
+//! An in-memory [`Provider`] for tests, only built with the `test-utils`
+//! feature.
+//!
+//! Unlike a provider connected to an `Asserter`, which answers requests in the
+//! order responses were pushed, [`MockProvider`] answers from the chain data it
+//! was given: logs are matched against the request's filter, blocks are looked
+//! up by number or hash and calls by their calldata. Anything else goes to a
+//! provider without responses and fails.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, PoisonError, RwLock}
+};
+
+use alloy_consensus::BlockHeader;
+use alloy_eips::{BlockId, BlockNumberOrTag};
+use alloy_network::{
+    BlockResponse, Ethereum, Network, TransactionBuilder, primitives::HeaderResponse
+};
+use alloy_primitives::{BlockNumber, Bytes, U64};
+use alloy_provider::{
+    Caller, EthCall, EthCallManyParams, EthCallParams, EthGetBlock, EthGetBlockParams, Provider,
+    ProviderBuilder, ProviderCall, RootProvider,
+    mock::Asserter,
+    transport::{TransportErrorKind, TransportResult}
+};
+use alloy_rpc_types::{Filter, Log};
+
+/// See the [module documentation](self)
+#[derive(Clone)]
+pub struct MockProvider<N: Network = Ethereum> {
+    root:  RootProvider<N>,
+    chain: Arc<RwLock<MockChain<N>>>
+}
+
+struct MockChain<N: Network> {
+    block_number: Option<u64>,
+    blocks:       BTreeMap<u64, N::BlockResponse>,
+    logs:         Vec<Log>,
+    calls:        HashMap<Bytes, Bytes>
+}
+
+impl<N: Network> Default for MockProvider<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<N: Network> MockProvider<N> {
+    pub fn new() -> Self {
+        Self {
+            root:  ProviderBuilder::new()
+                .disable_recommended_fillers()
+                .network::<N>()
+                .connect_mocked_client(Asserter::new()),
+            chain: Arc::new(RwLock::new(MockChain {
+                block_number: None,
+                blocks:       BTreeMap::new(),
+                logs:         Vec::new(),
+                calls:        HashMap::new()
+            }))
+        }
+    }
+
+    /// Overrides the block number returned by `get_block_number`, which is
+    /// otherwise the highest block added
+    pub fn set_block_number(&self, block_number: u64) {
+        self.write().block_number = Some(block_number);
+    }
+
+    /// Adds a block, replacing any earlier one with the same number
+    pub fn add_block(&self, block: N::BlockResponse) {
+        self.write().blocks.insert(block.header().number(), block);
+    }
+
+    /// Adds logs to be served to any `get_logs` filter they match. Logs
+    /// without a block number are never served.
+    pub fn add_logs(&self, logs: impl IntoIterator<Item = Log>) {
+        self.write().logs.extend(logs);
+    }
+
+    /// Answers `eth_call`s with the given calldata, whatever they're sent to
+    pub fn add_call(&self, input: impl Into<Bytes>, output: impl Into<Bytes>) {
+        self.write().calls.insert(input.into(), output.into());
+    }
+
+    fn read(&self) -> impl std::ops::Deref<Target = MockChain<N>> + '_ {
+        self.chain.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> impl std::ops::DerefMut<Target = MockChain<N>> + '_ {
+        self.chain.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn find_block(&self, block: BlockId) -> Option<N::BlockResponse> {
+        let chain = self.read();
+        match block {
+            BlockId::Hash(hash) => chain
+                .blocks
+                .values()
+                .find(|block| block.header().hash() == hash.block_hash)
+                .cloned(),
+            BlockId::Number(BlockNumberOrTag::Number(number)) => chain.blocks.get(&number).cloned(),
+            BlockId::Number(BlockNumberOrTag::Earliest) => chain
+                .blocks
+                .first_key_value()
+                .map(|(_, block)| block.clone()),
+            BlockId::Number(_) => chain
+                .blocks
+                .last_key_value()
+                .map(|(_, block)| block.clone())
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<N: Network> Provider<N> for MockProvider<N> {
+    fn root(&self) -> &RootProvider<N> {
+        &self.root
+    }
+
+    fn get_block_number(&self) -> ProviderCall<[(); 0], U64, BlockNumber> {
+        let chain = self.read();
+        let block_number = chain
+            .block_number
+            .or_else(|| chain.blocks.keys().next_back().copied())
+            .ok_or_else(|| TransportErrorKind::custom_str("mock provider has no blocks"));
+        ProviderCall::ready(block_number)
+    }
+
+    fn get_block(&self, block: BlockId) -> EthGetBlock<N::BlockResponse> {
+        let found = self.find_block(block);
+        EthGetBlock::new_provider(
+            block,
+            Box::new(move |_| ProviderCall::<EthGetBlockParams, _>::ready(Ok(found.clone())))
+        )
+    }
+
+    fn call(&self, tx: N::TransactionRequest) -> EthCall<N, Bytes> {
+        EthCall::call(MockCaller(self.chain.clone()), tx)
+    }
+
+    async fn get_logs(&self, filter: &Filter) -> TransportResult<Vec<Log>> {
+        Ok(self
+            .read()
+            .logs
+            .iter()
+            .filter(|log| {
+                filter.matches(&log.inner)
+                    && log
+                        .block_number
+                        .is_some_and(|number| filter.matches_block_range(number))
+            })
+            .cloned()
+            .collect())
+    }
+}
+
+struct MockCaller<N: Network>(Arc<RwLock<MockChain<N>>>);
+
+impl<N: Network> Caller<N, Bytes> for MockCaller<N> {
+    fn call(
+        &self,
+        params: EthCallParams<N>
+    ) -> TransportResult<ProviderCall<EthCallParams<N>, Bytes>> {
+        let input = params.data().input().cloned().unwrap_or_default();
+        let output = self
+            .0
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .calls
+            .get(&input)
+            .cloned()
+            .ok_or_else(|| {
+                TransportErrorKind::custom_str(&format!("no mock response for call {input}"))
+            })?;
+        Ok(ProviderCall::ready(Ok(output)))
+    }
+
+    fn estimate_gas(
+        &self,
+        _: EthCallParams<N>
+    ) -> TransportResult<ProviderCall<EthCallParams<N>, Bytes>> {
+        Err(TransportErrorKind::custom_str("mock provider doesn't estimate gas"))
+    }
+
+    fn call_many(
+        &self,
+        _: EthCallManyParams<'_>
+    ) -> TransportResult<ProviderCall<EthCallManyParams<'static>, Bytes>> {
+        Err(TransportErrorKind::custom_str("mock provider doesn't support eth_callMany"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::{Address, B256, LogData};
+
+    use super::*;
+
+    fn log(address: Address, block: u64) -> Log {
+        Log {
+            inner: alloy_primitives::Log {
+                address,
+                data: LogData::new_unchecked(vec![B256::ZERO], Bytes::new())
+            },
+            block_number: Some(block),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn serves_the_chain_it_was_given() {
+        let provider = MockProvider::<Ethereum>::new();
+        let mut block = alloy_rpc_types::Block::<alloy_rpc_types::Transaction>::default();
+        block.header.inner.number = 7;
+        provider.add_block(block);
+        provider.add_logs([log(Address::with_last_byte(1), 5), log(Address::with_last_byte(2), 6)]);
+        provider.add_call(vec![1, 2], vec![3]);
+
+        assert_eq!(provider.get_block_number().await.unwrap(), 7);
+        assert!(
+            provider
+                .get_block(BlockId::number(7))
+                .await
+                .unwrap()
+                .is_some()
+        );
+        assert!(
+            provider
+                .get_block(BlockId::number(8))
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let logs = provider
+            .get_logs(
+                &Filter::new()
+                    .address(Address::with_last_byte(2))
+                    .from_block(0)
+                    .to_block(10)
+            )
+            .await
+            .unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].block_number, Some(6));
+
+        let call =
+            alloy_rpc_types::TransactionRequest::default().input(Bytes::from(vec![1, 2]).into());
+        assert_eq!(provider.call(call).await.unwrap(), Bytes::from(vec![3]));
+        let unknown = alloy_rpc_types::TransactionRequest::default();
+        assert!(provider.call(unknown).await.is_err());
+    }
+}
@@ -4,20 +4,24 @@ use alloy_primitives::{Address, B256, BlockNumber, I256, Log, U256, aliases::I24
 use alloy_provider::Provider;
 use alloy_sol_types::{SolEvent, SolType, sol};
 use itertools::Itertools;
-use uni_v4_common::{PoolError, V4Network};
+use uni_v4_common::{PoolError, SwapSimulationError, V4Network};
 use uni_v4_structure::{
     PoolId as AngstromPoolId, PoolKey,
     pool_registry::{PoolRegistry, UniswapPoolIdSet},
     ray::Ray,
-    sqrt_pricex96::SqrtPriceX96
+    sqrt_pricex96::SqrtPriceX96,
+    tick_info::TickInfo
+};
+use uniswap_v3_math::{
+    tick_bitmap::flip_tick,
+    tick_math::{MAX_TICK, MIN_TICK}
 };
-use uniswap_v3_math::tick_math::{MAX_TICK, MIN_TICK};
 
 use super::bindings::{
     get_uniswap_v_4_pool_data::GetUniswapV4PoolData,
     get_uniswap_v_4_tick_data::GetUniswapV4TickData
 };
-use crate::i128_to_i256;
+use crate::{i32_to_i24, i128_to_i256};
 
 sol! {
     #[derive(Debug)]
@@ -82,6 +86,7 @@ sol! {
     contract IUniswapV4Pool {
         event Swap(PoolId indexed id, address indexed sender, int128 amount0, int128 amount1, uint160 sqrtPriceX96, uint128 liquidity, int24 tick, uint24 fee);
         event ModifyLiquidity(PoolId indexed id, address indexed sender, int24 tickLower, int24 tickUpper, int256 liquidityDelta, bytes32 salt);
+        event Initialize(PoolId indexed id, address indexed currency0, address indexed currency1, uint24 fee, int24 tickSpacing, address hooks, uint160 sqrtPriceX96, int24 tick);
     }
 
     #[derive(Debug, PartialEq, Eq)]
@@ -168,6 +173,63 @@ impl<T: V4Network> DataLoader<T> {
     ) -> Self {
         Self { pool_id_set, pool_registry: Some(registry), pool_manager: Some(pool_manager) }
     }
+
+    /// Loads the initialized ticks on both sides of `center_tick`, scanning
+    /// `num_ticks` tick spacing steps in each direction, and builds the tick
+    /// map and bitmap for them.
+    pub async fn load_ticks<P: Provider<T>>(
+        &self,
+        center_tick: i32,
+        num_ticks: u16,
+        block_number: Option<BlockNumber>,
+        provider: Arc<P>
+    ) -> Result<(HashMap<i32, TickInfo>, HashMap<i16, U256>), PoolError> {
+        let id = self.id_set().uniswap_pool_id();
+        let tick_spacing = self
+            .pool_registry
+            .as_ref()
+            .and_then(|registry| registry.get(&id))
+            .ok_or(PoolError::PoolNotInitialized)?
+            .tickSpacing;
+
+        let mut ticks = HashMap::new();
+        for zero_for_one in [true, false] {
+            let (loaded, _) = self
+                .load_tick_data(
+                    i32_to_i24(center_tick)?,
+                    zero_for_one,
+                    num_ticks,
+                    tick_spacing,
+                    block_number,
+                    provider.clone()
+                )
+                .await?;
+
+            ticks.extend(
+                loaded
+                    .into_iter()
+                    .filter(|tick| tick.initialized)
+                    .map(|tick| {
+                        (
+                            tick.tick.as_i32(),
+                            TickInfo {
+                                liquidity_gross: tick.liquidityGross,
+                                liquidity_net:   tick.liquidityNet,
+                                initialized:     true
+                            }
+                        )
+                    })
+            );
+        }
+
+        let mut tick_bitmap = HashMap::new();
+        for tick in ticks.keys() {
+            flip_tick(&mut tick_bitmap, *tick, tick_spacing.as_i32())
+                .map_err(SwapSimulationError::from)?;
+        }
+
+        Ok((ticks, tick_bitmap))
+    }
 }
 
 impl<T: V4Network> PoolDataLoader<T> for DataLoader<T> {
@@ -291,8 +353,8 @@ impl<T: V4Network> PoolDataLoader<T> for DataLoader<T> {
         let swap_event = IUniswapV4Pool::Swap::decode_log(log)?;
         Ok(SwapEvent {
             sender:         swap_event.sender,
-            amount0:        i128_to_i256(swap_event.amount0),
-            amount1:        i128_to_i256(swap_event.amount1),
+            amount0:        i128_to_i256(swap_event.amount0)?,
+            amount1:        i128_to_i256(swap_event.amount1)?,
             sqrt_price_x96: U256::from(swap_event.sqrtPriceX96),
             liquidity:      swap_event.liquidity,
             tick:           swap_event.tick.as_i32()
@@ -316,3 +378,94 @@ impl From<(PoolKey, PoolDataV4)> for PoolData {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use alloy_network::Ethereum;
+    use alloy_primitives::{Bytes, aliases::U24};
+    use alloy_provider::{ProviderBuilder, mock::Asserter};
+    use alloy_sol_types::SolValue;
+    use uni_v4_structure::{L1FeeConfiguration, PoolKeyWithFees, pool_registry::L1PoolRegistry};
+
+    use super::*;
+
+    const ANGSTROM: Address = Address::repeat_byte(0xaa);
+
+    fn tick(tick: i32, liquidity_net: i128) -> TickData {
+        TickData {
+            initialized:    liquidity_net != 0,
+            tick:           I24::unchecked_from(tick),
+            liquidityGross: liquidity_net.unsigned_abs(),
+            liquidityNet:   liquidity_net
+        }
+    }
+
+    fn response(ticks: Vec<TickData>) -> Bytes {
+        let valid_to = U256::from(ticks.len());
+        TicksWithBlock { ticks, validTo: valid_to, blockNumber: U256::from(100) }
+            .abi_encode()
+            .into()
+    }
+
+    fn loader() -> DataLoader<Ethereum> {
+        let pool_key = PoolKey {
+            currency0:   Address::with_last_byte(1),
+            currency1:   Address::with_last_byte(2),
+            fee:         U24::ZERO,
+            tickSpacing: I24::unchecked_from(60),
+            hooks:       ANGSTROM
+        };
+        let registry = L1PoolRegistry::from_config(
+            ANGSTROM,
+            vec![PoolKeyWithFees {
+                pool_key,
+                fee_cfg: L1FeeConfiguration { bundle_fee: 0, swap_fee: 0, protocol_fee: 0 }
+            }]
+        );
+        DataLoader::new_with_registry(pool_key.into(), registry, Address::with_last_byte(0xbb))
+    }
+
+    #[tokio::test]
+    async fn load_ticks_merges_both_directions() {
+        let loader = loader();
+        let asserter = Asserter::new();
+        let provider = ProviderBuilder::new()
+            .disable_recommended_fillers()
+            .connect_mocked_client(asserter.clone());
+        // below the center, including an uninitialized word boundary
+        asserter.push_success(&response(vec![tick(-120, 500), tick(-15360, 0), tick(-15420, 100)]));
+        // above the center
+        asserter.push_success(&response(vec![tick(60, -500)]));
+
+        let (ticks, tick_bitmap) = loader
+            .load_ticks(0, 2, None, Arc::new(provider))
+            .await
+            .unwrap();
+
+        let mut loaded = ticks.keys().copied().collect::<Vec<_>>();
+        loaded.sort();
+        assert_eq!(loaded, vec![-15420, -120, 60]);
+        assert_eq!(ticks[&-120].liquidity_net, 500);
+        assert_eq!(ticks[&60].liquidity_gross, 500);
+
+        // -15420 / 60 = -257 lands in word -2, -120 / 60 = -2 in word -1
+        assert_eq!(tick_bitmap[&-2], U256::from(1) << 255);
+        assert_eq!(tick_bitmap[&-1], U256::from(1) << 254);
+        assert_eq!(tick_bitmap[&0], U256::from(1) << 1);
+    }
+
+    #[tokio::test]
+    async fn load_ticks_rejects_ticks_outside_i24() {
+        let provider = ProviderBuilder::new()
+            .disable_recommended_fillers()
+            .connect_mocked_client(Asserter::new());
+
+        let tick = 1 << 23;
+        let err = loader()
+            .load_ticks(tick, 2, None, Arc::new(provider))
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.to_string(), format!("overflow from i32 to i24 {tick}"));
+    }
+}
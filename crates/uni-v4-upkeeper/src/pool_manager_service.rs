@@ -1,30 +1,54 @@
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     pin::Pin,
     sync::Arc,
     task::{Context, Poll}
 };
 
 use alloy_network::Ethereum;
-use alloy_primitives::Address;
+use alloy_primitives::{Address, U256};
 use alloy_provider::Provider;
 use futures::{Future, Stream, StreamExt};
 use thiserror::Error;
-use tokio::sync::mpsc;
 use uni_v4_common::{PoolUpdate, UniswapPools, V4Network};
 use uni_v4_structure::{
     BaselinePoolState, L1FeeConfiguration, PoolId, PoolKey,
     fee_config::FeeConfig,
     pool_registry::PoolRegistry,
-    pool_updates::{L1PoolUpdate, Slot0Update}
+    pool_swap::Quote,
+    pool_updates::{L1PoolUpdate, Slot0Data, Slot0Update, UpdatePool}
 };
 
 use super::baseline_pool_factory::{BaselinePoolFactory, BaselinePoolFactoryError, UpdateMessage};
 use crate::{
     pool_providers::{PoolEventStream, ProviderChainInitialization},
-    slot0::Slot0Stream
+    slot0::{Slot0Stream, latest_slot0_updates},
+    update_channel::{UpdateChannel, UpdateSender}
 };
 
+/// Default cap on the number of updates buffered while the factory is busy
+pub const DEFAULT_MAX_PENDING_UPDATES: usize = 10_000;
+
+/// Called with the key and fee configuration of each pool detected after
+/// startup, returning whether the pool should be created
+pub type NewPoolCallback<T> =
+    Arc<dyn Fn(&PoolKey, &<T as V4Network>::FeeConfig) -> bool + Send + Sync>;
+
+/// Frees the ticks of pools that haven't been updated in a while, keeping a
+/// band around the current tick along with the nearest initialized tick on
+/// each side of it. The factory loads the rest back in once the price nears
+/// the edge of what is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TickEviction {
+    /// blocks without an update before a pool's ticks are evicted, also how
+    /// often pools are checked
+    pub inactive_blocks: u64,
+    /// tick spacings kept on each side of the current tick. Should be well
+    /// above the tick edge threshold, otherwise the evicted ticks are
+    /// requested again straight away
+    pub keep_ticks:      u16
+}
+
 /// Pool information combining BaselinePoolState with token metadata
 #[derive(Debug, Clone)]
 pub struct PoolInfo<T: V4Network> {
@@ -44,7 +68,15 @@ pub enum PoolManagerServiceError {
     #[error("Pool factory error: {0}")]
     PoolFactory(String),
     #[error("Baseline pool factory error: {0}")]
-    BaselineFactory(#[from] BaselinePoolFactoryError)
+    BaselineFactory(#[from] BaselinePoolFactoryError),
+    #[error("Start block {start_block} is outside of [{deploy_block}, {latest_block}]")]
+    InvalidStartBlock { start_block: u64, deploy_block: u64, latest_block: u64 },
+    #[error("Pool {0:?} is not tracked")]
+    UnknownPool(PoolId),
+    #[error("Quote error: {0}")]
+    Quote(String),
+    #[error("Tick band [{lower_tick}, {upper_tick}] is empty")]
+    InvalidTickBand { lower_tick: i32, upper_tick: i32 }
 }
 
 /// Service for managing Uniswap V4 pools with real-time block subscription
@@ -60,12 +92,14 @@ where
     pub(crate) pools:              UniswapPools<T>,
     pub(crate) current_block:      u64,
     pub(crate) auto_pool_creation: bool,
+    pub(crate) new_pool_callback:  Option<NewPoolCallback<T>>,
     pub(crate) slot0_stream:       Option<S>,
     // If we are loading more ticks at a block, we will queue up updates messages here
     // so that we don't hit any race conditions.
-    pending_updates:               Vec<PoolUpdate<T>>,
+    pending_updates:               PendingUpdates<T>,
+    tick_eviction:                 Option<TickEviction>,
     // Channel for sending updates instead of applying them directly
-    update_sender:                 Option<mpsc::Sender<PoolUpdate<T>>>
+    update_sender:                 Option<UpdateChannel<T>>
 }
 
 impl<P, T, Event, S> PoolManagerService<P, T, Event, S>
@@ -94,14 +128,10 @@ where
         slot0_stream: Option<S>,
         current_block: Option<u64>,
         ticks_per_batch: Option<usize>,
-        update_channel: Option<mpsc::Sender<PoolUpdate<T>>>
+        update_channel: Option<UpdateSender<T>>,
+        update_channel_pools: Option<HashSet<PoolId>>
     ) -> Result<Self, PoolManagerServiceError> {
-        // Use provided current_block or get current block
-        let current_block = if let Some(block) = current_block {
-            block
-        } else {
-            provider.get_block_number().await.unwrap()
-        };
+        let current_block = resolve_start_block(&*provider, deploy_block, current_block).await?;
 
         // Create factory with optional filtering
         let (factory, pools) = BaselinePoolFactory::new(
@@ -124,9 +154,14 @@ where
             pools: UniswapPools::new(pools, current_block),
             current_block: deploy_block,
             auto_pool_creation,
+            new_pool_callback: None,
             slot0_stream,
-            pending_updates: Vec::new(),
-            update_sender: update_channel
+            pending_updates: PendingUpdates::new(DEFAULT_MAX_PENDING_UPDATES),
+            tick_eviction: None,
+            update_sender: update_channel.map(|sender| match update_channel_pools {
+                Some(pools) => UpdateChannel::filtered(sender, pools),
+                None => UpdateChannel::new(sender)
+            })
         };
 
         service
@@ -180,29 +215,258 @@ where
         self.current_block
     }
 
+    /// Set how many updates may be queued while the factory is loading ticks
+    /// before superseded slot0 refreshes start getting dropped
+    pub fn set_max_pending_updates(&mut self, max_pending_updates: usize) {
+        self.pending_updates.max_len = max_pending_updates;
+    }
+
+    /// Start evicting the ticks of inactive pools
+    pub fn set_tick_eviction(&mut self, policy: TickEviction) {
+        self.tick_eviction = Some(policy);
+    }
+
+    /// Runs the [`TickEviction`] policy, if any, once every `inactive_blocks`
+    fn evict_inactive_ticks(&self) {
+        let Some(policy) = self.tick_eviction else { return };
+        if policy.inactive_blocks == 0 || !self.current_block.is_multiple_of(policy.inactive_blocks)
+        {
+            return;
+        }
+
+        for mut entry in self.pools.get_pools().iter_mut() {
+            let pool = entry.value_mut();
+            if self.current_block.saturating_sub(pool.last_updated_block()) < policy.inactive_blocks
+            {
+                continue;
+            }
+
+            let band = pool.tick_spacing() * policy.keep_ticks as i32;
+            let tick = pool.current_tick();
+            let evicted = pool.evict_ticks_outside(tick - band, tick + band);
+            if evicted > 0 {
+                tracing::debug!("Evicted {evicted} ticks from inactive pool {:?}", entry.key());
+            }
+        }
+    }
+
+    /// Number of updates buffered because the update channel is full. Stays at
+    /// zero unless the consumer is falling behind.
+    pub fn lagging_updates(&self) -> usize {
+        self.update_sender
+            .as_ref()
+            .map(UpdateChannel::lagging)
+            .unwrap_or_default()
+    }
+
+    /// Whether the factory is done with the pools and ticks it was asked to
+    /// load. Pools created at startup can still be waiting on ticks after
+    /// `build().await`.
+    pub fn is_initialized(&self) -> bool {
+        !self.factory.is_processing()
+    }
+
+    /// Drives the factory until [`Self::is_initialized`], applying what it
+    /// loads. Await it before spawning the service, which otherwise does this
+    /// as part of its polling.
+    pub async fn wait_until_ready(&mut self) {
+        while !self.is_initialized() {
+            // a failed pool creation empties the factory without yielding,
+            // and its stream stays pending once there's nothing left
+            let next = std::future::poll_fn(|cx| match self.factory.poll_next_unpin(cx) {
+                Poll::Pending if !self.factory.is_processing() => Poll::Ready(None),
+                poll => poll
+            })
+            .await;
+            let Some(update) = next else {
+                break;
+            };
+            self.dispatch_update(update.into());
+        }
+    }
+
     /// Gives a reference to the optional slot0 stream
     pub fn slot0_stream_ref(&self) -> Option<&S> {
         self.slot0_stream.as_ref()
     }
 
+    /// Loads the state of a tracked pool as it was at a past `block`, for
+    /// historical quotes. Slot0 and ticks are read at that block, so blocks
+    /// older than the node's pruning window need an archive node. The fees are
+    /// the pool's current ones, not the ones in effect at `block`.
+    pub async fn pool_state_at_block(
+        &self,
+        pool_id: PoolId,
+        block: u64
+    ) -> Result<BaselinePoolState<T>, PoolManagerServiceError> {
+        let fee_cfg = self
+            .pools
+            .get_pools()
+            .get(&pool_id)
+            .map(|pool| *pool.fee_config())
+            .ok_or(PoolManagerServiceError::UnknownPool(pool_id))?;
+
+        Ok(self
+            .factory
+            .pool_state_at_block(pool_id, block, fee_cfg)
+            .await?)
+    }
+
+    /// Quotes an exact input swap in the pool's current mode. When the swap
+    /// runs past the loaded ticks, a band of ticks past the edge it hit is
+    /// loaded and the quote retried once. Works off the service's own pools,
+    /// so in channel mode the loaded ticks only reach the consumer.
+    pub async fn quote_loading_ticks(
+        &mut self,
+        pool_id: PoolId,
+        amount_in: u128,
+        direction: bool
+    ) -> Result<Quote, PoolManagerServiceError> {
+        let quote = |pools: &UniswapPools<T>| {
+            let pool = pools
+                .get_pool(&pool_id)
+                .ok_or(PoolManagerServiceError::UnknownPool(pool_id))?;
+            Ok::<_, PoolManagerServiceError>((
+                pool.quote(amount_in, direction, pool.mode().is_bundle()),
+                pool.tick_spacing(),
+                if direction {
+                    pool.liquidity().get_min_initialized_tick()
+                } else {
+                    pool.liquidity().get_max_initialized_tick()
+                }
+            ))
+        };
+
+        let (result, tick_spacing, edge) = quote(&self.pools)?;
+        let (err, edge) = match (result, edge) {
+            (Ok(quote), _) => return Ok(quote),
+            (Err(err), None) => return Err(PoolManagerServiceError::Quote(err.to_string())),
+            (Err(err), Some(edge)) => (err, edge)
+        };
+        tracing::debug!("Quote for pool {pool_id:?} failed ({err}), loading more ticks");
+
+        let start_tick = if direction { edge - tick_spacing } else { edge + tick_spacing };
+        let (ticks, tick_bitmap) = self
+            .factory
+            .load_more_ticks(
+                pool_id,
+                direction,
+                start_tick,
+                tick_spacing,
+                self.factory.tick_band(),
+                Some(self.current_block)
+            )
+            .await?;
+        self.dispatch_update(PoolUpdate::NewTicks { pool_id, ticks, tick_bitmap });
+
+        quote(&self.pools)?
+            .0
+            .map_err(|e| PoolManagerServiceError::Quote(e.to_string()))
+    }
+
+    /// Loads whatever part of `[lower_tick, upper_tick]` lies past the pool's
+    /// loaded ticks, ahead of a large trade into that band, and returns once
+    /// the ticks are applied. Like [`Self::quote_loading_ticks`], in channel
+    /// mode the loaded ticks only reach the consumer.
+    pub async fn prewarm_ticks(
+        &mut self,
+        pool_id: PoolId,
+        lower_tick: i32,
+        upper_tick: i32
+    ) -> Result<(), PoolManagerServiceError> {
+        if lower_tick > upper_tick {
+            return Err(PoolManagerServiceError::InvalidTickBand { lower_tick, upper_tick });
+        }
+
+        let (tick_spacing, min_tick, max_tick) = {
+            let pool = self
+                .pools
+                .get_pool(&pool_id)
+                .ok_or(PoolManagerServiceError::UnknownPool(pool_id))?;
+            let liquidity = pool.liquidity();
+            let current_tick = pool.current_tick();
+            (
+                pool.tick_spacing(),
+                liquidity.get_min_initialized_tick().unwrap_or(current_tick),
+                liquidity.get_max_initialized_tick().unwrap_or(current_tick)
+            )
+        };
+        // in tick spacings, counting from the loaded edge
+        let missing = |from: i32, to: i32| {
+            let spacings = ((to - from).max(0) as u32).div_ceil(tick_spacing as u32);
+            u16::try_from(spacings).unwrap_or(u16::MAX)
+        };
+        let (below, above) = (missing(lower_tick, min_tick), missing(max_tick, upper_tick));
+        if below == 0 && above == 0 {
+            return Ok(());
+        }
+
+        // start one spacing past the loaded edges, like `quote_loading_ticks`
+        let block = Some(self.current_block);
+        let (below_ticks, above_ticks) = futures::join!(
+            self.factory.load_more_ticks(
+                pool_id,
+                true,
+                min_tick - tick_spacing,
+                tick_spacing,
+                below,
+                block
+            ),
+            self.factory.load_more_ticks(
+                pool_id,
+                false,
+                max_tick + tick_spacing,
+                tick_spacing,
+                above,
+                block
+            )
+        );
+        let ((mut ticks, below_bitmap), (above_ticks, above_bitmap)) = (below_ticks?, above_ticks?);
+        ticks.extend(above_ticks);
+
+        // the bitmap words are applied whole, so keep the bits of the ticks
+        // already loaded in the words at the edges
+        let mut tick_bitmap = HashMap::new();
+        for (word_pos, word) in below_bitmap.into_iter().chain(above_bitmap) {
+            *tick_bitmap.entry(word_pos).or_insert(U256::ZERO) |= word;
+        }
+        if let Some(pool) = self.pools.get_pool(&pool_id) {
+            for (word_pos, word) in &mut tick_bitmap {
+                *word |= pool.liquidity().tick_bitmap_word(*word_pos);
+            }
+        }
+
+        self.dispatch_update(PoolUpdate::NewTicks { pool_id, ticks, tick_bitmap });
+        Ok(())
+    }
+
+    /// Whether a pool detected after startup gets created
+    pub(crate) fn should_create_pool(&self, pool_key: &PoolKey, fee_cfg: &T::FeeConfig) -> bool {
+        accepts_new_pool::<T>(
+            self.auto_pool_creation,
+            self.new_pool_callback.as_ref(),
+            pool_key,
+            fee_cfg
+        )
+    }
+
     /// Handle a new pool creation
     pub(crate) fn handle_new_pool(
         &mut self,
         pool_key: PoolKey,
         block_number: u64,
-        fee_cfg: T::FeeConfig
+        fee_cfg: T::FeeConfig,
+        initial_slot0: Option<Slot0Data>
     ) {
         self.factory
-            .queue_pool_creation(pool_key, block_number, fee_cfg);
+            .queue_pool_creation(pool_key, block_number, fee_cfg, initial_slot0);
     }
 
     /// Dispatch an update either via channel or apply directly
     fn dispatch_update(&mut self, update: PoolUpdate<T>) {
-        if let Some(sender) = &self.update_sender {
-            // Channel mode: send the update
-            if let Err(e) = sender.try_send(update.clone()) {
-                tracing::error!("Failed to send update via channel: {}", e);
-            }
+        if let Some(sender) = &mut self.update_sender {
+            // Channel mode: send the update, buffering it if the channel is full
+            sender.send(update.clone());
 
             // Always process certain critical updates internally even in channel mode
             match &update {
@@ -228,6 +492,7 @@ where
         match &update {
             PoolUpdate::NewBlock(block_number) => {
                 self.current_block = *block_number;
+                self.evict_inactive_ticks();
             }
             PoolUpdate::SwapEvent { pool_id, event, .. } => {
                 tracing::debug!("Swap event for pool {:?}: {:?}", pool_id, event);
@@ -275,6 +540,9 @@ where
                 // These are handled by update_pools
                 tracing::debug!("NewTicks update will be handled by update_pools");
             }
+            PoolUpdate::TickBitmapDelta { pool_id, word_pos, .. } => {
+                tracing::debug!("Tick bitmap word {word_pos} changed for pool {pool_id:?}");
+            }
         }
     }
 }
@@ -297,15 +565,7 @@ where
         match this.factory.poll_next_unpin(cx) {
             Poll::Ready(Some(update)) => {
                 // Convert factory update to PoolUpdate and dispatch
-                let pool_update = match update {
-                    UpdateMessage::NewTicks(pool_id, ticks, tick_bitmap) => {
-                        PoolUpdate::NewTicks { pool_id, ticks, tick_bitmap }
-                    }
-                    UpdateMessage::NewPool(pool_id, state) => {
-                        PoolUpdate::NewPoolState { pool_id, state }
-                    }
-                };
-                this.dispatch_update(pool_update);
+                this.dispatch_update(update.into());
             }
             Poll::Ready(None) => {
                 // Stream ended, which shouldn't happen in our case.
@@ -315,7 +575,7 @@ where
         }
 
         if !this.factory.is_processing() {
-            let updates = this.pending_updates.drain(..).collect::<Vec<_>>();
+            let updates = this.pending_updates.drain();
 
             if this.update_sender.is_some() {
                 // Channel mode: dispatch each update
@@ -380,10 +640,152 @@ where
             this.handle_slot0_updates(slot0_updates);
         }
 
+        if let Some(sender) = this.update_sender.as_mut() {
+            sender.poll_flush(cx);
+        }
+
         Poll::Pending
     }
 }
 
+/// Use the provided start block, or the latest one if none was given. A
+/// provided block has to lie between the deploy block and the chain head.
+async fn resolve_start_block<P, T>(
+    provider: &P,
+    deploy_block: u64,
+    start_block: Option<u64>
+) -> Result<u64, PoolManagerServiceError>
+where
+    P: Provider<T>,
+    T: V4Network
+{
+    let latest_block = provider
+        .get_block_number()
+        .await
+        .map_err(|e| PoolManagerServiceError::Provider(e.to_string()))?;
+
+    let Some(start_block) = start_block else { return Ok(latest_block) };
+    if !(deploy_block..=latest_block).contains(&start_block) {
+        return Err(PoolManagerServiceError::InvalidStartBlock {
+            start_block,
+            deploy_block,
+            latest_block
+        });
+    }
+
+    Ok(start_block)
+}
+
+/// The callback decides when one is set, otherwise every new pool is created
+/// if auto pool creation is on
+fn accepts_new_pool<T: V4Network>(
+    auto_pool_creation: bool,
+    callback: Option<&NewPoolCallback<T>>,
+    pool_key: &PoolKey,
+    fee_cfg: &T::FeeConfig
+) -> bool {
+    callback.map_or(auto_pool_creation, |callback| callback(pool_key, fee_cfg))
+}
+
+/// Updates buffered while the factory is loading ticks.
+///
+/// Slot0 refreshes only overwrite a pool's slot0, so once the buffer grows
+/// past `max_len` a refresh is dropped when a later swap or refresh for the
+/// same pool follows it with no liquidity event for that pool in between.
+/// Swaps are always kept as they also count towards the pool's session fees,
+/// and so are liquidity, fee, tick and block updates; if those alone exceed
+/// the cap the buffer keeps growing and a warning is logged.
+pub(crate) struct PendingUpdates<T: V4Network> {
+    updates: Vec<PoolUpdate<T>>,
+    max_len: usize
+}
+
+impl<T: V4Network> PendingUpdates<T> {
+    pub(crate) fn new(max_len: usize) -> Self {
+        Self { updates: Vec::new(), max_len }
+    }
+
+    pub(crate) fn extend(&mut self, updates: impl IntoIterator<Item = PoolUpdate<T>>) {
+        self.updates.extend(updates);
+        if self.updates.len() > self.max_len {
+            self.drop_superseded_slot0_refreshes();
+        }
+    }
+
+    /// Takes every queued update. A removed pool's updates queued after its
+    /// removal are dropped, up to the point the pool is created again.
+    pub(crate) fn drain(&mut self) -> Vec<PoolUpdate<T>> {
+        let mut updates = std::mem::take(&mut self.updates);
+        let before = updates.len();
+        let mut removed = HashSet::new();
+        updates.retain(|update| {
+            let Some(pool_id) = update.pool_id() else { return true };
+            let (creates, removes) = match update {
+                PoolUpdate::NewPoolState { .. } => (true, false),
+                PoolUpdate::ChainSpecific { update, .. } => (
+                    update.is_initialization_event() && !update.is_removal_event(),
+                    update.is_removal_event()
+                ),
+                _ => (false, false)
+            };
+
+            if removes {
+                removed.insert(pool_id);
+            } else if creates {
+                removed.remove(&pool_id);
+            } else if removed.contains(&pool_id) {
+                return false;
+            }
+            true
+        });
+
+        if updates.len() < before {
+            tracing::debug!(
+                "Dropped {} pending updates queued after their pool was removed",
+                before - updates.len()
+            );
+        }
+        updates
+    }
+
+    fn drop_superseded_slot0_refreshes(&mut self) {
+        let before = self.updates.len();
+        // pools whose slot0 gets overwritten later on, walking back from the end
+        let mut superseded = HashSet::new();
+        let keep = self
+            .updates
+            .iter()
+            .rev()
+            .map(|update| match update {
+                PoolUpdate::SwapEvent { pool_id, .. } => {
+                    superseded.insert(*pool_id);
+                    true
+                }
+                PoolUpdate::UpdatedSlot0 { pool_id, .. } => superseded.insert(*pool_id),
+                PoolUpdate::LiquidityEvent { pool_id, .. } => {
+                    superseded.remove(pool_id);
+                    true
+                }
+                _ => true
+            })
+            .collect::<Vec<_>>();
+        let mut keep = keep.into_iter().rev();
+        self.updates.retain(|_| keep.next().unwrap());
+
+        tracing::debug!(
+            "dropped {} superseded slot0 refreshes from the pending queue",
+            before - self.updates.len()
+        );
+        if self.updates.len() > self.max_len {
+            tracing::warn!(
+                "{} pending updates still exceed the cap of {} while the factory is processing",
+                self.updates.len(),
+                self.max_len
+            );
+        }
+    }
+}
+
 pub trait PoolEventProcessor<T: V4Network> {
     fn handle_chain_specific_update(&mut self, pool_id: PoolId, update: &T::PoolUpdate);
 
@@ -408,40 +810,36 @@ where
                 swap_fee,
                 protocol_fee,
                 tick_spacing,
+                initial_slot0,
                 block,
                 ..
             } => {
-                if self.auto_pool_creation {
-                    // Reconstruct pool_key from the NewPool data
-                    // We need to get the pool_key from the registry
-                    if let Some(pool_key) = self.factory.registry().get(pool_id) {
-                        self.handle_new_pool(
-                            *pool_key,
-                            *block,
-                            L1FeeConfiguration {
-                                bundle_fee:   *bundle_fee,
-                                swap_fee:     *swap_fee,
-                                protocol_fee: *protocol_fee
-                            }
-                        );
+                // Reconstruct pool_key from the NewPool data
+                // We need to get the pool_key from the registry
+                let Some(pool_key) = self.factory.registry().get(pool_id).copied() else {
+                    tracing::warn!("Pool {:?} not found in registry", pool_id);
+                    return;
+                };
+                let fee_cfg = L1FeeConfiguration {
+                    bundle_fee:   *bundle_fee,
+                    swap_fee:     *swap_fee,
+                    protocol_fee: *protocol_fee
+                };
+
+                if self.should_create_pool(&pool_key, &fee_cfg) {
+                    self.handle_new_pool(pool_key, *block, fee_cfg, initial_slot0.clone());
 
-                        tracing::info!(
-                            "Pool configured: {:?}, bundle_fee: {}, swap_fee: {}, protocol_fee: \
-                             {}, tick_spacing: {}",
-                            pool_id,
-                            bundle_fee,
-                            swap_fee,
-                            protocol_fee,
-                            tick_spacing
-                        );
-                    } else {
-                        tracing::warn!("Pool {:?} not found in registry", pool_id);
-                    }
-                } else {
                     tracing::info!(
-                        "Ignoring pool configured event (auto creation disabled): {:?}",
-                        pool_id
+                        "Pool configured: {:?}, bundle_fee: {}, swap_fee: {}, protocol_fee: {}, \
+                         tick_spacing: {}",
+                        pool_id,
+                        bundle_fee,
+                        swap_fee,
+                        protocol_fee,
+                        tick_spacing
                     );
+                } else {
+                    tracing::info!("Ignoring pool configured event: {:?}", pool_id);
                 }
             }
             L1PoolUpdate::PoolRemoved { pool_id, .. } => {
@@ -459,7 +857,7 @@ where
     }
 
     fn handle_slot0_updates(&mut self, slot0_updates: Vec<Slot0Update>) {
-        for update in slot0_updates {
+        for update in latest_slot0_updates(slot0_updates, self.pools.get_block()) {
             let pool_update = PoolUpdate::ChainSpecific {
                 pool_id: update.uni_pool_id,
                 update:  L1PoolUpdate::Slot0Update(update)
@@ -485,3 +883,451 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use alloy_provider::{ProviderBuilder, mock::Asserter};
+    use futures::channel::mpsc::{UnboundedReceiver, UnboundedSender, unbounded};
+    use uni_v4_structure::pool_updates::{ModifyLiquidityEventData, SwapEventData};
+
+    use super::*;
+    use crate::{mock_provider::MockProvider, slot0::Slot0SubscriptionAck};
+
+    /// Event stream fed by the test
+    struct TestEvents<T: V4Network>(UnboundedReceiver<Vec<PoolUpdate<T>>>);
+
+    impl<T: V4Network> Stream for TestEvents<T> {
+        type Item = Vec<PoolUpdate<T>>;
+
+        fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            self.0.poll_next_unpin(cx)
+        }
+    }
+
+    impl<T: V4Network> PoolEventStream<T> for TestEvents<T> {
+        fn start_tracking_pool(&mut self, _: PoolId) {}
+
+        fn stop_tracking_pool(&mut self, _: PoolId) {}
+
+        fn set_pool_registry(&mut self, _: T::PoolRegistry) {}
+    }
+
+    /// Slot0 stream that only keeps track of its subscriptions
+    #[derive(Default)]
+    struct RecordingSlot0Stream(HashSet<PoolId>);
+
+    impl Stream for RecordingSlot0Stream {
+        type Item = Slot0Update;
+
+        fn poll_next(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            Poll::Pending
+        }
+    }
+
+    impl Slot0Stream for RecordingSlot0Stream {
+        fn subscribe_pools(&mut self, pools: HashSet<PoolId>) -> Slot0SubscriptionAck {
+            self.0.extend(pools.iter().copied());
+            Slot0SubscriptionAck::ready(Ok(pools))
+        }
+
+        fn unsubscribe_pools(&mut self, pools: HashSet<PoolId>) -> Slot0SubscriptionAck {
+            self.0.retain(|pool_id| !pools.contains(pool_id));
+            Slot0SubscriptionAck::ready(Ok(pools))
+        }
+
+        fn subscribed_pools(&self) -> &HashSet<PoolId> {
+            &self.0
+        }
+    }
+
+    type TestService<T> =
+        PoolManagerService<MockProvider<T>, T, TestEvents<T>, RecordingSlot0Stream>;
+
+    /// A service at block 100 of an otherwise empty mock chain, knowing the
+    /// pools in `registry` without having loaded any of them. Events sent on
+    /// the returned sender come out of its event stream.
+    async fn test_service<T>(
+        address_book: T::AddressBook,
+        registry: T::PoolRegistry
+    ) -> (TestService<T>, MockProvider<T>, UnboundedSender<Vec<PoolUpdate<T>>>)
+    where
+        T: V4Network,
+        TestService<T>: PoolEventProcessor<T>,
+        MockProvider<T>: ProviderChainInitialization<T>
+    {
+        let provider = MockProvider::<T>::new();
+        provider.set_block_number(100);
+        let (sender, events) = unbounded();
+
+        let service = PoolManagerService::new(
+            Arc::new(provider.clone()),
+            TestEvents(events),
+            address_book,
+            registry,
+            Address::ZERO,
+            100,
+            None,
+            None,
+            None,
+            true,
+            Some(RecordingSlot0Stream::default()),
+            Some(100),
+            None,
+            None,
+            None
+        )
+        .await
+        .unwrap();
+
+        (service, provider, sender)
+    }
+
+    fn swap(pool_id: PoolId, block: u64, tick: i32) -> PoolUpdate<Ethereum> {
+        PoolUpdate::SwapEvent {
+            pool_id,
+            block,
+            tx_index: 0,
+            log_index: 0,
+            event: SwapEventData {
+                sender: Address::ZERO,
+                amount0: 0,
+                amount1: 0,
+                sqrt_price_x96: Default::default(),
+                liquidity: 0,
+                tick,
+                fee: 0
+            }
+        }
+    }
+
+    fn liquidity(pool_id: PoolId, block: u64) -> PoolUpdate<Ethereum> {
+        PoolUpdate::LiquidityEvent {
+            pool_id,
+            block,
+            tx_index: 1,
+            log_index: 0,
+            event: ModifyLiquidityEventData {
+                sender:          Address::ZERO,
+                tick_lower:      -60,
+                tick_upper:      60,
+                liquidity_delta: alloy_primitives::I256::ONE,
+                salt:            [0; 32]
+            }
+        }
+    }
+
+    fn slot0(pool_id: PoolId, tick: i32) -> PoolUpdate<Ethereum> {
+        PoolUpdate::UpdatedSlot0 {
+            pool_id,
+            data: Slot0Data { sqrt_price_x96: Default::default(), tick, liquidity: 0 }
+        }
+    }
+
+    #[tokio::test]
+    async fn start_block_must_be_within_deploy_and_head() {
+        let asserter = Asserter::new();
+        let provider = ProviderBuilder::new()
+            .disable_recommended_fillers()
+            .connect_mocked_client(asserter.clone());
+
+        for _ in 0..3 {
+            asserter.push_success(&"0x64");
+        }
+        assert_eq!(
+            resolve_start_block::<_, Ethereum>(&provider, 10, Some(50))
+                .await
+                .unwrap(),
+            50
+        );
+        assert_eq!(
+            resolve_start_block::<_, Ethereum>(&provider, 10, None)
+                .await
+                .unwrap(),
+            100
+        );
+        assert!(matches!(
+            resolve_start_block::<_, Ethereum>(&provider, 10, Some(1_000)).await,
+            Err(PoolManagerServiceError::InvalidStartBlock {
+                start_block:  1_000,
+                deploy_block: 10,
+                latest_block: 100
+            })
+        ));
+
+        asserter.push_success(&"0x64");
+        assert!(matches!(
+            resolve_start_block::<_, Ethereum>(&provider, 10, Some(5)).await,
+            Err(PoolManagerServiceError::InvalidStartBlock { .. })
+        ));
+    }
+
+    #[test]
+    fn new_pool_callback_picks_pools() {
+        let pool_key = |fee: u32| PoolKey {
+            currency0:   Address::with_last_byte(1),
+            currency1:   Address::with_last_byte(2),
+            fee:         alloy_primitives::aliases::U24::from(fee),
+            tickSpacing: alloy_primitives::aliases::I24::unchecked_from(60),
+            hooks:       Address::ZERO
+        };
+        let fee_cfg = L1FeeConfiguration { bundle_fee: 0, swap_fee: 3000, protocol_fee: 0 };
+        let callback: NewPoolCallback<Ethereum> = Arc::new(|key, _| key.fee.to::<u32>() == 500);
+        let accepts = |auto_pool_creation, callback, fee| {
+            accepts_new_pool::<Ethereum>(auto_pool_creation, callback, &pool_key(fee), &fee_cfg)
+        };
+
+        // the callback overrides auto pool creation either way
+        for auto_pool_creation in [true, false] {
+            assert!(accepts(auto_pool_creation, Some(&callback), 500));
+            assert!(!accepts(auto_pool_creation, Some(&callback), 3000));
+        }
+        assert!(accepts(true, None, 3000));
+        assert!(!accepts(false, None, 3000));
+    }
+
+    #[test]
+    fn flooding_slot0_refreshes_stays_within_cap() {
+        let pools = [PoolId::with_last_byte(1), PoolId::with_last_byte(2)];
+        let mut pending = PendingUpdates::<Ethereum>::new(8);
+
+        for tick in 0..1_000 {
+            pending.extend(pools.iter().map(|pool_id| slot0(*pool_id, tick)));
+            assert!(pending.updates.len() <= 8);
+        }
+
+        // only the latest refresh of each pool survives compaction
+        let updates = pending.drain();
+        assert!(updates.iter().all(|update| matches!(
+            update,
+            PoolUpdate::UpdatedSlot0 { data, .. } if data.tick >= 996
+        )));
+    }
+
+    #[test]
+    fn swaps_and_liquidity_events_are_never_dropped() {
+        let pool_id = PoolId::with_last_byte(1);
+        let mut pending = PendingUpdates::<Ethereum>::new(2);
+
+        pending.extend([
+            swap(pool_id, 1, 1),
+            liquidity(pool_id, 1),
+            swap(pool_id, 2, 2),
+            slot0(pool_id, 2),
+            swap(pool_id, 3, 3)
+        ]);
+
+        // the refresh is overwritten by the last swap, everything else stays
+        let updates = pending.drain();
+        assert_eq!(updates.len(), 4);
+        assert!(matches!(updates[0], PoolUpdate::SwapEvent { block: 1, .. }));
+        assert!(matches!(updates[1], PoolUpdate::LiquidityEvent { .. }));
+        assert!(matches!(updates[2], PoolUpdate::SwapEvent { block: 2, .. }));
+        assert!(matches!(updates[3], PoolUpdate::SwapEvent { block: 3, .. }));
+    }
+
+    #[test]
+    fn liquidity_events_keep_the_refresh_before_them() {
+        let pool_id = PoolId::with_last_byte(1);
+        let other = PoolId::with_last_byte(2);
+        let mut pending = PendingUpdates::<Ethereum>::new(2);
+
+        pending.extend([
+            slot0(pool_id, 1),
+            slot0(other, 1),
+            liquidity(pool_id, 1),
+            slot0(pool_id, 2),
+            slot0(other, 2)
+        ]);
+
+        let updates = pending.drain();
+        assert_eq!(updates.len(), 4);
+        assert!(matches!(&updates[0], PoolUpdate::UpdatedSlot0 { data, .. } if data.tick == 1));
+        assert!(matches!(updates[1], PoolUpdate::LiquidityEvent { .. }));
+        assert!(
+            matches!(updates[2], PoolUpdate::UpdatedSlot0 { pool_id: id, .. } if id == pool_id)
+        );
+        assert!(matches!(updates[3], PoolUpdate::UpdatedSlot0 { pool_id: id, .. } if id == other));
+    }
+
+    #[tokio::test]
+    async fn removed_pools_drop_their_queued_updates() {
+        use uni_v4_structure::{L1AddressBook, pool_registry::L1PoolRegistry};
+
+        let removed = PoolId::with_last_byte(1);
+        let other = PoolId::with_last_byte(2);
+        let pool_removed = |block| PoolUpdate::ChainSpecific {
+            pool_id: removed,
+            update:  L1PoolUpdate::PoolRemoved {
+                pool_id: removed,
+                block,
+                tx_index: 1,
+                log_index: 0
+            }
+        };
+        let new_pool = |block| PoolUpdate::ChainSpecific {
+            pool_id: removed,
+            update:  L1PoolUpdate::NewPool {
+                pool_id: removed,
+                token0: Address::with_last_byte(1),
+                token1: Address::with_last_byte(2),
+                bundle_fee: 0,
+                swap_fee: 3000,
+                protocol_fee: 0,
+                tick_spacing: 60,
+                initial_slot0: None,
+                block,
+                tx_index: 1,
+                log_index: 0
+            }
+        };
+        let (mut service, _provider, _events) = test_service::<Ethereum>(
+            L1AddressBook::new(Address::ZERO, Address::ZERO),
+            L1PoolRegistry::from_config(Address::ZERO, vec![])
+        )
+        .await;
+        let (sender, mut receiver) = tokio::sync::mpsc::channel(16);
+        service.update_sender = Some(UpdateChannel::new(sender.into()));
+
+        // queued while the factory was loading ticks
+        service.pending_updates.extend([
+            swap(removed, 101, 1),
+            swap(other, 101, 1),
+            pool_removed(102),
+            swap(removed, 102, 2),
+            new_pool(103),
+            swap(removed, 103, 3)
+        ]);
+        let waker = futures::task::noop_waker();
+        assert!(
+            Pin::new(&mut service)
+                .poll(&mut Context::from_waker(&waker))
+                .is_pending()
+        );
+
+        let mut forwarded = Vec::new();
+        while let Ok(update) = receiver.try_recv() {
+            forwarded.push((update.pool_id().unwrap(), update.block().unwrap()));
+        }
+        // the swap between the removal and the re-creation never goes out
+        assert_eq!(
+            forwarded,
+            vec![(removed, 101), (other, 101), (removed, 102), (removed, 103), (removed, 103)]
+        );
+    }
+
+    #[tokio::test]
+    async fn evicted_pools_stay_quotable() {
+        use uni_v4_structure::{
+            L1AddressBook,
+            pool_registry::L1PoolRegistry,
+            testing::{l1_fee_config, pool_with_positions}
+        };
+
+        let pool_id = PoolId::with_last_byte(1);
+        let (mut service, ..) = test_service::<Ethereum>(
+            L1AddressBook::new(Address::ZERO, Address::ZERO),
+            L1PoolRegistry::from_config(Address::ZERO, vec![])
+        )
+        .await;
+        let mut state = pool_with_positions(
+            0,
+            &[(-600, 600, 10u128.pow(18)), (-12_000, 12_000, 10u128.pow(18))],
+            l1_fee_config()
+        );
+        // loaded ticks without liquidity have no bitmap bit, aligned or not
+        for tick in [30_000, 30_001] {
+            state
+                .liquidity_mut()
+                .initialized_ticks_mut()
+                .insert(tick, Default::default());
+        }
+        service.dispatch_update(PoolUpdate::NewPoolState { pool_id, state });
+        // a band of one tick spacing holds none of the pool's ticks
+        service.set_tick_eviction(TickEviction { inactive_blocks: 10, keep_ticks: 1 });
+
+        service.dispatch_update(PoolUpdate::NewBlock(110));
+
+        let pools = service.get_pools();
+        let pool = pools.get_pool(&pool_id).unwrap();
+        let liquidity = pool.liquidity();
+        let mut kept = liquidity
+            .initialized_ticks()
+            .keys()
+            .copied()
+            .collect::<Vec<_>>();
+        kept.sort();
+        assert_eq!(kept, vec![-600, 600]);
+        let bit = |tick: i32| {
+            let compressed = tick / 60;
+            let word = liquidity.tick_bitmap_word((compressed >> 8) as i16);
+            word.bit(compressed.rem_euclid(256) as usize)
+        };
+        assert!(bit(-600) && bit(600));
+        assert!(!bit(-12_000) && !bit(12_000) && !bit(30_000));
+        drop(pool);
+        // served from the kept ticks, without going to the chain
+        let quote = service
+            .quote_loading_ticks(pool_id, 10u128.pow(15), true)
+            .await
+            .unwrap();
+        assert!(quote.amount_out > 0);
+    }
+
+    #[tokio::test]
+    async fn loading_ticks_for_unregistered_pools_errors() {
+        use uni_v4_structure::{
+            L1AddressBook,
+            pool_registry::L1PoolRegistry,
+            testing::{l1_fee_config, pool_with_positions}
+        };
+
+        let pool_id = PoolId::with_last_byte(1);
+        let (mut service, ..) = test_service::<Ethereum>(
+            L1AddressBook::new(Address::ZERO, Address::ZERO),
+            L1PoolRegistry::from_config(Address::ZERO, vec![])
+        )
+        .await;
+        // tracked, but never added to the factory's registry
+        let state = pool_with_positions(0, &[(-600, 600, 10u128.pow(18))], l1_fee_config());
+        service.dispatch_update(PoolUpdate::NewPoolState { pool_id, state });
+
+        assert!(matches!(
+            service.prewarm_ticks(pool_id, -6_000, 6_000).await,
+            Err(PoolManagerServiceError::BaselineFactory(_))
+        ));
+    }
+
+    #[cfg(feature = "l2")]
+    #[tokio::test]
+    async fn l2_pools_subscribe_to_slot0_once_created() {
+        use op_alloy_network::Optimism;
+        use uni_v4_structure::{
+            PoolKeyWithFees,
+            l2_structure::{L2AddressBook, pool_registry::L2PoolRegistry},
+            testing::{l2_fee_config, pool_with_positions}
+        };
+
+        let pool_key = PoolKey {
+            currency0:   Address::ZERO,
+            currency1:   Address::with_last_byte(2),
+            fee:         alloy_primitives::aliases::U24::from(3000),
+            tickSpacing: alloy_primitives::aliases::I24::unchecked_from(60),
+            hooks:       Address::with_last_byte(0xaa)
+        };
+        let pool_id = PoolId::from(pool_key);
+        let registry = L2PoolRegistry::from_config(vec![PoolKeyWithFees {
+            pool_key,
+            fee_cfg: l2_fee_config(3000)
+        }]);
+        let (mut service, ..) =
+            test_service::<Optimism>(L2AddressBook::new(Address::ZERO), registry).await;
+
+        service.process_pool_update(PoolUpdate::NewPoolState {
+            pool_id,
+            state: pool_with_positions(0, &[(-600, 600, 1_000_000)], l2_fee_config(3000))
+        });
+
+        let subscribed = service.slot0_stream_ref().unwrap().subscribed_pools();
+        assert_eq!(subscribed, &HashSet::from([pool_id]));
+    }
+}
@@ -4,14 +4,18 @@ use alloy_network::Ethereum;
 use alloy_primitives::Address;
 use alloy_provider::Provider;
 use futures::Stream;
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc};
 use uni_v4_common::{PoolUpdate, V4Network};
 use uni_v4_structure::{PoolId, PoolKey};
 
 use super::{
     pool_data_loader::DataLoader,
-    pool_manager_service::{PoolManagerService, PoolManagerServiceError},
-    slot0::Slot0Stream
+    pool_manager_service::{
+        NewPoolCallback, PoolManagerService, PoolManagerServiceError, TickEviction
+    },
+    slot0::Slot0Stream,
+    tick_trend::PredictiveTickLoading,
+    update_channel::UpdateSender
 };
 use crate::{
     pool_data_loader::PoolDataLoader,
@@ -43,7 +47,12 @@ where
     ticks_per_batch:            Option<usize>,
     reorg_detection_blocks:     Option<u64>,
     reorg_lookback_block_chunk: Option<u64>,
-    update_channel:             Option<mpsc::Sender<PoolUpdate<T>>>
+    update_channel:             Option<UpdateSender<T>>,
+    update_channel_pools:       Option<HashSet<PoolId>>,
+    max_pending_updates:        Option<usize>,
+    predictive_tick_loading:    Option<PredictiveTickLoading>,
+    new_pool_callback:          Option<NewPoolCallback<T>>,
+    tick_eviction:              Option<TickEviction>
 }
 
 impl<P, T, Event, Slot0> PoolManagerServiceBuilder<P, T, Event, Slot0>
@@ -77,7 +86,12 @@ where
             ticks_per_batch: None,
             reorg_detection_blocks: None,
             reorg_lookback_block_chunk: None,
-            update_channel: None
+            update_channel: None,
+            update_channel_pools: None,
+            max_pending_updates: None,
+            predictive_tick_loading: None,
+            new_pool_callback: None,
+            tick_eviction: None
         }
     }
 }
@@ -107,7 +121,12 @@ where
             ticks_per_batch:            self.ticks_per_batch,
             reorg_detection_blocks:     self.reorg_detection_blocks,
             reorg_lookback_block_chunk: self.reorg_lookback_block_chunk,
-            update_channel:             self.update_channel
+            update_channel:             self.update_channel,
+            update_channel_pools:       self.update_channel_pools,
+            max_pending_updates:        self.max_pending_updates,
+            predictive_tick_loading:    self.predictive_tick_loading,
+            new_pool_callback:          self.new_pool_callback,
+            tick_eviction:              self.tick_eviction
         }
     }
 }
@@ -142,12 +161,41 @@ where
         self
     }
 
+    /// Decide per pool whether a pool detected after startup gets created.
+    /// The callback is given the pool's key and fee configuration and takes
+    /// precedence over [`Self::with_auto_pool_creation`]
+    pub fn on_new_pool_detected(
+        mut self,
+        callback: impl Fn(&PoolKey, &T::FeeConfig) -> bool + Send + Sync + 'static
+    ) -> Self {
+        self.new_pool_callback = Some(Arc::new(callback));
+        self
+    }
+
     /// Set the current block to load pools at
     pub fn with_current_block(mut self, block: u64) -> Self {
         self.current_block = Some(block);
         self
     }
 
+    /// Once a pool's tick has moved the same way for `trend_blocks` blocks,
+    /// scale the edge threshold and the number of ticks loaded on that side by
+    /// `band_multiplier`, so a trending price doesn't keep walking out of the
+    /// loaded range
+    pub fn with_predictive_tick_loading(mut self, trend_blocks: u32, band_multiplier: u16) -> Self {
+        self.predictive_tick_loading =
+            Some(PredictiveTickLoading { trend_blocks, band_multiplier });
+        self
+    }
+
+    /// Every `inactive_blocks`, drop the ticks of pools that haven't been
+    /// updated for that long, keeping `keep_ticks` tick spacings on each side
+    /// of the current tick
+    pub fn with_inactive_tick_eviction(mut self, inactive_blocks: u64, keep_ticks: u16) -> Self {
+        self.tick_eviction = Some(TickEviction { inactive_blocks, keep_ticks });
+        self
+    }
+
     /// Set the number of ticks to load per batch
     pub fn with_ticks_per_batch(mut self, ticks_per_batch: usize) -> Self {
         self.ticks_per_batch = Some(ticks_per_batch);
@@ -168,9 +216,48 @@ where
 
     /// Set the channel for sending pool updates
     /// When set, the service will send all updates via this channel instead of
-    /// applying them directly
+    /// applying them directly. If the channel fills up, updates are buffered
+    /// in order until the consumer catches up rather than being dropped
     pub fn with_update_channel(mut self, sender: mpsc::Sender<PoolUpdate<T>>) -> Self {
-        self.update_channel = Some(sender);
+        self.update_channel = Some(sender.into());
+        self.update_channel_pools = None;
+        self
+    }
+
+    /// Like [`Self::with_update_channel`], but only updates for `pools` are
+    /// sent, along with every `NewBlock` and `Reorg` marker. Pools are given by
+    /// their uniswap pool id
+    pub fn with_update_channel_filtered(
+        mut self,
+        sender: mpsc::Sender<PoolUpdate<T>>,
+        pools: HashSet<PoolId>
+    ) -> Self {
+        self.update_channel = Some(sender.into());
+        self.update_channel_pools = Some(pools);
+        self
+    }
+
+    /// Send pool updates to any number of subscribers instead of applying them
+    /// directly. Returns the builder along with a first receiver; call
+    /// [`broadcast::Receiver::resubscribe`] for more. Each subscriber gets
+    /// every update, but one that falls more than `capacity` updates behind
+    /// skips the oldest ones and receives
+    /// [`broadcast::error::RecvError::Lagged`]
+    pub fn with_broadcast_channel(
+        mut self,
+        capacity: usize
+    ) -> (Self, broadcast::Receiver<PoolUpdate<T>>) {
+        let (sender, receiver) = broadcast::channel(capacity);
+        self.update_channel = Some(sender.into());
+        self.update_channel_pools = None;
+        (self, receiver)
+    }
+
+    /// Cap the number of updates queued while new ticks are being loaded.
+    /// Past the cap, slot0 refreshes superseded by a newer swap or refresh for
+    /// the same pool are dropped
+    pub fn with_max_pending_updates(mut self, max_pending_updates: usize) -> Self {
+        self.max_pending_updates = Some(max_pending_updates);
         self
     }
 
@@ -186,7 +273,7 @@ where
         let event_stream = self.event_stream;
 
         // Create service using the consolidated new method
-        let service = PoolManagerService::new(
+        let mut service = PoolManagerService::new(
             self.provider.clone(),
             event_stream,
             self.address_book,
@@ -200,10 +287,22 @@ where
             self.slot0_stream,
             self.current_block,
             self.ticks_per_batch,
-            self.update_channel
+            self.update_channel,
+            self.update_channel_pools
         )
         .await?;
 
+        if let Some(max_pending_updates) = self.max_pending_updates {
+            service.set_max_pending_updates(max_pending_updates);
+        }
+        if let Some(config) = self.predictive_tick_loading {
+            service.factory.set_predictive_tick_loading(config);
+        }
+        if let Some(policy) = self.tick_eviction {
+            service.set_tick_eviction(policy);
+        }
+        service.new_pool_callback = self.new_pool_callback;
+
         Ok(service)
     }
 }
@@ -247,7 +346,12 @@ where
             ticks_per_batch:            builder.ticks_per_batch,
             reorg_detection_blocks:     builder.reorg_detection_blocks,
             reorg_lookback_block_chunk: builder.reorg_lookback_block_chunk,
-            update_channel:             builder.update_channel
+            update_channel:             builder.update_channel,
+            update_channel_pools:       builder.update_channel_pools,
+            max_pending_updates:        builder.max_pending_updates,
+            predictive_tick_loading:    builder.predictive_tick_loading,
+            new_pool_callback:          builder.new_pool_callback,
+            tick_eviction:              builder.tick_eviction
         }
     }
 }
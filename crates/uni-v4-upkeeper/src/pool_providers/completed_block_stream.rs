@@ -2,11 +2,60 @@ use std::{pin::Pin, sync::Arc, task::Poll};
 
 use alloy_primitives::B256;
 use alloy_provider::Provider;
-use alloy_rpc_types::Block;
-use futures::{FutureExt, Stream, StreamExt, future::BoxFuture, stream::FuturesOrdered};
+use alloy_rpc_types::{Block, Header};
+use futures::{
+    FutureExt, Stream, StreamExt,
+    future::BoxFuture,
+    stream::{BoxStream, FuturesOrdered}
+};
+
+use crate::pool_providers::update_provider::PoolUpdateError;
 
 pub type BlockQueryResponse = Block;
 
+/// Subscribes to new blocks on a pubsub provider, e.g. one connected with
+/// `WsConnect`, and fetches each one in full. The L1 updates need the
+/// transactions to pick up `batchUpdatePools` calls, which the subscription's
+/// headers don't carry. The result can be passed straight to `StateStream`.
+pub async fn block_stream_from_provider<P: Provider + 'static>(
+    provider: Arc<P>
+) -> Result<BoxStream<'static, Block>, PoolUpdateError> {
+    let headers = provider
+        .subscribe_blocks()
+        .await
+        .map_err(|e| PoolUpdateError::Provider(e.to_string()))?
+        .into_stream();
+
+    Ok(full_blocks(provider, headers))
+}
+
+/// Fetches the full block for each header in order, skipping blocks the
+/// provider fails to return
+fn full_blocks<P: Provider + 'static>(
+    provider: Arc<P>,
+    headers: impl Stream<Item = Header> + Send + 'static
+) -> BoxStream<'static, Block> {
+    headers
+        .then(move |header| {
+            let provider = provider.clone();
+            async move {
+                match provider.get_block_by_hash(header.hash).full().await {
+                    Ok(Some(block)) => Some(block),
+                    Ok(None) => {
+                        tracing::warn!(number = header.number, "subscribed block not found");
+                        None
+                    }
+                    Err(e) => {
+                        tracing::warn!(number = header.number, "failed to fetch block: {e}");
+                        None
+                    }
+                }
+            }
+        })
+        .filter_map(futures::future::ready)
+        .boxed()
+}
+
 pub struct CompletedBlockStream<P: Provider> {
     prev_block_hash:   B256,
     prev_block_number: u64,
@@ -123,3 +172,45 @@ async fn process_no_block<P: Provider>(client: Arc<P>, block_number: u64) -> Blo
         .unwrap()
         .unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use alloy_provider::{ProviderBuilder, mock::Asserter};
+    use alloy_rpc_types::BlockTransactions;
+
+    use super::*;
+
+    fn block(number: u64) -> Block {
+        let mut block = Block::<alloy_rpc_types::Transaction>::default();
+        block.header.hash = B256::with_last_byte(number as u8);
+        block.header.inner.number = number;
+        block.transactions = BlockTransactions::Full(vec![]);
+        block
+    }
+
+    #[tokio::test]
+    async fn subscribed_headers_are_fetched_in_full() {
+        let asserter = Asserter::new();
+        let provider = Arc::new(
+            ProviderBuilder::new()
+                .disable_recommended_fillers()
+                .connect_mocked_client(asserter.clone())
+        );
+
+        // the fetch for block 2 fails and is skipped
+        asserter.push_success(&block(1));
+        asserter.push_failure_msg("unavailable");
+        asserter.push_success(&block(3));
+        let headers = futures::stream::iter([1, 2, 3].map(|number| block(number).header));
+
+        let blocks = full_blocks(provider, headers).collect::<Vec<_>>().await;
+        assert_eq!(
+            blocks
+                .iter()
+                .map(|block| block.number())
+                .collect::<Vec<_>>(),
+            vec![1, 3]
+        );
+        assert!(blocks.iter().all(|block| block.transactions.is_full()));
+    }
+}
@@ -4,6 +4,8 @@ use futures::Stream;
 use uni_v4_common::{PoolUpdate, V4Network};
 
 pub mod completed_block_stream;
+pub mod polling_block_stream;
+pub mod update_engine;
 pub mod update_provider;
 use uni_v4_structure::{PoolId, PoolKeyWithFees};
 
@@ -18,14 +20,19 @@ pub trait PoolEventStream<T: V4Network>:
 }
 
 pub trait ProviderChainUpdate<T: V4Network> {
+    /// Chain specific updates between the two blocks. `initialize_logs` are
+    /// the pool manager `Initialize` logs already fetched for the range
     fn fetch_chain_data(
         &mut self,
         from_block: u64,
-        to_block: u64
+        to_block: u64,
+        initialize_logs: &[alloy_rpc_types::Log]
     ) -> impl Future<Output = Result<Vec<PoolUpdate<T>>, PoolUpdateError>> + Send;
 }
 
 pub trait ProviderChainInitialization<T: V4Network>: Provider<T> {
+    /// Pools configured between the two blocks. The returned fee configs are
+    /// the ones the initially loaded pools are created with
     fn fetch_pools(
         &self,
         address_book: T::AddressBook,
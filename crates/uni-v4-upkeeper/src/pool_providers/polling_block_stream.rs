@@ -0,0 +1,110 @@
+use std::{
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration
+};
+
+use alloy_provider::Provider;
+use alloy_rpc_types::Block;
+use futures::{Stream, StreamExt, stream::BoxStream};
+
+/// Block source for providers without a subscription, e.g. plain HTTP. Polls
+/// the chain head every `interval` and yields each new block in full, in
+/// order, so blocks produced between two polls are all still yielded. Can be
+/// used as the block stream of `StateStream`.
+pub struct PollingBlockStream {
+    blocks: BoxStream<'static, Block>
+}
+
+impl PollingBlockStream {
+    /// Yields blocks after `last_block`
+    pub fn new<P: Provider + 'static>(
+        provider: Arc<P>,
+        last_block: u64,
+        interval: Duration
+    ) -> Self {
+        let blocks = futures::stream::unfold(
+            (provider, last_block + 1, last_block),
+            move |(provider, next_block, mut head)| async move {
+                loop {
+                    if next_block > head {
+                        match provider.get_block_number().await {
+                            Ok(number) if number >= next_block => {
+                                head = number;
+                                continue;
+                            }
+                            Ok(_) => {}
+                            Err(e) => tracing::warn!("failed to poll the block number: {e}")
+                        }
+                    } else {
+                        match provider.get_block_by_number(next_block.into()).full().await {
+                            Ok(Some(block)) => {
+                                return Some((block, (provider, next_block + 1, head)));
+                            }
+                            Ok(None) => tracing::warn!(next_block, "polled block not found"),
+                            Err(e) => tracing::warn!(next_block, "failed to fetch block: {e}")
+                        }
+                    }
+
+                    tokio::time::sleep(interval).await;
+                }
+            }
+        );
+
+        Self { blocks: blocks.boxed() }
+    }
+}
+
+impl Stream for PollingBlockStream {
+    type Item = Block;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.blocks.poll_next_unpin(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::U64;
+    use alloy_provider::{ProviderBuilder, mock::Asserter};
+    use alloy_rpc_types::BlockTransactions;
+
+    use super::*;
+
+    fn block(number: u64) -> Block {
+        let mut block = Block::<alloy_rpc_types::Transaction>::default();
+        block.header.inner.number = number;
+        block.transactions = BlockTransactions::Full(vec![]);
+        block
+    }
+
+    #[tokio::test]
+    async fn every_block_is_yielded_once() {
+        let asserter = Asserter::new();
+        let provider = Arc::new(
+            ProviderBuilder::new()
+                .disable_recommended_fillers()
+                .connect_mocked_client(asserter.clone())
+        );
+
+        // no new block on the first poll, then two at once, then a failed poll
+        // and one more
+        asserter.push_success(&U64::from(10));
+        asserter.push_success(&U64::from(12));
+        asserter.push_success(&block(11));
+        asserter.push_success(&block(12));
+        asserter.push_failure_msg("unavailable");
+        asserter.push_success(&U64::from(13));
+        asserter.push_success(&block(13));
+
+        let stream = PollingBlockStream::new(provider, 10, Duration::from_millis(1));
+        let numbers = stream
+            .take(3)
+            .map(|block| block.number())
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(numbers, vec![11, 12, 13]);
+        assert!(asserter.read_q().is_empty());
+    }
+}
@@ -0,0 +1,749 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    marker::PhantomData
+};
+
+use alloy_sol_types::SolEvent;
+use uni_v4_common::{ModifyLiquidityEventData, PoolUpdate, StreamMode, SwapEventData, V4Network};
+use uni_v4_structure::{PoolId, UpdatePool, pool_updates::Slot0Data};
+
+use crate::pool_data_loader::IUniswapV4Pool;
+
+/// Stored event for reorg handling - only liquidity events need to be stored
+#[derive(Debug, Clone)]
+struct StoredEvent {
+    block:           u64,
+    tx_index:        u64,
+    log_index:       u64,
+    pool_id:         PoolId,
+    liquidity_event: ModifyLiquidityEventData
+}
+
+/// What has to be fetched after a new block is received
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockAction {
+    /// Fetch the events of `from_block..=to_block` and pass them to
+    /// [`PoolUpdateEngine::apply_new_block`]. The range spans more than one
+    /// block when blocks were missed.
+    Advance { from_block: u64, to_block: u64 },
+    /// The current block was received again. Re-fetch the events of
+    /// `from_block..=to_block` and pass them to
+    /// [`PoolUpdateEngine::apply_reorg`].
+    Reorg { from_block: u64, to_block: u64 },
+    /// The block is behind the current block, nothing to do
+    Ignore
+}
+
+/// The updates produced by a reorg, still missing the re-queried slot0 of
+/// the pools it touched
+pub struct ReorgUpdates<T: V4Network> {
+    pub from_block:     u64,
+    pub to_block:       u64,
+    /// Pools whose slot0 has to be re-queried
+    pub affected_pools: HashSet<PoolId>,
+    inverse_events:     usize,
+    fresh_events:       usize,
+    updates:            Vec<PoolUpdate<T>>
+}
+
+impl<T: V4Network> ReorgUpdates<T> {
+    /// Appends the re-queried slot0 data and closes the reorg. Logs a `reorg`
+    /// span carrying how many pools, unwound events, fresh events and slot0
+    /// refreshes it took.
+    pub fn finish(
+        mut self,
+        slot0s: impl IntoIterator<Item = (PoolId, Slot0Data)>
+    ) -> Vec<PoolUpdate<T>> {
+        let slot0_updates = slot0s
+            .into_iter()
+            .map(|(pool_id, data)| PoolUpdate::UpdatedSlot0 { pool_id, data })
+            .collect::<Vec<_>>();
+
+        let span = tracing::warn_span!(
+            "reorg",
+            from_block = self.from_block,
+            to_block = self.to_block,
+            affected_pools = self.affected_pools.len(),
+            inverse_events = self.inverse_events,
+            fresh_events = self.fresh_events,
+            slot0_refreshes = slot0_updates.len()
+        );
+        let _entered = span.enter();
+        tracing::warn!("unwound reorg from block {} to {}", self.from_block, self.to_block);
+
+        self.updates.extend(slot0_updates);
+        self.updates
+            .push(PoolUpdate::Reorg { from_block: self.from_block, to_block: self.to_block });
+
+        self.updates
+    }
+}
+
+/// The state machine behind [`PoolUpdateProvider`]: decides what to fetch for
+/// each block and turns fetched logs into [`PoolUpdate`]s, keeping the
+/// history needed to unwind reorgs. It does no I/O itself.
+///
+/// [`PoolUpdateProvider`]: super::update_provider::PoolUpdateProvider
+#[derive(Debug)]
+pub struct PoolUpdateEngine<T: V4Network> {
+    tracked_pools:              HashSet<PoolId>,
+    event_history:              VecDeque<StoredEvent>,
+    current_block:              u64,
+    reorg_detection_blocks:     u64,
+    reorg_lookback_block_chunk: u64,
+    stream_mode:                StreamMode,
+    _network:                   PhantomData<T>
+}
+
+impl<T: V4Network> PoolUpdateEngine<T> {
+    pub fn new(
+        current_block: u64,
+        reorg_detection_blocks: u64,
+        reorg_lookback_block_chunk: u64
+    ) -> Self {
+        Self {
+            tracked_pools: HashSet::new(),
+            event_history: VecDeque::with_capacity(reorg_detection_blocks as usize),
+            current_block,
+            reorg_detection_blocks,
+            reorg_lookback_block_chunk,
+            stream_mode: StreamMode::default(),
+            _network: PhantomData
+        }
+    }
+
+    /// Set the stream mode for this engine
+    pub fn with_stream_mode(mut self, mode: StreamMode) -> Self {
+        self.stream_mode = mode;
+        self
+    }
+
+    pub fn set_stream_mode(&mut self, mode: StreamMode) {
+        self.stream_mode = mode;
+    }
+
+    pub fn current_block(&self) -> u64 {
+        self.current_block
+    }
+
+    pub fn reorg_lookback_block_chunk(&self) -> u64 {
+        self.reorg_lookback_block_chunk
+    }
+
+    /// Add a pool to track
+    pub fn add_pool(&mut self, pool_id: PoolId) {
+        self.tracked_pools.insert(pool_id);
+    }
+
+    /// Remove a pool from tracking
+    pub fn remove_pool(&mut self, pool_id: PoolId) {
+        self.tracked_pools.remove(&pool_id);
+    }
+
+    /// Get all tracked pool IDs
+    pub fn tracked_pools(&self) -> Vec<PoolId> {
+        self.tracked_pools.iter().copied().collect()
+    }
+
+    pub fn is_tracking(&self, pool_id: &PoolId) -> bool {
+        self.tracked_pools.contains(pool_id)
+    }
+
+    /// Decides what to do with a newly received block
+    pub fn on_block(&self, block_number: u64) -> BlockAction {
+        if block_number == self.current_block {
+            let (from_block, to_block) = self.reorg_range();
+            BlockAction::Reorg { from_block, to_block }
+        } else if block_number > self.current_block {
+            BlockAction::Advance { from_block: self.current_block + 1, to_block: block_number }
+        } else {
+            BlockAction::Ignore
+        }
+    }
+
+    /// The blocks that are unwound and re-fetched on a reorg
+    pub fn reorg_range(&self) -> (u64, u64) {
+        (
+            self.current_block
+                .saturating_sub(self.reorg_detection_blocks.saturating_sub(1)),
+            self.current_block
+        )
+    }
+
+    /// Decodes the swap and liquidity logs of tracked pools. Logs without a
+    /// block number are attributed to `default_block`. Liquidity events are
+    /// added to the reorg history when `store_in_history` is set.
+    pub fn process_logs(
+        &mut self,
+        swap_logs: &[alloy_rpc_types::Log],
+        modify_logs: &[alloy_rpc_types::Log],
+        default_block: u64,
+        store_in_history: bool
+    ) -> Vec<PoolUpdate<T>> {
+        let mut updates = Vec::new();
+
+        for log in swap_logs {
+            let block_number = log.block_number.unwrap_or(default_block);
+            if let Some(update) = self.process_swap_event(log, block_number) {
+                updates.push(update);
+            }
+        }
+
+        for log in modify_logs {
+            let block_number = log.block_number.unwrap_or(default_block);
+            if let Some(update) = self.process_liquidity_event(log, block_number, store_in_history)
+            {
+                updates.push(update);
+            }
+        }
+
+        updates
+    }
+
+    /// Turns the `Initialize` logs of tracked pools into their starting slot0,
+    /// so a pool initialized after it was registered doesn't need a slot0
+    /// query. Pools that already have events in `updates` are skipped: slot0
+    /// refreshes sort after a block's events and would roll those back.
+    pub fn process_initialize_logs(
+        &self,
+        initialize_logs: &[alloy_rpc_types::Log],
+        updates: &[PoolUpdate<T>]
+    ) -> Vec<PoolUpdate<T>> {
+        let active_pools: HashSet<_> = updates.iter().filter_map(PoolUpdate::pool_id).collect();
+
+        initialize_logs
+            .iter()
+            .filter_map(|log| IUniswapV4Pool::Initialize::decode_log(&log.inner).ok())
+            .filter(|event| {
+                self.tracked_pools.contains(&event.id) && !active_pools.contains(&event.id)
+            })
+            .map(|event| PoolUpdate::UpdatedSlot0 {
+                pool_id: event.id,
+                data:    Slot0Data {
+                    sqrt_price_x96: event.sqrtPriceX96,
+                    tick:           event.tick.as_i32(),
+                    liquidity:      0
+                }
+            })
+            .collect()
+    }
+
+    /// Process a swap event log
+    fn process_swap_event(
+        &self,
+        log: &alloy_rpc_types::Log,
+        block_number: u64
+    ) -> Option<PoolUpdate<T>> {
+        let swap_event = IUniswapV4Pool::Swap::decode_log(&log.inner).ok()?;
+        // Check if we're tracking this Uniswap pool ID
+        if !self.tracked_pools.contains(&swap_event.id) {
+            return None;
+        }
+
+        let event_data = SwapEventData {
+            sender:         swap_event.sender,
+            amount0:        swap_event.amount0,
+            amount1:        swap_event.amount1,
+            sqrt_price_x96: swap_event.sqrtPriceX96,
+            liquidity:      swap_event.liquidity,
+            tick:           swap_event.tick.as_i32(),
+            fee:            swap_event.fee.to()
+        };
+
+        Some(PoolUpdate::SwapEvent {
+            pool_id:   swap_event.id, // Use Uniswap pool ID
+            block:     block_number,
+            tx_index:  log.transaction_index.unwrap(),
+            log_index: log.log_index.unwrap(),
+            event:     event_data
+        })
+    }
+
+    /// Process a liquidity event log
+    fn process_liquidity_event(
+        &mut self,
+        log: &alloy_rpc_types::Log,
+        block_number: u64,
+        store_in_history: bool
+    ) -> Option<PoolUpdate<T>> {
+        let modify_event = IUniswapV4Pool::ModifyLiquidity::decode_log(&log.inner).ok()?;
+        // Check if we're tracking this Uniswap pool ID
+        if !self.tracked_pools.contains(&modify_event.id) {
+            return None;
+        }
+
+        let event_data = ModifyLiquidityEventData {
+            sender:          modify_event.sender,
+            tick_lower:      modify_event.tickLower.as_i32(),
+            tick_upper:      modify_event.tickUpper.as_i32(),
+            liquidity_delta: modify_event.liquidityDelta,
+            salt:            modify_event.salt.0
+        };
+
+        // Store in history only if requested
+        if store_in_history {
+            self.add_to_history(StoredEvent {
+                block:           block_number,
+                tx_index:        log.transaction_index.unwrap(),
+                log_index:       log.log_index.unwrap(),
+                pool_id:         modify_event.id, // Use Uniswap pool ID
+                liquidity_event: event_data.clone()
+            });
+        }
+
+        Some(PoolUpdate::LiquidityEvent {
+            pool_id:   modify_event.id, // Use Uniswap pool ID
+            block:     block_number,
+            tx_index:  log.transaction_index.unwrap(),
+            log_index: log.log_index.unwrap(),
+            event:     event_data
+        })
+    }
+
+    /// Moves the engine to `block_number`, returning a `NewBlock` marker for
+    /// every block since the current one, each followed by that block's
+    /// updates that pass the stream mode. Updates that don't come from a log
+    /// go with the last block.
+    pub fn apply_new_block(
+        &mut self,
+        block_number: u64,
+        block_updates: Vec<PoolUpdate<T>>
+    ) -> Vec<PoolUpdate<T>> {
+        let mut block_updates = self.filter_stream_mode(block_updates);
+        // stable, so each block's updates keep their order
+        block_updates.sort_by_key(|update| update.block().unwrap_or(u64::MAX));
+        let mut block_updates = block_updates.into_iter().peekable();
+
+        let mut updates = Vec::new();
+        for block in (self.current_block + 1).min(block_number)..=block_number {
+            updates.push(PoolUpdate::NewBlock(block));
+            while let Some(update) = block_updates.next_if(|update| {
+                block == block_number || update.block().is_some_and(|number| number <= block)
+            }) {
+                updates.push(update);
+            }
+        }
+
+        self.current_block = block_number;
+        self.prune_history();
+
+        updates
+    }
+
+    /// Unwinds the history of the reorged range and replaces it with the
+    /// re-fetched `fresh_events`. The returned updates start with the `Reorg`
+    /// marker, followed by the inverse of the unwound liquidity events and
+    /// then the fresh events.
+    pub fn apply_reorg(&mut self, fresh_events: Vec<PoolUpdate<T>>) -> ReorgUpdates<T> {
+        let (from_block, to_block) = self.reorg_range();
+        let mut updates = vec![PoolUpdate::Reorg { from_block, to_block }];
+
+        let inverse_events = self.get_inverse_liquidity_events(from_block, to_block);
+        let mut affected_pools = Self::get_affected_pools(&inverse_events);
+        affected_pools.extend(Self::get_affected_pools(&fresh_events));
+
+        // In InitializationOnly mode, we don't need inverse liquidity events as we're
+        // not tracking swap/liquidity changes
+        let inverse_events =
+            if self.stream_mode == StreamMode::Full { inverse_events } else { Vec::new() };
+        let inverse_count = inverse_events.len();
+        updates.extend(inverse_events);
+
+        self.event_history.retain(|event| event.block < from_block);
+        for update in &fresh_events {
+            if let Some(stored_event) = Self::update_to_stored_event(update) {
+                self.add_to_history(stored_event);
+            }
+        }
+        let fresh_events = self.filter_stream_mode(fresh_events);
+        let fresh_count = fresh_events.len();
+        updates.extend(fresh_events);
+
+        ReorgUpdates {
+            from_block,
+            to_block,
+            affected_pools,
+            inverse_events: inverse_count,
+            fresh_events: fresh_count,
+            updates
+        }
+    }
+
+    fn filter_stream_mode(&self, updates: Vec<PoolUpdate<T>>) -> Vec<PoolUpdate<T>> {
+        match self.stream_mode {
+            StreamMode::Full => updates,
+            StreamMode::InitializationOnly => updates
+                .into_iter()
+                .filter(|update| match update {
+                    PoolUpdate::FeeUpdate { .. }
+                    | PoolUpdate::UpdatedSlot0 { .. }
+                    | PoolUpdate::NewPoolState { .. } => true,
+                    PoolUpdate::ChainSpecific { pool_id: _, update } => {
+                        update.is_initialization_event()
+                    }
+                    _ => false
+                })
+                .collect()
+        }
+    }
+
+    /// Add event to history, maintaining the reorg detection window
+    fn add_to_history(&mut self, event: StoredEvent) {
+        self.event_history.push_back(event);
+        self.prune_history();
+    }
+
+    /// Remove all events from blocks older than the reorg detection window
+    fn prune_history(&mut self) {
+        let cutoff_block = self
+            .current_block
+            .saturating_sub(self.reorg_detection_blocks.saturating_sub(1));
+
+        self.event_history.retain(|e| e.block >= cutoff_block);
+    }
+
+    /// Get inverse liquidity events for reorg handling
+    fn get_inverse_liquidity_events(&self, from_block: u64, to_block: u64) -> Vec<PoolUpdate<T>> {
+        // Iterate through history in reverse order to process most recent first
+        self.event_history
+            .iter()
+            .rev()
+            .filter(|event| event.block >= from_block && event.block <= to_block)
+            .map(|event| PoolUpdate::LiquidityEvent {
+                pool_id:   event.pool_id,
+                block:     event.block,
+                tx_index:  event.tx_index,
+                log_index: event.log_index,
+                event:     ModifyLiquidityEventData {
+                    liquidity_delta: -event.liquidity_event.liquidity_delta,
+                    ..event.liquidity_event.clone()
+                }
+            })
+            .collect()
+    }
+
+    /// Get pools affected by events
+    fn get_affected_pools(updates: &[PoolUpdate<T>]) -> HashSet<PoolId> {
+        updates
+            .iter()
+            .filter_map(|update| match update {
+                PoolUpdate::SwapEvent { pool_id, .. }
+                | PoolUpdate::LiquidityEvent { pool_id, .. }
+                | PoolUpdate::UpdatedSlot0 { pool_id, .. }
+                | PoolUpdate::FeeUpdate { pool_id, .. } => Some(*pool_id),
+                PoolUpdate::ChainSpecific { pool_id, update } => {
+                    update.is_pool_affected().then_some(*pool_id)
+                }
+                _ => None
+            })
+            .collect()
+    }
+
+    /// Convert PoolUpdate to StoredEvent for history
+    /// Only liquidity events are stored since we re-query slot0 after reorgs
+    fn update_to_stored_event(update: &PoolUpdate<T>) -> Option<StoredEvent> {
+        match update {
+            PoolUpdate::LiquidityEvent { pool_id, block, tx_index, log_index, event } => {
+                Some(StoredEvent {
+                    block:           *block,
+                    tx_index:        *tx_index,
+                    log_index:       *log_index,
+                    pool_id:         *pool_id,
+                    liquidity_event: event.clone()
+                })
+            }
+            _ => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use alloy_network::Ethereum;
+    use alloy_primitives::{Address, B256, I256, U160, aliases::I24};
+    use alloy_sol_types::SolEvent;
+    use uni_v4_common::PoolUpdate;
+    use uni_v4_structure::pool_updates::Slot0Data;
+
+    use super::{BlockAction, PoolUpdateEngine};
+    use crate::pool_data_loader::IUniswapV4Pool;
+
+    const POOL: B256 = B256::repeat_byte(1);
+
+    fn rpc_log(
+        data: alloy_primitives::LogData,
+        block: u64,
+        log_index: u64
+    ) -> alloy_rpc_types::Log {
+        alloy_rpc_types::Log {
+            inner: alloy_primitives::Log { address: Address::ZERO, data },
+            block_number: Some(block),
+            transaction_index: Some(0),
+            log_index: Some(log_index),
+            ..Default::default()
+        }
+    }
+
+    fn modify_log(pool_id: B256, block: u64, log_index: u64, delta: i64) -> alloy_rpc_types::Log {
+        let event = IUniswapV4Pool::ModifyLiquidity {
+            id:             pool_id,
+            sender:         Address::ZERO,
+            tickLower:      I24::unchecked_from(-60),
+            tickUpper:      I24::unchecked_from(60),
+            liquidityDelta: I256::try_from(delta).unwrap(),
+            salt:           B256::ZERO
+        };
+        rpc_log(event.encode_log_data(), block, log_index)
+    }
+
+    fn swap_log(pool_id: B256, block: u64, log_index: u64) -> alloy_rpc_types::Log {
+        let event = IUniswapV4Pool::Swap {
+            id:           pool_id,
+            sender:       Address::ZERO,
+            amount0:      -1,
+            amount1:      1,
+            sqrtPriceX96: U160::from(1u128 << 96),
+            liquidity:    1,
+            tick:         I24::ZERO,
+            fee:          Default::default()
+        };
+        rpc_log(event.encode_log_data(), block, log_index)
+    }
+
+    fn initialize_log(pool_id: B256, block: u64, tick: i32) -> alloy_rpc_types::Log {
+        let event = IUniswapV4Pool::Initialize {
+            id:           pool_id,
+            currency0:    Address::with_last_byte(1),
+            currency1:    Address::with_last_byte(2),
+            fee:          Default::default(),
+            tickSpacing:  I24::unchecked_from(60),
+            hooks:        Address::ZERO,
+            sqrtPriceX96: U160::from(1u128 << 96),
+            tick:         I24::unchecked_from(tick)
+        };
+        rpc_log(event.encode_log_data(), block, 0)
+    }
+
+    fn engine() -> PoolUpdateEngine<Ethereum> {
+        let mut engine = PoolUpdateEngine::new(100, 10, 100);
+        engine.add_pool(POOL);
+        engine
+    }
+
+    fn liquidity_delta(update: &PoolUpdate<Ethereum>) -> Option<I256> {
+        match update {
+            PoolUpdate::LiquidityEvent { event, .. } => Some(event.liquidity_delta),
+            _ => None
+        }
+    }
+
+    #[test]
+    fn normal_block() {
+        let mut engine = engine();
+        assert_eq!(engine.on_block(101), BlockAction::Advance { from_block: 101, to_block: 101 });
+
+        let logs = engine.process_logs(
+            &[swap_log(POOL, 101, 0), swap_log(B256::repeat_byte(2), 101, 1)],
+            &[modify_log(POOL, 101, 2, 1000)],
+            101,
+            true
+        );
+        let updates = engine.apply_new_block(101, logs);
+
+        assert!(matches!(updates[0], PoolUpdate::NewBlock(101)));
+        assert!(matches!(updates[1], PoolUpdate::SwapEvent { pool_id: POOL, .. }));
+        assert_eq!(liquidity_delta(&updates[2]), Some(I256::try_from(1000).unwrap()));
+        assert_eq!(updates.len(), 3);
+        assert_eq!(engine.current_block(), 101);
+    }
+
+    #[test]
+    fn initialize_logs_become_slot0_updates() {
+        let mut engine = engine();
+        let other = B256::repeat_byte(2);
+
+        let updates = engine.process_initialize_logs(
+            &[initialize_log(POOL, 101, 0), initialize_log(other, 101, 60)],
+            &[]
+        );
+
+        assert_eq!(updates.len(), 1);
+        let PoolUpdate::UpdatedSlot0 { pool_id, data } = &updates[0] else {
+            panic!("expected a slot0 update, got {updates:?}");
+        };
+        assert_eq!(*pool_id, POOL);
+        assert_eq!(data.sqrt_price_x96, U160::from(1u128 << 96));
+        assert_eq!(data.tick, 0);
+        assert_eq!(data.liquidity, 0);
+
+        // a swap in the same range already moved the pool past its initial price
+        let swaps = engine.process_logs(&[swap_log(POOL, 101, 1)], &[], 101, true);
+        assert!(
+            engine
+                .process_initialize_logs(&[initialize_log(POOL, 101, 0)], &swaps)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn reorg_unwinds_and_replaces_history() {
+        let mut engine = engine();
+        let logs = engine.process_logs(&[], &[modify_log(POOL, 101, 0, 1000)], 101, true);
+        engine.apply_new_block(101, logs);
+
+        assert_eq!(engine.on_block(101), BlockAction::Reorg { from_block: 92, to_block: 101 });
+
+        let fresh = engine.process_logs(&[], &[modify_log(POOL, 101, 0, 500)], 101, false);
+        let reorg = engine.apply_reorg(fresh);
+        assert!(reorg.affected_pools.contains(&POOL));
+
+        let updates = reorg.finish([]);
+        assert!(matches!(updates[0], PoolUpdate::Reorg { from_block: 92, to_block: 101 }));
+        assert_eq!(liquidity_delta(&updates[1]), Some(I256::try_from(-1000).unwrap()));
+        assert_eq!(liquidity_delta(&updates[2]), Some(I256::try_from(500).unwrap()));
+        assert!(matches!(updates[3], PoolUpdate::Reorg { from_block: 92, to_block: 101 }));
+
+        // the fresh events replaced the reorged ones in the history
+        let updates = engine.apply_reorg(vec![]).finish([]);
+        assert_eq!(liquidity_delta(&updates[1]), Some(I256::try_from(-500).unwrap()));
+        assert_eq!(updates.len(), 3);
+    }
+
+    /// Collects the integer fields of every `reorg` span
+    #[derive(Default)]
+    struct ReorgSpans(Arc<Mutex<Vec<(&'static str, u64)>>>);
+
+    impl tracing::field::Visit for ReorgSpans {
+        fn record_u64(&mut self, field: &tracing::field::Field, value: u64) {
+            self.0.lock().unwrap().push((field.name(), value));
+        }
+
+        fn record_debug(&mut self, _: &tracing::field::Field, _: &dyn std::fmt::Debug) {}
+    }
+
+    impl tracing::Subscriber for ReorgSpans {
+        fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            if span.metadata().name() == "reorg" {
+                span.record(&mut ReorgSpans(self.0.clone()));
+            }
+            tracing::span::Id::from_u64(1)
+        }
+
+        fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+        fn event(&self, _: &tracing::Event<'_>) {}
+
+        fn enter(&self, _: &tracing::span::Id) {}
+
+        fn exit(&self, _: &tracing::span::Id) {}
+    }
+
+    #[test]
+    fn reorg_counts_match_its_updates() {
+        let mut engine = engine();
+        let logs = engine.process_logs(
+            &[],
+            &[modify_log(POOL, 100, 0, 1000), modify_log(POOL, 101, 0, 1000)],
+            100,
+            true
+        );
+        engine.apply_new_block(101, logs);
+        engine.on_block(101);
+
+        let fresh = engine.process_logs(&[swap_log(POOL, 101, 0)], &[], 101, false);
+        let reorg = engine.apply_reorg(fresh);
+        assert_eq!((reorg.inverse_events, reorg.fresh_events), (2, 1));
+
+        let slot0 = Slot0Data {
+            sqrt_price_x96: U160::from(1u128 << 96),
+            tick:           0,
+            liquidity:      1
+        };
+        let fields = Arc::new(Mutex::new(Vec::new()));
+        let updates = tracing::subscriber::with_default(ReorgSpans(fields.clone()), || {
+            reorg.finish([(POOL, slot0)])
+        });
+
+        assert_eq!(
+            *fields.lock().unwrap(),
+            vec![
+                ("from_block", 92),
+                ("to_block", 101),
+                ("affected_pools", 1),
+                ("inverse_events", 2),
+                ("fresh_events", 1),
+                ("slot0_refreshes", 1)
+            ]
+        );
+
+        // marker, the two unwound modifies, the fresh swap, the slot0 refresh
+        // and the closing marker
+        assert_eq!(updates.len(), 6);
+        let inverse_deltas = updates[1..3]
+            .iter()
+            .map(liquidity_delta)
+            .collect::<Vec<_>>();
+        assert_eq!(inverse_deltas, vec![Some(I256::try_from(-1000).unwrap()); 2]);
+        assert!(matches!(updates[3], PoolUpdate::SwapEvent { block: 101, .. }));
+        assert!(matches!(updates[4], PoolUpdate::UpdatedSlot0 { .. }));
+    }
+
+    #[test]
+    fn gap_fetches_missed_blocks() {
+        let mut engine = engine();
+        assert_eq!(engine.on_block(105), BlockAction::Advance { from_block: 101, to_block: 105 });
+        assert_eq!(engine.on_block(99), BlockAction::Ignore);
+
+        let logs = engine.process_logs(
+            &[],
+            &[modify_log(POOL, 102, 0, 1000), modify_log(POOL, 104, 0, 1000)],
+            101,
+            true
+        );
+        let updates = engine.apply_new_block(105, logs);
+
+        // every missed block gets its own marker, ahead of its own events
+        let blocks = updates
+            .iter()
+            .map(|update| match update {
+                PoolUpdate::NewBlock(block) => (*block, true),
+                update => (update.block().unwrap(), false)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            blocks,
+            vec![
+                (101, true),
+                (102, true),
+                (102, false),
+                (103, true),
+                (104, true),
+                (104, false),
+                (105, true)
+            ]
+        );
+        assert_eq!(engine.current_block(), 105);
+        assert_eq!(engine.on_block(106), BlockAction::Advance { from_block: 106, to_block: 106 });
+    }
+
+    #[test]
+    fn zero_reorg_window_unwinds_the_current_block() {
+        let mut engine = PoolUpdateEngine::<Ethereum>::new(100, 0, 100);
+        engine.add_pool(POOL);
+        let logs = engine.process_logs(&[], &[modify_log(POOL, 101, 0, 1000)], 101, true);
+        engine.apply_new_block(101, logs);
+
+        assert_eq!(engine.on_block(101), BlockAction::Reorg { from_block: 101, to_block: 101 });
+        let updates = engine.apply_reorg(vec![]).finish([]);
+        assert_eq!(updates.len(), 3);
+        assert_eq!(liquidity_delta(&updates[1]), Some(I256::try_from(-1000).unwrap()));
+    }
+}
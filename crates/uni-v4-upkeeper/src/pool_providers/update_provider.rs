@@ -1,5 +1,5 @@
 use std::{
-    collections::{HashMap, HashSet, VecDeque},
+    collections::HashMap,
     future::Future,
     pin::Pin,
     sync::Arc,
@@ -8,26 +8,31 @@ use std::{
 
 use alloy_consensus::{BlockHeader, Transaction};
 use alloy_eips::BlockId;
-use alloy_network::{BlockResponse, Ethereum, Network};
-use alloy_primitives::{Address, U160, aliases::I24};
+use alloy_network::{BlockResponse, Ethereum, Network, TransactionResponse};
+use alloy_primitives::{Address, Bytes, U160};
 use alloy_provider::Provider;
-use alloy_rpc_types::Filter;
+use alloy_rpc_types::{BlockTransactions, Filter};
 use alloy_sol_types::{SolCall, SolEvent};
 use futures::{FutureExt, StreamExt, stream::Stream};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 // pub use types::*;
-use uni_v4_common::PoolUpdate;
-use uni_v4_common::{ModifyLiquidityEventData, StreamMode, SwapEventData, V4Network};
+use uni_v4_common::{PoolUpdate, StreamMode, V4Network};
 use uni_v4_structure::{
-    L1FeeConfiguration, PoolId, PoolKey, PoolKeyWithFees, UpdatePool,
+    L1FeeConfiguration, PoolId, PoolKey, PoolKeyWithFees,
     fee_config::L1FeeUpdate,
     pool_registry::PoolRegistry,
     pool_updates::{L1PoolUpdate, Slot0Data}
 };
 
 use crate::{
+    checked_fee, checked_tick_spacing,
     pool_data_loader::{DataLoader, IUniswapV4Pool, PoolDataLoader},
-    pool_providers::{PoolEventStream, ProviderChainUpdate}
+    pool_providers::{
+        PoolEventStream, ProviderChainUpdate,
+        update_engine::{BlockAction, PoolUpdateEngine}
+    }
 };
 
 #[derive(Debug, Error)]
@@ -40,32 +45,23 @@ pub enum PoolUpdateError {
     ReorgHandling(String)
 }
 
-/// Stored event for reorg handling - only liquidity events need to be stored
-#[derive(Debug, Clone)]
-struct StoredEvent {
-    block:           u64,
-    tx_index:        u64,
-    log_index:       u64,
-    pool_id:         PoolId,
-    liquidity_event: ModifyLiquidityEventData
-}
-
-/// Pool update provider that streams pool state changes
+/// Pool update provider that streams pool state changes. This is the I/O
+/// shell around [`PoolUpdateEngine`], fetching the logs and chain data the
+/// engine asks for.
 pub struct PoolUpdateProvider<P, T>
 where
     P: Provider<T> + 'static,
     T: V4Network
 {
-    provider:                   Arc<P>,
-    pool_manager:               Address,
-    address_book:               T::AddressBook,
-    pool_registry:              T::PoolRegistry,
-    tracked_pools:              HashSet<PoolId>,
-    event_history:              VecDeque<StoredEvent>,
-    current_block:              u64,
-    reorg_detection_blocks:     u64,
-    reorg_lookback_block_chunk: u64,
-    stream_mode:                StreamMode
+    provider:               Arc<P>,
+    pool_manager:           Address,
+    address_book:           T::AddressBook,
+    pool_registry:          T::PoolRegistry,
+    engine:                 PoolUpdateEngine<T>,
+    // emit a fresh slot0 for every tracked pool before the first block
+    refresh_slot0_on_start: bool,
+    // trace transactions for `batchUpdatePools` calls made through other contracts
+    trace_internal_calls:   bool
 }
 
 impl<P, T> PoolUpdateProvider<P, T>
@@ -124,14 +120,15 @@ where
         Self {
             provider,
             pool_manager,
-            tracked_pools: HashSet::new(),
-            event_history: VecDeque::with_capacity(reorg_detection_blocks as usize),
-            current_block,
-            reorg_detection_blocks,
-            reorg_lookback_block_chunk,
-            stream_mode: StreamMode::default(),
             address_book,
-            pool_registry
+            pool_registry,
+            engine: PoolUpdateEngine::new(
+                current_block,
+                reorg_detection_blocks,
+                reorg_lookback_block_chunk
+            ),
+            refresh_slot0_on_start: false,
+            trace_internal_calls: false
         }
     }
 
@@ -154,95 +151,57 @@ where
 
     /// Set the stream mode for this provider
     pub fn with_stream_mode(mut self, mode: StreamMode) -> Self {
-        self.stream_mode = mode;
+        self.engine.set_stream_mode(mode);
         self
     }
 
-    /// Add a pool to track
-    pub fn add_pool(&mut self, pool_id: PoolId) {
-        self.tracked_pools.insert(pool_id);
-    }
-
-    /// Remove a pool from tracking
-    pub fn remove_pool(&mut self, pool_id: PoolId) {
-        self.tracked_pools.remove(&pool_id);
+    /// Fetch slot0 for every tracked pool when the stream starts and emit it as
+    /// `UpdatedSlot0`, so pools without recent activity don't keep whatever
+    /// price they were loaded with
+    pub fn with_refresh_slot0_on_start(mut self, enabled: bool) -> Self {
+        self.refresh_slot0_on_start = enabled;
+        self
     }
 
-    /// Get all tracked pool IDs
-    pub fn tracked_pools(&self) -> Vec<PoolId> {
-        self.tracked_pools.iter().copied().collect()
+    /// Also pick up `batchUpdatePools` calls made through a multisig or proxy
+    /// rather than sent to the controller directly. Every other transaction in
+    /// a block is traced with `debug_traceTransaction`, so this needs a node
+    /// with the debug namespace and costs a request per transaction.
+    pub fn with_internal_call_tracing(mut self, enabled: bool) -> Self {
+        self.trace_internal_calls = enabled;
+        self
     }
 
-    /// Process a swap event log
-    fn process_swap_event(
-        &self,
-        log: &alloy_rpc_types::Log,
-        block_number: u64
-    ) -> Option<PoolUpdate<T>> {
-        if let Ok(swap_event) = IUniswapV4Pool::Swap::decode_log(&log.inner) {
-            // Check if we're tracking this Uniswap pool ID
-            if self.tracked_pools.contains(&swap_event.id) {
-                let event_data = SwapEventData {
-                    sender:         swap_event.sender,
-                    amount0:        swap_event.amount0,
-                    amount1:        swap_event.amount1,
-                    sqrt_price_x96: swap_event.sqrtPriceX96,
-                    liquidity:      swap_event.liquidity,
-                    tick:           swap_event.tick.as_i32(),
-                    fee:            swap_event.fee.to()
-                };
-
-                return Some(PoolUpdate::SwapEvent {
-                    pool_id:   swap_event.id, // Use Uniswap pool ID
-                    block:     block_number,
-                    tx_index:  log.transaction_index.unwrap(),
-                    log_index: log.log_index.unwrap(),
-                    event:     event_data
-                });
+    /// Fetch the current slot0 of every tracked pool
+    pub async fn refresh_slot0(&self) -> Vec<PoolUpdate<T>> {
+        let mut updates = Vec::new();
+        for pool_id in self.engine.tracked_pools() {
+            match self.fetch_slot0_data(pool_id).await {
+                Ok(data) => updates.push(PoolUpdate::UpdatedSlot0 { pool_id, data }),
+                Err(e) => tracing::error!("Failed to refresh slot0 for pool {pool_id:?}: {e}")
             }
         }
-        None
+        updates
     }
 
-    /// Process a liquidity event log
-    fn process_liquidity_event(
-        &mut self,
-        log: &alloy_rpc_types::Log,
-        block_number: u64,
-        store_in_history: bool
-    ) -> Option<PoolUpdate<T>> {
-        if let Ok(modify_event) = IUniswapV4Pool::ModifyLiquidity::decode_log(&log.inner) {
-            // Check if we're tracking this Uniswap pool ID
-            if self.tracked_pools.contains(&modify_event.id) {
-                let event_data = ModifyLiquidityEventData {
-                    sender:          modify_event.sender,
-                    tick_lower:      modify_event.tickLower.as_i32(),
-                    tick_upper:      modify_event.tickUpper.as_i32(),
-                    liquidity_delta: modify_event.liquidityDelta,
-                    salt:            modify_event.salt.0
-                };
+    /// The state machine driving this provider
+    pub fn engine(&self) -> &PoolUpdateEngine<T> {
+        &self.engine
+    }
 
-                // Store in history only if requested
-                if store_in_history {
-                    self.add_to_history(StoredEvent {
-                        block:           block_number,
-                        tx_index:        log.transaction_index.unwrap(),
-                        log_index:       log.log_index.unwrap(),
-                        pool_id:         modify_event.id, // Use Uniswap pool ID
-                        liquidity_event: event_data.clone()
-                    });
-                }
+    /// Add a pool to track
+    pub fn add_pool(&mut self, pool_id: PoolId) {
+        self.engine.add_pool(pool_id);
+    }
 
-                return Some(PoolUpdate::LiquidityEvent {
-                    pool_id:   modify_event.id, // Use Uniswap pool ID
-                    block:     block_number,
-                    tx_index:  log.transaction_index.unwrap(),
-                    log_index: log.log_index.unwrap(),
-                    event:     event_data
-                });
-            }
-        }
-        None
+    /// Remove a pool from tracking
+    pub fn remove_pool(&mut self, pool_id: PoolId) {
+        self.engine.remove_pool(pool_id);
+    }
+
+    /// Get all tracked pool IDs
+    pub fn tracked_pools(&self) -> Vec<PoolId> {
+        self.engine.tracked_pools()
     }
 
     /// Process events for a block range
@@ -252,17 +211,16 @@ where
         to_block: u64,
         store_in_history: bool
     ) -> Result<Vec<PoolUpdate<T>>, PoolUpdateError> {
-        let mut updates = Vec::new();
+        let tracked_pools = self.engine.tracked_pools();
 
         // If no pools are tracked, return early
-        if self.tracked_pools.is_empty() {
-            return Ok(updates);
+        if tracked_pools.is_empty() {
+            return Ok(Vec::new());
         }
 
         // Create pool topics for filtering - tracked_pools already contains Uniswap
         // pool IDs
-        let pool_topics: Vec<_> = self
-            .tracked_pools
+        let pool_topics: Vec<_> = tracked_pools
             .iter()
             .map(|pool_id| pool_id.0.into())
             .collect();
@@ -282,64 +240,48 @@ where
             .from_block(from_block)
             .to_block(to_block);
 
-        // Get logs for both event types
-        let (swap_logs, modify_logs) = futures::try_join!(
+        // Not filtered by pool, the chain data can configure pools that aren't
+        // tracked yet and needs their initialization too
+        let initialize_filter = Filter::new()
+            .address(self.pool_manager)
+            .event_signature(IUniswapV4Pool::Initialize::SIGNATURE_HASH)
+            .from_block(from_block)
+            .to_block(to_block);
+
+        // Get logs for all three event types
+        let (swap_logs, modify_logs, initialize_logs) = futures::try_join!(
             self.provider.get_logs(&swap_filter),
-            self.provider.get_logs(&modify_filter)
+            self.provider.get_logs(&modify_filter),
+            self.provider.get_logs(&initialize_filter)
         )
         .map_err(|e| PoolUpdateError::Provider(format!("Failed to get logs: {e}")))?;
 
-        // Process swap logs
-        for log in swap_logs {
-            let block_number = log.block_number.unwrap_or(from_block);
-            if let Some(update) = self.process_swap_event(&log, block_number) {
-                updates.push(update);
-            }
-        }
-
-        // Process modify liquidity logs
-        for log in modify_logs {
-            let block_number = log.block_number.unwrap_or(from_block);
-            if let Some(update) = self.process_liquidity_event(&log, block_number, store_in_history)
-            {
-                updates.push(update);
-            }
-        }
+        let mut updates =
+            self.engine
+                .process_logs(&swap_logs, &modify_logs, from_block, store_in_history);
+        let initial_slot0s = self
+            .engine
+            .process_initialize_logs(&initialize_logs, &updates);
+        updates.extend(initial_slot0s);
 
         // Process chain specific data
-        let chain_specific_logs = self.fetch_chain_data(from_block, to_block).await?;
+        let chain_specific_logs = self
+            .fetch_chain_data(from_block, to_block, &initialize_logs)
+            .await?;
         updates.extend(chain_specific_logs);
 
         Ok(updates)
     }
 
-    /// Process events for a specific block
-    async fn process_block_events(
-        &mut self,
-        block_number: u64
-    ) -> Result<Vec<PoolUpdate<T>>, PoolUpdateError> {
-        // Use the shared helper with store_in_history = true for single blocks
-        self.process_events_for_block_range(block_number, block_number, true)
-            .await
-    }
-
-    /// Add event to history, maintaining the 10-block window
-    fn add_to_history(&mut self, event: StoredEvent) {
-        self.event_history.push_back(event);
-
-        // Maintain exactly reorg_detection_blocks worth of history
-        // Remove all events from blocks that are too old
-        let cutoff_block = self
-            .current_block
-            .saturating_sub(self.reorg_detection_blocks - 1);
-
-        // Remove all events from blocks older than cutoff
-        self.event_history.retain(|e| e.block >= cutoff_block);
+    /// Fetch the active liquidity of a pool at the current block, to reconcile
+    /// against the local state with `BaselinePoolState::reconcile_liquidity`
+    pub async fn fetch_active_liquidity(&self, pool_id: PoolId) -> Result<u128, PoolUpdateError> {
+        Ok(self.fetch_slot0_data(pool_id).await?.liquidity)
     }
 
     /// Fetch current slot0 data for a pool at the current block
     async fn fetch_slot0_data(&self, pool_id: PoolId) -> Result<Slot0Data, PoolUpdateError> {
-        self.fetch_slot0_data_at_block(pool_id, self.current_block)
+        self.fetch_slot0_data_at_block(pool_id, self.engine.current_block())
             .await
     }
 
@@ -377,23 +319,23 @@ where
         })
     }
 
-    /// Backfill events for missed blocks
-    async fn backfill_blocks(
+    /// Fetch events for a block range, chunked to avoid overwhelming the
+    /// provider
+    async fn fetch_blocks(
         &mut self,
         from_block: u64,
-        to_block: u64
+        to_block: u64,
+        store_in_history: bool
     ) -> Result<Vec<PoolUpdate<T>>, PoolUpdateError> {
         let mut all_updates = Vec::new();
+        let chunk = self.engine.reorg_lookback_block_chunk();
 
-        // Process blocks in chunks to avoid overwhelming the provider
         let mut current = from_block;
-
         while current <= to_block {
-            let end = (current + self.reorg_lookback_block_chunk - 1).min(to_block);
+            let end = (current + chunk - 1).min(to_block);
 
-            // Use the shared helper with store_in_history = false for backfilling
             let chunk_updates = self
-                .process_events_for_block_range(current, end, false)
+                .process_events_for_block_range(current, end, store_in_history)
                 .await?;
             all_updates.extend(chunk_updates);
 
@@ -403,224 +345,71 @@ where
         Ok(all_updates)
     }
 
-    /// Get inverse liquidity events for reorg handling
-    fn get_inverse_liquidity_events(&self, from_block: u64, to_block: u64) -> Vec<PoolUpdate<T>> {
-        let mut inverse_events = Vec::new();
-
-        // Iterate through history in reverse order to process most recent first
-        for event in self.event_history.iter().rev() {
-            if event.block < from_block || event.block > to_block {
-                continue;
-            }
-
-            // Create inverse event by negating liquidity delta
-            let inverse_event = ModifyLiquidityEventData {
-                sender:          event.liquidity_event.sender,
-                tick_lower:      event.liquidity_event.tick_lower,
-                tick_upper:      event.liquidity_event.tick_upper,
-                liquidity_delta: -event.liquidity_event.liquidity_delta,
-                salt:            event.liquidity_event.salt
-            };
-
-            inverse_events.push(PoolUpdate::LiquidityEvent {
-                pool_id:   event.pool_id,
-                block:     event.block,
-                tx_index:  event.tx_index,
-                log_index: event.log_index,
-                event:     inverse_event
-            });
-        }
-
-        inverse_events
-    }
-
-    /// Get pools affected by events
-    fn get_affected_pools(&self, updates: &[PoolUpdate<T>]) -> HashSet<PoolId> {
-        let mut affected_pools = HashSet::new();
-
-        for update in updates {
-            match update {
-                PoolUpdate::SwapEvent { pool_id, .. }
-                | PoolUpdate::LiquidityEvent { pool_id, .. }
-                | PoolUpdate::UpdatedSlot0 { pool_id, .. }
-                | PoolUpdate::FeeUpdate { pool_id, .. } => {
-                    affected_pools.insert(*pool_id);
-                }
-                PoolUpdate::ChainSpecific { pool_id, update } => {
-                    if update.is_pool_affected() {
-                        affected_pools.insert(*pool_id);
-                    }
-                }
-                _ => {}
-            }
-        }
-
-        affected_pools
-    }
-
-    /// Clear history for reorg
-    fn clear_history_from_block(&mut self, from_block: u64) {
-        self.event_history.retain(|event| event.block < from_block);
-    }
-
-    /// Handle a reorg event
-    async fn handle_reorg(&mut self) -> Vec<PoolUpdate<T>> {
-        let mut updates = Vec::new();
-        let reorg_start = self
-            .current_block
-            .saturating_sub(self.reorg_detection_blocks - 1);
-
-        // 1. First, emit the reorg event so the pipeline knows a reorg is happening
-        updates.push(PoolUpdate::Reorg { from_block: reorg_start, to_block: self.current_block });
-
-        // 2. Get inverse liquidity events
-        let inverse_events = self.get_inverse_liquidity_events(reorg_start, self.current_block);
-
-        // Filter inverse events based on stream mode
-        match self.stream_mode {
-            StreamMode::Full => {
-                updates.extend(inverse_events.clone());
-            }
-            StreamMode::InitializationOnly => {
-                // In InitializationOnly mode, we don't need inverse liquidity
-                // events as we're not tracking swap/liquidity
-                // changes
-            }
-        }
-
-        // 3. Clear affected history
-        self.clear_history_from_block(reorg_start);
-
-        // 4. Re-query the blocks
-        match self.backfill_blocks(reorg_start, self.current_block).await {
-            Ok(fresh_events) => {
-                // Get affected pools from both inverse and fresh events
-                let mut affected_pools = self.get_affected_pools(&inverse_events);
-                affected_pools.extend(self.get_affected_pools(&fresh_events));
-
-                // Add fresh events to history
-                for update in &fresh_events {
-                    if let Some(stored_event) = Self::update_to_stored_event(update) {
-                        self.add_to_history(stored_event);
-                    }
-                }
+    /// Handle a reorg event. If the reorged blocks can't be re-fetched the
+    /// engine is left as it was.
+    async fn handle_reorg(
+        &mut self,
+        from_block: u64,
+        to_block: u64
+    ) -> Result<Vec<PoolUpdate<T>>, PoolUpdateError> {
+        // Re-query the blocks, the engine adds them to its history itself
+        let fresh_events = self
+            .fetch_blocks(from_block, to_block, false)
+            .await
+            .map_err(|e| {
+                PoolUpdateError::ReorgHandling(format!(
+                    "Failed to backfill blocks {from_block} to {to_block}: {e}"
+                ))
+            })?;
 
-                // Filter fresh events based on stream mode
-                match self.stream_mode {
-                    StreamMode::Full => {
-                        updates.extend(fresh_events);
-                    }
-                    StreamMode::InitializationOnly => {
-                        // Only include initialization-related updates
-                        updates.extend(fresh_events.into_iter().filter(|update| match update {
-                            PoolUpdate::FeeUpdate { .. }
-                            | PoolUpdate::UpdatedSlot0 { .. }
-                            | PoolUpdate::NewPoolState { .. } => true,
-                            PoolUpdate::ChainSpecific { pool_id: _, update } => {
-                                update.is_initialization_event()
-                            }
-                            _ => false
-                        }));
-                    }
-                }
+        let reorg = self.engine.apply_reorg(fresh_events);
 
-                // 5. Query slot0 for affected pools
-                for pool_id in affected_pools {
-                    if let Ok(slot0_data) = self.fetch_slot0_data(pool_id).await {
-                        updates.push(PoolUpdate::UpdatedSlot0 { pool_id, data: slot0_data });
-                    }
-                }
-            }
-            Err(e) => {
-                // Log error but continue
-                panic!("Failed to backfill during reorg: {e}");
+        // Query slot0 for affected pools
+        let mut slot0s = Vec::new();
+        for pool_id in &reorg.affected_pools {
+            if let Ok(slot0_data) = self.fetch_slot0_data(*pool_id).await {
+                slot0s.push((*pool_id, slot0_data));
             }
         }
-        updates.push(PoolUpdate::Reorg { from_block: reorg_start, to_block: self.current_block });
 
-        updates
+        Ok(reorg.finish(slot0s))
     }
 
+    /// The updates for a newly received block. Only fails when a reorg can't
+    /// be unwound.
     pub async fn on_new_block(
         &mut self,
         block: <T as Network>::BlockResponse
-    ) -> Vec<PoolUpdate<T>> {
-        let mut updates = Vec::new();
+    ) -> Result<Vec<PoolUpdate<T>>, PoolUpdateError> {
         let block_number = block.header().number();
 
-        // Check for reorg
-        if block_number == self.current_block {
-            // Reorg detected!
-            updates = self.handle_reorg().await;
-        } else if block_number > self.current_block {
-            // Always emit NewBlock event first for normal block progression
-            updates.push(PoolUpdate::NewBlock(block_number));
-
-            // Then process block events
-            match self.process_block_events(block_number).await {
-                Ok(block_updates) => {
-                    // Filter updates based on stream mode
-                    match self.stream_mode {
-                        StreamMode::Full => {
-                            // Include all updates
-                            updates.extend(block_updates);
-                        }
-                        StreamMode::InitializationOnly => {
-                            // Only include initialization-related updates
-                            updates.extend(block_updates.into_iter().filter(
-                                |update| match update {
-                                    PoolUpdate::FeeUpdate { .. }
-                                    | PoolUpdate::UpdatedSlot0 { .. }
-                                    | PoolUpdate::NewPoolState { .. } => true,
-                                    PoolUpdate::ChainSpecific { pool_id: _, update } => {
-                                        update.is_initialization_event()
-                                    }
-                                    _ => false
-                                }
-                            ));
-                        }
-                    }
-                }
-                Err(e) => {
-                    tracing::error!("Failed to process block {}: {}", block_number, e);
-                }
+        let updates = match self.engine.on_block(block_number) {
+            BlockAction::Reorg { from_block, to_block } => {
+                self.handle_reorg(from_block, to_block).await?
             }
+            BlockAction::Advance { from_block, to_block } => {
+                let block_updates = self
+                    .fetch_blocks(from_block, to_block, true)
+                    .await
+                    .unwrap_or_else(|e| {
+                        tracing::error!("Failed to process block {}: {}", block_number, e);
+                        vec![]
+                    });
 
-            // Update current block
-            self.current_block = block_number;
-
-            // Clean up old events from history to maintain exactly reorg_detection_blocks
-            let cutoff_block = self
-                .current_block
-                .saturating_sub(self.reorg_detection_blocks - 1);
-            self.event_history.retain(|e| e.block >= cutoff_block);
-        } else if block_number < self.current_block {
-            // Block is behind our current block, this shouldn't happen in normal operation
-            tracing::warn!(
-                "Received old block {} when current block is {}",
-                block_number,
-                self.current_block
-            );
-        }
-
-        updates
-    }
-
-    /// Convert PoolUpdate to StoredEvent for history
-    /// Only liquidity events are stored since we re-query slot0 after reorgs
-    fn update_to_stored_event(update: &PoolUpdate<T>) -> Option<StoredEvent> {
-        match update {
-            PoolUpdate::LiquidityEvent { pool_id, block, tx_index, log_index, event } => {
-                Some(StoredEvent {
-                    block:           *block,
-                    tx_index:        *tx_index,
-                    log_index:       *log_index,
-                    pool_id:         *pool_id,
-                    liquidity_event: event.clone()
-                })
+                self.engine.apply_new_block(block_number, block_updates)
             }
-            _ => None
-        }
+            BlockAction::Ignore => {
+                // Block is behind our current block, this shouldn't happen in normal operation
+                tracing::warn!(
+                    "Received old block {} when current block is {}",
+                    block_number,
+                    self.engine.current_block()
+                );
+                vec![]
+            }
+        };
+
+        Ok(updates)
     }
 }
 
@@ -634,7 +423,16 @@ where
     update_provider:      Option<PoolUpdateProvider<P, T>>,
     block_stream:         B,
     processing: Option<
-        Pin<Box<dyn Future<Output = (PoolUpdateProvider<P, T>, Vec<PoolUpdate<T>>)> + Send>>
+        Pin<
+            Box<
+                dyn Future<
+                        Output = (
+                            PoolUpdateProvider<P, T>,
+                            Result<Vec<PoolUpdate<T>>, PoolUpdateError>
+                        )
+                    > + Send
+            >
+        >
     >,
     start_tracking_pools: Vec<PoolId>,
     stop_tracking_pools:  Vec<PoolId>,
@@ -658,6 +456,63 @@ where
             pool_reg: None
         }
     }
+
+    /// Pulls every update that can be produced without waiting, for driving
+    /// the stream from a synchronous loop. A block whose processing has to
+    /// wait on the provider stays in flight and is picked up by the next call.
+    pub fn try_drain_ready(&mut self, cx: &mut Context<'_>) -> Vec<PoolUpdate<T>>
+    where
+        Self: Unpin
+    {
+        let mut updates = Vec::new();
+        loop {
+            let was_processing = self.processing.is_some();
+            match self.poll_next_unpin(cx) {
+                Poll::Ready(Some(batch)) => updates.extend(batch),
+                Poll::Ready(None) => break,
+                // a block was just taken off the stream and still needs its first poll
+                Poll::Pending if !was_processing && self.processing.is_some() => continue,
+                Poll::Pending => break
+            }
+        }
+        updates
+    }
+
+    /// Turns the stream into an iterator that blocks on `handle` for each
+    /// batch, for scripts and data dumps. Each `next` call runs
+    /// [`Handle::block_on`](tokio::runtime::Handle::block_on), so iterate from
+    /// a plain thread, never from inside an async task.
+    pub fn into_blocking_iter(self, handle: tokio::runtime::Handle) -> BlockingStateIter<P, T, B> {
+        BlockingStateIter { stream: self, handle }
+    }
+}
+
+/// Blocking iterator over a [`StateStream`], see
+/// [`StateStream::into_blocking_iter`]
+pub struct BlockingStateIter<P, T, B>
+where
+    P: Provider<T> + 'static,
+    T: V4Network,
+    B: Stream<Item = <T as Network>::BlockResponse> + Unpin + Send + 'static,
+    PoolUpdateProvider<P, T>: ProviderChainUpdate<T>
+{
+    stream: StateStream<P, T, B>,
+    handle: tokio::runtime::Handle
+}
+
+impl<P, T, B> Iterator for BlockingStateIter<P, T, B>
+where
+    P: Provider<T> + 'static,
+    T: V4Network,
+    B: Stream<Item = <T as Network>::BlockResponse> + Unpin + Send + 'static,
+    PoolUpdateProvider<P, T>: ProviderChainUpdate<T>,
+    StateStream<P, T, B>: Unpin
+{
+    type Item = Vec<PoolUpdate<T>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.handle.block_on(self.stream.next())
+    }
 }
 
 impl<P, T, B> PoolEventStream<T> for StateStream<P, T, B>
@@ -710,7 +565,15 @@ where
             if let Poll::Ready((provider, new_updates)) = processing.poll_unpin(cx) {
                 this.update_provider = Some(provider);
 
-                return Poll::Ready(Some(new_updates));
+                match new_updates {
+                    Ok(new_updates) => return Poll::Ready(Some(new_updates)),
+                    Err(e) => {
+                        // the engine is left as it was and carries on with the next block
+                        tracing::error!("Failed to process block: {e}");
+                        cx.waker().wake_by_ref();
+                        return Poll::Pending;
+                    }
+                }
             }
             this.processing = Some(processing);
 
@@ -728,6 +591,20 @@ where
             updater.pool_registry = pool_reg;
         }
 
+        if std::mem::take(&mut updater.refresh_slot0_on_start) {
+            cx.waker().wake_by_ref();
+            let update_provider = this.update_provider.take().unwrap();
+
+            this.processing = Some(
+                async move {
+                    let updates = update_provider.refresh_slot0().await;
+                    (update_provider, Ok(updates))
+                }
+                .boxed()
+            );
+            return Poll::Pending;
+        }
+
         if let Poll::Ready(possible_new_block) = this.block_stream.poll_next_unpin(cx) {
             if let Some(new_block) = possible_new_block {
                 cx.waker().wake_by_ref();
@@ -793,10 +670,12 @@ where
     async fn fetch_chain_data(
         &mut self,
         from_block: u64,
-        to_block: u64
+        to_block: u64,
+        initialize_logs: &[alloy_rpc_types::Log]
     ) -> Result<Vec<PoolUpdate<Ethereum>>, PoolUpdateError> {
         let mut updates =
             self.process_controller_logs(self.fetch_controller_logs(from_block, to_block).await?);
+        attach_initial_slot0(&mut updates, initialize_logs);
 
         updates.extend(
             self.fetch_controller_batch_updates(from_block, to_block)
@@ -850,10 +729,8 @@ where
                 .map_err(|e| PoolUpdateError::Provider(format!("Failed to get block: {e}")))?
                 .ok_or_else(|| PoolUpdateError::Provider("Block not found".to_string()))?;
 
-            if let Some(transactions) = block.transactions().as_transactions() {
-                for tx in transactions {
-                    updates.extend(self.process_batch_update_pools(tx, from_block));
-                }
+            for tx in self.full_transactions(block).await? {
+                updates.extend(self.batch_updates_in_tx(&tx, from_block).await?);
             }
         } else {
             // For block ranges, iterate through each block
@@ -865,11 +742,9 @@ where
                     .await
                     .map_err(|e| PoolUpdateError::Provider(format!("Failed to get block: {e}")))?;
 
-                if let Some(block) = block
-                    && let Some(transactions) = block.transactions().as_transactions()
-                {
-                    for tx in transactions {
-                        updates.extend(self.process_batch_update_pools(tx, block_num));
+                if let Some(block) = block {
+                    for tx in self.full_transactions(block).await? {
+                        updates.extend(self.batch_updates_in_tx(&tx, block_num).await?);
                     }
                 }
             }
@@ -877,6 +752,37 @@ where
         Ok(updates)
     }
 
+    /// The transactions of a block requested with `.full()`. Some nodes still
+    /// answer with hashes only, in which case each transaction is fetched on
+    /// its own rather than dropping the block's `batchUpdatePools` calls.
+    async fn full_transactions(
+        &self,
+        block: alloy_rpc_types::Block
+    ) -> Result<Vec<alloy_rpc_types::Transaction>, PoolUpdateError> {
+        let hashes = match block.transactions {
+            BlockTransactions::Full(transactions) => return Ok(transactions),
+            BlockTransactions::Uncle => return Ok(Vec::new()),
+            BlockTransactions::Hashes(hashes) => hashes
+        };
+
+        let mut transactions = Vec::with_capacity(hashes.len());
+        for hash in hashes {
+            let tx = self
+                .provider
+                .get_transaction_by_hash(hash)
+                .await
+                .map_err(|e| {
+                    PoolUpdateError::Provider(format!("Failed to get transaction {hash}: {e}"))
+                })?
+                .ok_or_else(|| {
+                    PoolUpdateError::Provider(format!("Transaction {hash} not found"))
+                })?;
+            transactions.push(tx);
+        }
+
+        Ok(transactions)
+    }
+
     /// Process controller event logs
     fn process_controller_logs(
         &mut self,
@@ -886,13 +792,25 @@ where
 
         for log in logs {
             let block_number = log.block_number.unwrap();
+            let tx_index = log.transaction_index.unwrap();
+            let log_index = log.log_index.unwrap();
 
             if let Ok(event) = types::ControllerV1::PoolConfigured::decode_log(&log.inner) {
+                let (Some(bundle_fee), Some(swap_fee), Some(protocol_fee)) = (
+                    checked_fee(event.bundleFee.to(), "bundle fee"),
+                    checked_fee(event.unlockedFee.to(), "unlocked fee"),
+                    checked_fee(event.protocolUnlockedFee.to(), "protocol unlocked fee")
+                ) else {
+                    continue;
+                };
+                let Some(tick_spacing) = checked_tick_spacing(event.tickSpacing.into()) else {
+                    continue;
+                };
                 let pool_key = PoolKey {
                     currency0:   event.asset0,
                     currency1:   event.asset1,
                     fee:         event.bundleFee,
-                    tickSpacing: I24::unchecked_from(event.tickSpacing),
+                    tickSpacing: tick_spacing,
                     hooks:       self.address_book().angstrom
                 };
 
@@ -911,11 +829,14 @@ where
                         pool_id,
                         token0: pool_key.currency0,
                         token1: pool_key.currency1,
-                        bundle_fee: event.bundleFee.to(),
-                        swap_fee: event.unlockedFee.to(),
-                        protocol_fee: event.protocolUnlockedFee.to(),
+                        bundle_fee,
+                        swap_fee,
+                        protocol_fee,
                         tick_spacing: event.tickSpacing as i32,
-                        block: block_number
+                        initial_slot0: None,
+                        block: block_number,
+                        tx_index,
+                        log_index
                     }
                 });
             }
@@ -938,7 +859,12 @@ where
 
                 updates.push(PoolUpdate::ChainSpecific {
                     pool_id,
-                    update: L1PoolUpdate::PoolRemoved { pool_id, block: block_number }
+                    update: L1PoolUpdate::PoolRemoved {
+                        pool_id,
+                        block: block_number,
+                        tx_index,
+                        log_index
+                    }
                 });
             }
         }
@@ -946,66 +872,110 @@ where
         updates
     }
 
-    /// Process batch update pools from transaction
-    fn process_batch_update_pools(
+    /// The `batchUpdatePools` fee updates made by a transaction. Unless
+    /// internal call tracing is on, only calls sent straight to the controller
+    /// are seen.
+    async fn batch_updates_in_tx(
         &self,
         tx: &alloy_rpc_types::Transaction,
         block_number: u64
+    ) -> Result<Vec<PoolUpdate<Ethereum>>, PoolUpdateError> {
+        let controller = self.address_book().controller_v1;
+        let tx_index = tx.transaction_index.unwrap_or_default();
+
+        if tx.to() == Some(controller) {
+            return Ok(self.process_batch_update_pools(tx.input(), tx_index, block_number));
+        }
+        if !self.trace_internal_calls {
+            return Ok(Vec::new());
+        }
+
+        let hash = tx.tx_hash();
+        let trace: CallFrame = self
+            .provider
+            .raw_request(
+                "debug_traceTransaction".into(),
+                (hash, TracerConfig { tracer: "callTracer" })
+            )
+            .await
+            .map_err(|e| {
+                PoolUpdateError::Provider(format!("Failed to trace transaction {hash}: {e}"))
+            })?;
+
+        let mut inputs = Vec::new();
+        trace.calls_to(controller, &mut inputs);
+
+        Ok(inputs
+            .into_iter()
+            .flat_map(|input| self.process_batch_update_pools(input, tx_index, block_number))
+            .collect())
+    }
+
+    /// Decode `batchUpdatePools` calldata sent to the controller
+    fn process_batch_update_pools(
+        &self,
+        input: &[u8],
+        tx_index: u64,
+        block_number: u64
     ) -> Vec<PoolUpdate<Ethereum>> {
         let mut updates = Vec::new();
 
-        // Check if transaction is to the controller
-        if tx.to() == Some(self.address_book().controller_v1) {
-            // Try to decode as batchUpdatePools call
-            if let Ok(call) = types::ControllerV1::batchUpdatePoolsCall::abi_decode(tx.input()) {
-                for update in call.updates {
-                    // Normalize asset order
-                    let (_asset0, _asset1) = if update.assetB > update.assetA {
-                        (update.assetA, update.assetB)
-                    } else {
-                        (update.assetB, update.assetA)
-                    };
-                    let pools = self.pool_registry.get_pools_by_token_pair(
+        // Try to decode as batchUpdatePools call
+        if let Ok(call) = types::ControllerV1::batchUpdatePoolsCall::abi_decode(input) {
+            for update in call.updates {
+                let (Some(bundle_fee), Some(swap_fee), Some(protocol_fee)) = (
+                    checked_fee(update.bundleFee.to(), "bundle fee"),
+                    checked_fee(update.unlockedFee.to(), "unlocked fee"),
+                    checked_fee(update.protocolUnlockedFee.to(), "protocol unlocked fee")
+                ) else {
+                    continue;
+                };
+                // Normalize asset order
+                let (_asset0, _asset1) = if update.assetB > update.assetA {
+                    (update.assetA, update.assetB)
+                } else {
+                    (update.assetB, update.assetA)
+                };
+                let pools = self.pool_registry.get_pools_by_token_pair(
+                    update.assetA,
+                    update.assetB,
+                    Some(self.address_book().angstrom)
+                );
+
+                // Find the pool with matching fee tier
+                let pool_key = pools
+                    .iter()
+                    .find(|pk| pk.fee.to::<u32>() == update.bundleFee.to::<u32>())
+                    .cloned()
+                    .cloned();
+
+                if pool_key.is_none() && !pools.is_empty() {
+                    tracing::warn!(
+                        "No pool found matching fee tier {} for token pair ({}, {}), skipping fee \
+                         update",
+                        update.bundleFee,
                         update.assetA,
-                        update.assetB,
-                        Some(self.address_book().angstrom)
+                        update.assetB
                     );
+                }
 
-                    // Find the pool with matching fee tier
-                    let pool_key = pools
-                        .iter()
-                        .find(|pk| pk.fee.to::<u32>() == update.bundleFee.to::<u32>())
-                        .cloned()
-                        .cloned();
-
-                    if pool_key.is_none() && !pools.is_empty() {
-                        tracing::warn!(
-                            "No pool found matching fee tier {} for token pair ({}, {}), skipping \
-                             fee update",
-                            update.bundleFee,
-                            update.assetA,
-                            update.assetB
-                        );
-                    }
-
-                    if let Some(pool_key) = pool_key {
-                        // Get the Uniswap pool ID from registry
-                        let angstrom_pool_id = PoolId::from(pool_key);
-                        let pool_id = self
-                            .pool_registry
-                            .private_key_from_public(&angstrom_pool_id)
-                            .unwrap();
-
-                        updates.push(PoolUpdate::FeeUpdate {
-                            pool_id,
-                            block: block_number,
-                            update: L1FeeUpdate {
-                                bundle_fee:   update.bundleFee.to(),
-                                swap_fee:     update.unlockedFee.to(),
-                                protocol_fee: update.protocolUnlockedFee.to()
-                            }
-                        });
-                    }
+                if let Some(pool_key) = pool_key {
+                    // Get the Uniswap pool ID from registry
+                    let angstrom_pool_id = PoolId::from(pool_key);
+                    let pool_id = self
+                        .pool_registry
+                        .private_key_from_public(&angstrom_pool_id)
+                        .unwrap();
+
+                    // decoded from calldata, so the update sits at the start of
+                    // its transaction
+                    updates.push(PoolUpdate::FeeUpdate {
+                        pool_id,
+                        block: block_number,
+                        tx_index,
+                        log_index: 0,
+                        update: L1FeeUpdate { bundle_fee, swap_fee, protocol_fee }
+                    });
                 }
             }
         }
@@ -1014,6 +984,45 @@ where
     }
 }
 
+/// Parameters for `debug_traceTransaction`
+#[derive(Debug, Clone, Serialize)]
+struct TracerConfig {
+    tracer: &'static str
+}
+
+/// A frame of the geth `callTracer` output, keeping only what's needed to
+/// find calls into the controller
+#[derive(Debug, Deserialize)]
+struct CallFrame {
+    #[serde(rename = "type")]
+    kind:  String,
+    #[serde(default)]
+    to:    Option<Address>,
+    #[serde(default)]
+    input: Bytes,
+    #[serde(default)]
+    error: Option<String>,
+    #[serde(default)]
+    calls: Vec<CallFrame>
+}
+
+impl CallFrame {
+    /// Collects the input of every call into `target` that took effect.
+    /// Reverted frames are skipped together with their subcalls, and so are
+    /// delegate and static calls, which can't change the target's state.
+    fn calls_to<'a>(&'a self, target: Address, inputs: &mut Vec<&'a Bytes>) {
+        if self.error.is_some() {
+            return;
+        }
+        if self.kind == "CALL" && self.to == Some(target) {
+            inputs.push(&self.input);
+        }
+        for call in &self.calls {
+            call.calls_to(target, inputs);
+        }
+    }
+}
+
 pub async fn fetch_angstrom_pools<P>(
     mut deploy_block: u64,
     end_block: u64,
@@ -1064,19 +1073,25 @@ where
             if let Ok(pool) =
                 types::ControllerV1::PoolConfigured::decode_log(&log.clone().into_inner())
             {
+                let (Some(bundle_fee), Some(swap_fee), Some(protocol_fee)) = (
+                    checked_fee(pool.bundleFee.to(), "bundle fee"),
+                    checked_fee(pool.unlockedFee.to(), "unlocked fee"),
+                    checked_fee(pool.protocolUnlockedFee.to(), "protocol unlocked fee")
+                ) else {
+                    return set;
+                };
+                let Some(tick_spacing) = checked_tick_spacing(pool.tickSpacing.into()) else {
+                    return set;
+                };
                 let pool_key_with_fees = PoolKeyWithFees {
                     pool_key: PoolKey {
                         currency0:   pool.asset0,
                         currency1:   pool.asset1,
                         fee:         pool.bundleFee,
-                        tickSpacing: I24::unchecked_from(pool.tickSpacing),
+                        tickSpacing: tick_spacing,
                         hooks:       angstrom_address
                     },
-                    fee_cfg:  L1FeeConfiguration {
-                        bundle_fee:   pool.bundleFee.to(),
-                        swap_fee:     pool.unlockedFee.to(),
-                        protocol_fee: pool.protocolUnlockedFee.to()
-                    }
+                    fee_cfg:  L1FeeConfiguration { bundle_fee, swap_fee, protocol_fee }
                 };
 
                 let mut raw = pool_key_with_fees.pool_key;
@@ -1102,5 +1117,654 @@ where
             set
         })
         .into_values()
+        // sorted so startup sees the pools in the same order every run
+        .sorted_by_key(|pool| PoolId::from(pool.pool_key))
         .collect::<Vec<_>>()
 }
+
+/// Fills in the initial price of pools configured in the range from their
+/// pool manager `Initialize` events, so they don't start out at a default
+/// price if the pool data read misses the initialization
+fn attach_initial_slot0(
+    updates: &mut [PoolUpdate<Ethereum>],
+    initialize_logs: &[alloy_rpc_types::Log]
+) {
+    let initialized: HashMap<PoolId, Slot0Data> = initialize_logs
+        .iter()
+        .filter_map(|log| IUniswapV4Pool::Initialize::decode_log(&log.inner).ok())
+        .map(|event| {
+            let slot0 = Slot0Data {
+                sqrt_price_x96: event.sqrtPriceX96,
+                tick:           event.tick.as_i32(),
+                liquidity:      0
+            };
+            (event.id, slot0)
+        })
+        .collect();
+
+    for update in updates {
+        if let PoolUpdate::ChainSpecific {
+            update: L1PoolUpdate::NewPool { pool_id, initial_slot0, .. },
+            ..
+        } = update
+        {
+            *initial_slot0 = initialized.get(pool_id).cloned();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::Waker;
+
+    use alloy_primitives::{
+        U256,
+        aliases::{I24, U24}
+    };
+    use alloy_provider::{ProviderBuilder, RootProvider, mock::Asserter};
+    use alloy_sol_types::SolValue;
+    use uni_v4_structure::{
+        L1AddressBook,
+        pool_key::MAX_LP_FEE,
+        pool_registry::{L1PoolRegistry, UniswapPoolIdSet},
+        sqrt_pricex96::SqrtPriceX96
+    };
+
+    use super::*;
+    use crate::{
+        baseline_pool_factory::{BaselinePoolFactory, UpdateMessage},
+        mock_provider::MockProvider,
+        pool_data_loader::{PoolDataV4, TicksWithBlock},
+        pool_providers::ProviderChainInitialization
+    };
+
+    const ANGSTROM: Address = Address::repeat_byte(0xaa);
+
+    fn block(number: u64) -> alloy_rpc_types::Block {
+        let mut block = alloy_rpc_types::Block::<alloy_rpc_types::Transaction>::default();
+        block.header.inner.number = number;
+        block
+    }
+
+    fn angstrom_pool(token1: u8) -> PoolKeyWithFees<L1FeeConfiguration> {
+        PoolKeyWithFees {
+            pool_key: PoolKey {
+                currency0:   Address::with_last_byte(1),
+                currency1:   Address::with_last_byte(token1),
+                fee:         U24::ZERO,
+                tickSpacing: I24::unchecked_from(60),
+                hooks:       ANGSTROM
+            },
+            fee_cfg:  L1FeeConfiguration { bundle_fee: 0, swap_fee: 0, protocol_fee: 0 }
+        }
+    }
+
+    /// Provider answering every RPC call with the next response queued on
+    /// `asserter`.
+    fn mocked_provider(asserter: &Asserter) -> RootProvider<Ethereum> {
+        ProviderBuilder::new()
+            .disable_recommended_fillers()
+            .connect_mocked_client(asserter.clone())
+    }
+
+    /// Controller log configuring the pool `angstrom_pool(token1)` describes,
+    /// emitted at block 5.
+    fn pool_configured(controller: Address, token1: u8, bundle_fee: u32) -> alloy_rpc_types::Log {
+        let event = types::ControllerV1::PoolConfigured {
+            asset0:              Address::with_last_byte(1),
+            asset1:              Address::with_last_byte(token1),
+            tickSpacing:         60,
+            bundleFee:           U24::from(bundle_fee),
+            unlockedFee:         U24::from(3000),
+            protocolUnlockedFee: U24::from(500)
+        };
+        alloy_rpc_types::Log {
+            inner: alloy_primitives::Log { address: controller, data: event.encode_log_data() },
+            block_number: Some(5),
+            transaction_index: Some(0),
+            log_index: Some(token1 as u64),
+            ..Default::default()
+        }
+    }
+
+    fn transaction(
+        to: Address,
+        input: Bytes,
+        block_number: u64,
+        index: u64
+    ) -> alloy_rpc_types::Transaction {
+        let legacy = alloy_consensus::TxLegacy {
+            to: alloy_primitives::TxKind::Call(to),
+            input,
+            ..Default::default()
+        };
+        let envelope = alloy_consensus::TxEnvelope::Legacy(alloy_consensus::Signed::new_unhashed(
+            legacy,
+            alloy_primitives::Signature::test_signature()
+        ));
+        alloy_rpc_types::Transaction {
+            inner:               alloy_consensus::transaction::Recovered::new_unchecked(
+                envelope,
+                Address::ZERO
+            ),
+            block_hash:          None,
+            block_number:        Some(block_number),
+            transaction_index:   Some(index),
+            effective_gas_price: None
+        }
+    }
+
+    fn batch_update_call(
+        pool: &PoolKeyWithFees<L1FeeConfiguration>,
+        unlocked_fee: u32
+    ) -> types::ControllerV1::batchUpdatePoolsCall {
+        types::ControllerV1::batchUpdatePoolsCall {
+            updates: vec![types::ControllerV1::PoolUpdate {
+                assetA:              pool.pool_key.currency1,
+                assetB:              pool.pool_key.currency0,
+                bundleFee:           pool.pool_key.fee,
+                unlockedFee:         U24::from(unlocked_fee),
+                protocolUnlockedFee: U24::from(500)
+            }]
+        }
+    }
+
+    #[test]
+    fn refresh_slot0_on_start_emits_slot0_for_every_pool() {
+        let asserter = Asserter::new();
+        let provider = mocked_provider(&asserter);
+        let registry =
+            L1PoolRegistry::from_config(ANGSTROM, vec![angstrom_pool(2), angstrom_pool(3)]);
+        let pools = registry.all_uniswap_pool_ids().collect::<Vec<_>>();
+
+        let mut update_provider = PoolUpdateProvider::<_, Ethereum>::new_at_block(
+            Arc::new(provider),
+            Address::ZERO,
+            L1AddressBook::new(Address::ZERO, ANGSTROM),
+            registry,
+            100
+        )
+        .with_refresh_slot0_on_start(true);
+        for pool_id in &pools {
+            update_provider.add_pool(*pool_id);
+        }
+
+        let mut ticks = HashMap::new();
+        for (pool_id, tick) in update_provider.tracked_pools().into_iter().zip([-120, 240]) {
+            let data = PoolDataV4 {
+                token0Decimals: 18,
+                token1Decimals: 6,
+                liquidity:      1_000,
+                sqrtPrice:      U160::from(1u128 << 96),
+                tick:           I24::unchecked_from(tick),
+                liquidityNet:   0
+            };
+            asserter.push_success(&alloy_primitives::Bytes::from(data.abi_encode()));
+            ticks.insert(pool_id, tick);
+        }
+
+        let mut stream = StateStream::new(update_provider, futures::stream::pending());
+        let updates = stream.try_drain_ready(&mut Context::from_waker(Waker::noop()));
+
+        assert_eq!(updates.len(), pools.len());
+        for update in updates {
+            let PoolUpdate::UpdatedSlot0 { pool_id, data } = update else {
+                panic!("expected slot0 update, got {update:?}")
+            };
+            assert_eq!(data.tick, ticks[&pool_id]);
+            assert_eq!(data.liquidity, 1_000);
+        }
+    }
+
+    #[tokio::test]
+    async fn initial_pools_keep_the_fetched_fees() {
+        let asserter = Asserter::new();
+        let provider = Arc::new(mocked_provider(&asserter));
+        let controller = Address::repeat_byte(0xcc);
+        let address_book = L1AddressBook::new(controller, ANGSTROM);
+        let logs = vec![pool_configured(controller, 2, 100)];
+
+        asserter.push_success(&logs);
+        let fetched = provider.fetch_pools(address_book, 0, 10).await.unwrap();
+        assert_eq!(fetched.len(), 1);
+        assert_eq!(
+            fetched[0].fee_cfg,
+            L1FeeConfiguration { bundle_fee: 100, swap_fee: 3000, protocol_fee: 500 }
+        );
+
+        asserter.push_success(&logs);
+        let data = PoolDataV4 {
+            token0Decimals: 18,
+            token1Decimals: 18,
+            liquidity:      1_000,
+            sqrtPrice:      U160::from(1u128 << 96),
+            tick:           I24::ZERO,
+            liquidityNet:   0
+        };
+        asserter.push_success(&alloy_primitives::Bytes::from(data.abi_encode()));
+        for _ in 0..2 {
+            let ticks = TicksWithBlock {
+                ticks:       vec![],
+                validTo:     U256::ZERO,
+                blockNumber: U256::from(10)
+            };
+            asserter.push_success(&alloy_primitives::Bytes::from(ticks.abi_encode()));
+        }
+
+        let (factory, pools) = BaselinePoolFactory::new(
+            0,
+            10,
+            address_book,
+            L1PoolRegistry::new(ANGSTROM),
+            provider,
+            Address::ZERO,
+            Some(1),
+            None,
+            None,
+            Some(1)
+        )
+        .await;
+
+        let pool_id = factory
+            .registry()
+            .make_pool_id_set(PoolId::from(fetched[0].pool_key))
+            .unwrap()
+            .uniswap_pool_id();
+        assert_eq!(*pools.get(&pool_id).unwrap().fee_config(), fetched[0].fee_cfg);
+    }
+
+    #[tokio::test]
+    async fn fetched_pools_come_back_in_pool_id_order() {
+        let asserter = Asserter::new();
+        let provider = mocked_provider(&asserter);
+        let controller = Address::repeat_byte(0xcc);
+        let logs = (2..12u8)
+            .map(|token1| pool_configured(controller, token1, 100))
+            .collect::<Vec<_>>();
+        let fetch = || fetch_angstrom_pools(0, 10, ANGSTROM, controller, &provider);
+
+        asserter.push_success(&logs);
+        let first = fetch().await;
+        asserter.push_success(&logs);
+        let second = fetch().await;
+
+        assert_eq!(first.len(), 10);
+        assert_eq!(first, second);
+        assert!(
+            first
+                .iter()
+                .map(|pool| PoolId::from(pool.pool_key))
+                .is_sorted()
+        );
+    }
+
+    #[test]
+    fn blocking_iter_matches_polling() {
+        let state_stream = || {
+            let provider = MockProvider::<Ethereum>::new();
+            let update_provider = PoolUpdateProvider::<_, Ethereum>::new_at_block(
+                Arc::new(provider),
+                Address::ZERO,
+                L1AddressBook::new(Address::ZERO, Address::ZERO),
+                L1PoolRegistry::from_config(Address::ZERO, vec![]),
+                100
+            );
+            StateStream::new(update_provider, futures::stream::iter((101..=103).map(block)))
+        };
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let polled = runtime.block_on(state_stream().collect::<Vec<_>>());
+        let blocking = state_stream()
+            .into_blocking_iter(runtime.handle().clone())
+            .collect::<Vec<_>>();
+
+        assert_eq!(blocking.len(), 3);
+        assert_eq!(format!("{blocking:?}"), format!("{polled:?}"));
+    }
+
+    #[test]
+    fn try_drain_ready_processes_every_queued_block() {
+        let provider = MockProvider::<Ethereum>::new();
+        let update_provider = PoolUpdateProvider::<_, Ethereum>::new_at_block(
+            Arc::new(provider),
+            Address::ZERO,
+            L1AddressBook::new(Address::ZERO, Address::ZERO),
+            L1PoolRegistry::from_config(Address::ZERO, vec![]),
+            100
+        );
+        let blocks = futures::stream::iter((101..=103).map(block));
+        let mut stream = StateStream::new(update_provider, blocks);
+
+        let updates = stream.try_drain_ready(&mut Context::from_waker(Waker::noop()));
+
+        let new_blocks = updates
+            .iter()
+            .filter_map(|update| match update {
+                PoolUpdate::NewBlock(block) => Some(*block),
+                _ => None
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(new_blocks, vec![101, 102, 103]);
+        assert_eq!(stream.update_provider.unwrap().engine().current_block(), 103);
+    }
+
+    #[tokio::test]
+    async fn hash_only_blocks_fetch_their_transactions() {
+        let asserter = Asserter::new();
+        let provider = mocked_provider(&asserter);
+        let controller = Address::repeat_byte(0xcc);
+        let pool = angstrom_pool(2);
+        let update_provider = PoolUpdateProvider::<_, Ethereum>::new_at_block(
+            Arc::new(provider),
+            Address::ZERO,
+            L1AddressBook::new(controller, ANGSTROM),
+            L1PoolRegistry::from_config(ANGSTROM, vec![pool.clone()]),
+            100
+        );
+
+        let call = batch_update_call(&pool, 3000);
+        let tx = transaction(controller, call.abi_encode().into(), 101, 4);
+        let mut hashes_only = block(101);
+        hashes_only.transactions = BlockTransactions::Hashes(vec![tx.tx_hash()]);
+        asserter.push_success(&tx);
+
+        let transactions = update_provider
+            .full_transactions(hashes_only)
+            .await
+            .unwrap();
+
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0].tx_hash(), tx.tx_hash());
+        assert_eq!(transactions[0].transaction_index, Some(4));
+        assert_eq!(transactions[0].input(), tx.input());
+    }
+
+    #[test]
+    fn traced_controller_calls_skip_reverted_frames() {
+        let controller = Address::repeat_byte(0xcc);
+        let multisig = Address::repeat_byte(0x5a);
+        let pool = angstrom_pool(2);
+        let applied = Bytes::from(batch_update_call(&pool, 3000).abi_encode());
+        let reverted = Bytes::from(batch_update_call(&pool, 9000).abi_encode());
+        let trace: CallFrame = serde_json::from_value(serde_json::json!({
+            "type": "CALL",
+            "to": multisig,
+            "input": "0x",
+            "calls": [
+                { "type": "CALL", "to": controller, "input": reverted, "error": "execution reverted" },
+                { "type": "DELEGATECALL", "to": multisig, "input": "0x", "calls": [
+                    { "type": "CALL", "to": controller, "input": applied }
+                ]},
+                { "type": "STATICCALL", "to": controller, "input": reverted }
+            ]
+        }))
+        .unwrap();
+
+        let mut inputs = Vec::new();
+        trace.calls_to(controller, &mut inputs);
+
+        assert_eq!(inputs, vec![&applied]);
+    }
+
+    #[tokio::test]
+    async fn block_range_picks_up_tracked_pool_events_only() {
+        let pool_manager = Address::repeat_byte(0x44);
+        let registry = L1PoolRegistry::from_config(ANGSTROM, vec![angstrom_pool(2)]);
+        let tracked = registry.all_uniswap_pool_ids().next().unwrap();
+        let untracked = PoolId::repeat_byte(9);
+        let log = |data: alloy_primitives::LogData, block| alloy_rpc_types::Log {
+            inner: alloy_primitives::Log { address: pool_manager, data },
+            block_number: Some(block),
+            transaction_index: Some(0),
+            log_index: Some(0),
+            ..Default::default()
+        };
+        let swap = |pool_id, block| {
+            let event = IUniswapV4Pool::Swap {
+                id:           pool_id,
+                sender:       Address::ZERO,
+                amount0:      -1,
+                amount1:      1,
+                sqrtPriceX96: U160::from(1u128 << 96),
+                liquidity:    1,
+                tick:         I24::ZERO,
+                fee:          U24::ZERO
+            };
+            log(event.encode_log_data(), block)
+        };
+        let modify = IUniswapV4Pool::ModifyLiquidity {
+            id:             tracked,
+            sender:         Address::ZERO,
+            tickLower:      I24::unchecked_from(-60),
+            tickUpper:      I24::unchecked_from(60),
+            liquidityDelta: alloy_primitives::I256::ONE,
+            salt:           alloy_primitives::B256::ZERO
+        };
+
+        let provider = MockProvider::<Ethereum>::new();
+        provider.add_logs([
+            swap(tracked, 101),
+            swap(untracked, 101),
+            log(modify.encode_log_data(), 102),
+            swap(tracked, 105)
+        ]);
+        for number in 101..=102 {
+            provider.add_block(block(number));
+        }
+        let mut update_provider = PoolUpdateProvider::<_, Ethereum>::new_at_block(
+            Arc::new(provider),
+            pool_manager,
+            L1AddressBook::new(Address::repeat_byte(0xcc), ANGSTROM),
+            registry,
+            100
+        );
+        update_provider.add_pool(tracked);
+
+        let updates = update_provider
+            .process_events_for_block_range(101, 102, false)
+            .await
+            .unwrap();
+
+        assert_eq!(updates.len(), 2);
+        assert!(
+            updates
+                .iter()
+                .all(|update| update.pool_id() == Some(tracked))
+        );
+        assert!(matches!(updates[0], PoolUpdate::SwapEvent { block: 101, .. }));
+        assert!(matches!(updates[1], PoolUpdate::LiquidityEvent { block: 102, .. }));
+    }
+
+    #[tokio::test]
+    async fn failed_reorg_backfill_is_an_error() {
+        let registry = L1PoolRegistry::from_config(ANGSTROM, vec![angstrom_pool(2)]);
+        let tracked = registry.all_uniswap_pool_ids().next().unwrap();
+        // block 100 is never served, so the reorged block can't be re-fetched
+        let mut update_provider = PoolUpdateProvider::<_, Ethereum>::new_with_config(
+            Arc::new(MockProvider::<Ethereum>::new()),
+            Address::repeat_byte(0x44),
+            100,
+            1,
+            DEFAULT_REORG_LOOKBACK_BLOCK_CHUNK,
+            L1AddressBook::new(Address::repeat_byte(0xcc), ANGSTROM),
+            registry
+        );
+        update_provider.add_pool(tracked);
+
+        let err = update_provider.on_new_block(block(100)).await.unwrap_err();
+
+        assert!(matches!(err, PoolUpdateError::ReorgHandling(_)));
+        assert_eq!(update_provider.engine.current_block(), 100);
+    }
+
+    #[tokio::test]
+    async fn over_cap_fees_are_rejected() {
+        let controller = Address::repeat_byte(0xcc);
+        let provider = MockProvider::<Ethereum>::new();
+        let mut update_provider = PoolUpdateProvider::<_, Ethereum>::new_at_block(
+            Arc::new(provider),
+            Address::repeat_byte(0x44),
+            L1AddressBook::new(controller, ANGSTROM),
+            L1PoolRegistry::new(ANGSTROM),
+            5
+        );
+
+        let updates = update_provider.process_controller_logs(vec![
+            pool_configured(controller, 2, 2_000_000),
+            pool_configured(controller, 3, MAX_LP_FEE),
+        ]);
+
+        assert_eq!(updates.len(), 1);
+        assert!(matches!(
+            updates[0],
+            PoolUpdate::ChainSpecific {
+                update: L1PoolUpdate::NewPool { bundle_fee: MAX_LP_FEE, .. },
+                ..
+            }
+        ));
+        assert_eq!(
+            update_provider
+                .pool_registry
+                .all_angstrom_pool_ids()
+                .count(),
+            1
+        );
+
+        // nor loaded with the pools at startup
+        let asserter = Asserter::new();
+        asserter.push_success(&vec![
+            pool_configured(controller, 2, 2_000_000),
+            pool_configured(controller, 3, MAX_LP_FEE),
+        ]);
+        let fetched =
+            fetch_angstrom_pools(0, 10, ANGSTROM, controller, &mocked_provider(&asserter)).await;
+        assert_eq!(fetched.len(), 1);
+        assert_eq!(fetched[0].fee_cfg.bundle_fee, MAX_LP_FEE);
+    }
+
+    #[tokio::test]
+    async fn new_pools_carry_their_initialize_price() {
+        let controller = Address::repeat_byte(0xcc);
+        let pool_manager = Address::repeat_byte(0x44);
+        let pool = angstrom_pool(2);
+        let registry = L1PoolRegistry::from_config(ANGSTROM, vec![pool.clone(), angstrom_pool(3)]);
+        let uniswap_id = registry
+            .make_pool_id_set(PoolId::from(pool.pool_key))
+            .unwrap()
+            .uniswap_pool_id();
+        let tracked = registry
+            .all_uniswap_pool_ids()
+            .find(|pool_id| *pool_id != uniswap_id)
+            .unwrap();
+        let initialize = IUniswapV4Pool::Initialize {
+            id:           uniswap_id,
+            currency0:    pool.pool_key.currency0,
+            currency1:    pool.pool_key.currency1,
+            fee:          pool.pool_key.fee,
+            tickSpacing:  pool.pool_key.tickSpacing,
+            hooks:        ANGSTROM,
+            sqrtPriceX96: U160::from(1u128 << 96),
+            tick:         I24::ZERO
+        };
+
+        let asserter = Asserter::new();
+        let mut update_provider = PoolUpdateProvider::<_, Ethereum>::new_at_block(
+            Arc::new(mocked_provider(&asserter)),
+            pool_manager,
+            L1AddressBook::new(controller, ANGSTROM),
+            L1PoolRegistry::new(ANGSTROM),
+            4
+        );
+        update_provider.add_pool(tracked);
+
+        // swaps, liquidity events, initializations, controller logs and the
+        // block, so a second `Initialize` query would run out of responses
+        let no_logs = Vec::<alloy_rpc_types::Log>::new();
+        asserter.push_success(&no_logs);
+        asserter.push_success(&no_logs);
+        asserter.push_success(&vec![alloy_rpc_types::Log {
+            inner: alloy_primitives::Log {
+                address: pool_manager,
+                data:    initialize.encode_log_data()
+            },
+            block_number: Some(5),
+            transaction_index: Some(0),
+            log_index: Some(3),
+            ..Default::default()
+        }]);
+        asserter.push_success(&vec![pool_configured(controller, 2, 0)]);
+        asserter.push_success(&block(5));
+
+        let updates = update_provider
+            .process_events_for_block_range(5, 5, false)
+            .await
+            .unwrap();
+
+        let [
+            PoolUpdate::ChainSpecific {
+                update: L1PoolUpdate::NewPool { pool_id, initial_slot0: Some(slot0), .. },
+                ..
+            }
+        ] = updates.as_slice()
+        else {
+            panic!("expected a single new pool with its initial price, got {updates:?}");
+        };
+        assert_eq!(*pool_id, uniswap_id);
+        assert_eq!(slot0.sqrt_price_x96, U160::from(1u128 << 96));
+        assert_eq!(slot0.tick, 0);
+    }
+
+    #[tokio::test]
+    async fn created_pool_starts_at_its_initialize_price() {
+        let asserter = Asserter::new();
+        let provider = Arc::new(mocked_provider(&asserter));
+        asserter.push_success(&Vec::<alloy_rpc_types::Log>::new());
+        let (mut factory, _) = BaselinePoolFactory::new(
+            0,
+            10,
+            L1AddressBook::new(Address::repeat_byte(0xcc), ANGSTROM),
+            L1PoolRegistry::new(ANGSTROM),
+            provider,
+            Address::ZERO,
+            None,
+            None,
+            None,
+            None
+        )
+        .await;
+
+        // the read at the creation block doesn't see the initialization yet
+        let data = PoolDataV4 {
+            token0Decimals: 18,
+            token1Decimals: 18,
+            liquidity:      0,
+            sqrtPrice:      U160::ZERO,
+            tick:           I24::ZERO,
+            liquidityNet:   0
+        };
+        asserter.push_success(&alloy_primitives::Bytes::from(data.abi_encode()));
+        let batches = 2 * crate::baseline_pool_factory::INITIAL_TICKS_PER_SIDE.div_ceil(10);
+        for _ in 0..batches {
+            let ticks = TicksWithBlock {
+                ticks:       vec![],
+                validTo:     U256::ZERO,
+                blockNumber: U256::from(10)
+            };
+            asserter.push_success(&alloy_primitives::Bytes::from(ticks.abi_encode()));
+        }
+
+        let pool = angstrom_pool(2);
+        let initial_slot0 = Slot0Data {
+            sqrt_price_x96: U160::from(1u128 << 96),
+            tick:           0,
+            liquidity:      0
+        };
+        factory.queue_pool_creation(pool.pool_key, 10, pool.fee_cfg, Some(initial_slot0));
+
+        let Some(UpdateMessage::NewPool(_, state)) = factory.next().await else {
+            panic!("expected the queued pool to be created");
+        };
+        assert_eq!(state.current_price(), SqrtPriceX96::from(U160::from(1u128 << 96)));
+        assert_eq!(state.current_tick(), 0);
+    }
+}
@@ -1,5 +1,5 @@
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet, hash_map::Entry},
     future::Future,
     pin::Pin,
     sync::Arc,
@@ -12,6 +12,8 @@ use jsonrpsee::{
     proc_macros::rpc,
     ws_client::WsClient
 };
+use thiserror::Error;
+use tokio::sync::oneshot;
 use uni_v4_structure::{PoolId, pool_updates::Slot0Update};
 
 #[rpc(client, namespace = "angstrom")]
@@ -25,14 +27,48 @@ pub trait SubApi {
     async fn subscribe_amm(&self, pools: HashSet<PoolId>) -> jsonrpsee::core::SubscriptionResult;
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum Slot0SubscriptionError {
+    #[error("slot0 subscription was rejected: {0}")]
+    Rejected(String),
+    #[error("slot0 stream was dropped before the subscription was answered")]
+    Dropped
+}
+
+/// Resolves once the server has answered the subscription request made by
+/// [`Slot0Stream::subscribe_pools`] or [`Slot0Stream::unsubscribe_pools`],
+/// with the pools the accepted subscription covers. The request is only sent
+/// while the stream is being polled. A rejected request is retried by the
+/// stream, but the acknowledgement reports the first answer.
+pub struct Slot0SubscriptionAck(oneshot::Receiver<Result<HashSet<PoolId>, Slot0SubscriptionError>>);
+
+impl Slot0SubscriptionAck {
+    /// An acknowledgement that has already been answered
+    pub fn ready(result: Result<HashSet<PoolId>, Slot0SubscriptionError>) -> Self {
+        let (tx, rx) = oneshot::channel();
+        let _ = tx.send(result);
+        Self(rx)
+    }
+}
+
+impl Future for Slot0SubscriptionAck {
+    type Output = Result<HashSet<PoolId>, Slot0SubscriptionError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.0
+            .poll_unpin(cx)
+            .map(|result| result.unwrap_or(Err(Slot0SubscriptionError::Dropped)))
+    }
+}
+
 /// Trait for streams that provide slot0 updates with dynamic pool subscription
 /// management
 pub trait Slot0Stream: Stream<Item = Slot0Update> + Unpin + Send {
     /// Subscribe to updates for a set of pools
-    fn subscribe_pools(&mut self, pools: HashSet<PoolId>);
+    fn subscribe_pools(&mut self, pools: HashSet<PoolId>) -> Slot0SubscriptionAck;
 
     /// Unsubscribe from updates for a set of pools
-    fn unsubscribe_pools(&mut self, pools: HashSet<PoolId>);
+    fn unsubscribe_pools(&mut self, pools: HashSet<PoolId>) -> Slot0SubscriptionAck;
 
     /// Get the current set of subscribed pools
     fn subscribed_pools(&self) -> &HashSet<PoolId>;
@@ -43,12 +79,21 @@ pub struct Slot0Client {
     client:               Arc<WsClient>,
     subscription:         Option<Subscription<Slot0Update>>,
     subscribed_pools:     HashSet<PoolId>,
-    pending_subscription: Option<
-        Pin<Box<dyn Future<Output = Result<Subscription<Slot0Update>, ClientError>> + Send>>
-    >,
+    // pools covered by `subscription`, as accepted by the server
+    acknowledged_pools:   HashSet<PoolId>,
+    pending_subscription: Option<PendingSubscription>,
+    // waiting on the answer to `pending_subscription`
+    pending_acks:         Vec<oneshot::Sender<Result<HashSet<PoolId>, Slot0SubscriptionError>>>,
     waker:                Option<Waker>
 }
 
+type PendingSubscription = Pin<
+    Box<
+        dyn Future<Output = (HashSet<PoolId>, Result<Subscription<Slot0Update>, ClientError>)>
+            + Send
+    >
+>;
+
 impl Slot0Client {
     /// Create a new Slot0Client from a jsonrpsee WebSocket client
     pub fn new(client: Arc<WsClient>) -> Self {
@@ -56,18 +101,49 @@ impl Slot0Client {
             client,
             subscription: None,
             subscribed_pools: HashSet::new(),
+            acknowledged_pools: HashSet::new(),
             pending_subscription: None,
+            pending_acks: Vec::new(),
             waker: None
         }
     }
 
+    /// The pools the live subscription covers. Differs from
+    /// [`Slot0Stream::subscribed_pools`] while a change is waiting on the
+    /// server or after the server rejected one.
+    pub fn acknowledged_pools(&self) -> &HashSet<PoolId> {
+        &self.acknowledged_pools
+    }
+
     fn reconnect(&mut self) {
         let client = self.client.clone();
         let pools = self.subscribed_pools.clone();
 
-        let connection_future = Box::pin(async move { client.subscribe_amm(pools).await });
+        let connection_future = Box::pin(async move {
+            let subscription = client.subscribe_amm(pools.clone()).await;
+            (pools, subscription)
+        });
         self.pending_subscription = Some(connection_future);
     }
+
+    /// Resubscribes with the current pools and returns the acknowledgement for
+    /// it
+    fn resubscribe(&mut self) -> Slot0SubscriptionAck {
+        let (tx, rx) = oneshot::channel();
+        self.pending_acks.push(tx);
+        self.reconnect();
+
+        if let Some(waker) = self.waker.as_ref() {
+            waker.wake_by_ref();
+        }
+        Slot0SubscriptionAck(rx)
+    }
+
+    fn acknowledge(&mut self, result: Result<HashSet<PoolId>, Slot0SubscriptionError>) {
+        for ack in self.pending_acks.drain(..) {
+            let _ = ack.send(result.clone());
+        }
+    }
 }
 
 impl Stream for Slot0Client {
@@ -80,11 +156,14 @@ impl Stream for Slot0Client {
 
         if let Some(mut new_stream_future) = self.pending_subscription.take() {
             match new_stream_future.poll_unpin(cx) {
-                Poll::Ready(stream) => match stream {
+                Poll::Ready((pools, stream)) => match stream {
                     Ok(stream) => {
                         self.subscription = Some(stream);
+                        self.acknowledge(Ok(pools.clone()));
+                        self.acknowledged_pools = pools;
                     }
-                    Err(_) => {
+                    Err(e) => {
+                        self.acknowledge(Err(Slot0SubscriptionError::Rejected(e.to_string())));
                         cx.waker().wake_by_ref();
                         self.reconnect();
                     }
@@ -112,34 +191,16 @@ impl Stream for Slot0Client {
 }
 
 impl Slot0Stream for Slot0Client {
-    fn subscribe_pools(&mut self, pools: HashSet<PoolId>) {
+    fn subscribe_pools(&mut self, pools: HashSet<PoolId>) -> Slot0SubscriptionAck {
         self.subscribed_pools.extend(pools);
-
-        let client = self.client.clone();
-        let pools = self.subscribed_pools.clone();
-
-        let connection_future = Box::pin(async move { client.subscribe_amm(pools).await });
-        self.pending_subscription = Some(connection_future);
-
-        if let Some(waker) = self.waker.as_ref() {
-            waker.wake_by_ref();
-        }
+        self.resubscribe()
     }
 
-    fn unsubscribe_pools(&mut self, pools: HashSet<PoolId>) {
+    fn unsubscribe_pools(&mut self, pools: HashSet<PoolId>) -> Slot0SubscriptionAck {
         for pool in pools {
             self.subscribed_pools.remove(&pool);
         }
-
-        let client = self.client.clone();
-        let pools = self.subscribed_pools.clone();
-
-        let connection_future = Box::pin(async move { client.subscribe_amm(pools).await });
-        self.pending_subscription = Some(connection_future);
-
-        if let Some(waker) = self.waker.as_ref() {
-            waker.wake_by_ref();
-        }
+        self.resubscribe()
     }
 
     fn subscribed_pools(&self) -> &HashSet<PoolId> {
@@ -147,6 +208,31 @@ impl Slot0Stream for Slot0Client {
     }
 }
 
+/// Orders slot0 updates by `(current_block, seq_id)` and keeps only the latest
+/// one for each pool in each block, dropping any from before `min_block`.
+/// Updates can arrive out of order, this makes the last sequenced one win.
+pub(crate) fn latest_slot0_updates(updates: Vec<Slot0Update>, min_block: u64) -> Vec<Slot0Update> {
+    let mut latest: HashMap<(PoolId, u64), Slot0Update> = HashMap::new();
+    for update in updates {
+        if update.current_block < min_block {
+            continue;
+        }
+        match latest.entry((update.uni_pool_id, update.current_block)) {
+            Entry::Occupied(mut entry) if entry.get().seq_id < update.seq_id => {
+                entry.insert(update);
+            }
+            Entry::Occupied(_) => {}
+            Entry::Vacant(entry) => {
+                entry.insert(update);
+            }
+        }
+    }
+
+    let mut updates = latest.into_values().collect::<Vec<_>>();
+    updates.sort_by_key(|update| (update.current_block, update.seq_id));
+    updates
+}
+
 /// A no-op implementation of Slot0Stream for testing or when no stream is
 /// needed
 #[derive(Default)]
@@ -163,15 +249,141 @@ impl Stream for NoOpSlot0Stream {
 }
 
 impl Slot0Stream for NoOpSlot0Stream {
-    fn subscribe_pools(&mut self, _pools: HashSet<PoolId>) {
-        // No-op
+    fn subscribe_pools(&mut self, _pools: HashSet<PoolId>) -> Slot0SubscriptionAck {
+        Slot0SubscriptionAck::ready(Ok(HashSet::new()))
     }
 
-    fn unsubscribe_pools(&mut self, _pools: HashSet<PoolId>) {
-        // No-op
+    fn unsubscribe_pools(&mut self, _pools: HashSet<PoolId>) -> Slot0SubscriptionAck {
+        Slot0SubscriptionAck::ready(Ok(HashSet::new()))
     }
 
     fn subscribed_pools(&self) -> &HashSet<PoolId> {
         &self.placeholder
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use futures::SinkExt;
+    use jsonrpsee::ws_client::WsClientBuilder;
+    use serde_json::json;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::tungstenite::Message;
+
+    use super::*;
+
+    const REJECTED: PoolId = PoolId::repeat_byte(0xff);
+
+    /// Serves `angstrom_subscribeAmm`, rejecting any subscription that includes
+    /// [`REJECTED`]
+    async fn mock_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            let mut subscriptions = 0;
+
+            while let Some(Ok(message)) = ws.next().await {
+                let Message::Text(text) = message else { continue };
+                let request: serde_json::Value = serde_json::from_str(&text).unwrap();
+                let id = &request["id"];
+
+                let response = if request["method"] == "angstrom_subscribeAmm" {
+                    let pools: HashSet<PoolId> =
+                        serde_json::from_value(request["params"][0].clone()).unwrap();
+                    if pools.contains(&REJECTED) {
+                        json!({
+                            "jsonrpc": "2.0",
+                            "id": id,
+                            "error": { "code": -32602, "message": "unknown pool" }
+                        })
+                    } else {
+                        subscriptions += 1;
+                        json!({ "jsonrpc": "2.0", "id": id, "result": subscriptions })
+                    }
+                } else {
+                    json!({ "jsonrpc": "2.0", "id": id, "result": true })
+                };
+                ws.send(Message::text(response.to_string())).await.unwrap();
+            }
+        });
+
+        url
+    }
+
+    /// Polls the client until the server has answered `ack`
+    async fn answer(
+        client: &mut Slot0Client,
+        mut ack: Slot0SubscriptionAck
+    ) -> Result<HashSet<PoolId>, Slot0SubscriptionError> {
+        loop {
+            tokio::select! {
+                result = &mut ack => return result,
+                _ = client.next() => {}
+            }
+        }
+    }
+
+    fn slot0(pool_id: PoolId, current_block: u64, seq_id: u16) -> Slot0Update {
+        Slot0Update {
+            seq_id,
+            current_block,
+            angstrom_pool_id: pool_id,
+            uni_pool_id: pool_id,
+            sqrt_price_x96: Default::default(),
+            liquidity: 0,
+            tick: seq_id as i32
+        }
+    }
+
+    #[test]
+    fn last_seq_id_wins_per_pool_and_block() {
+        let (pool_a, pool_b) = (PoolId::repeat_byte(1), PoolId::repeat_byte(2));
+        let updates = vec![
+            slot0(pool_a, 10, 7),
+            slot0(pool_b, 10, 3),
+            slot0(pool_a, 10, 119),
+            slot0(pool_a, 9, 120),
+            slot0(pool_a, 10, 0),
+            slot0(pool_b, 11, 1),
+            slot0(pool_b, 10, 42),
+            slot0(pool_a, 10, 64),
+        ];
+
+        let latest = latest_slot0_updates(updates, 10);
+        let applied = latest
+            .iter()
+            .map(|update| (update.uni_pool_id, update.current_block, update.tick))
+            .collect::<Vec<_>>();
+        assert_eq!(applied, vec![(pool_b, 10, 42), (pool_a, 10, 119), (pool_b, 11, 1)]);
+    }
+
+    #[tokio::test]
+    async fn acknowledgements_follow_the_server() {
+        let (pool_a, pool_b) = (PoolId::repeat_byte(1), PoolId::repeat_byte(2));
+        let ws = WsClientBuilder::default()
+            .build(mock_server().await)
+            .await
+            .unwrap();
+        let mut client = Slot0Client::new(Arc::new(ws));
+
+        let ack = client.subscribe_pools(HashSet::from([pool_a, pool_b]));
+        assert_eq!(client.subscribed_pools(), &HashSet::from([pool_a, pool_b]));
+        assert!(client.acknowledged_pools().is_empty());
+        assert_eq!(answer(&mut client, ack).await, Ok(HashSet::from([pool_a, pool_b])));
+        assert_eq!(client.acknowledged_pools(), &HashSet::from([pool_a, pool_b]));
+
+        let ack = client.unsubscribe_pools(HashSet::from([pool_a]));
+        assert_eq!(answer(&mut client, ack).await, Ok(HashSet::from([pool_b])));
+        assert_eq!(client.subscribed_pools(), &HashSet::from([pool_b]));
+        assert_eq!(client.acknowledged_pools(), &HashSet::from([pool_b]));
+
+        // a rejected change leaves the live subscription as it was
+        let ack = client.subscribe_pools(HashSet::from([REJECTED]));
+        assert!(matches!(answer(&mut client, ack).await, Err(Slot0SubscriptionError::Rejected(_))));
+        assert!(client.subscribed_pools().contains(&REJECTED));
+        assert_eq!(client.acknowledged_pools(), &HashSet::from([pool_b]));
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+use uni_v4_structure::PoolId;
+
+/// Parameters for widening tick loads in the direction the price is trending
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PredictiveTickLoading {
+    /// consecutive blocks the tick has to move the same way to count as a
+    /// trend
+    pub trend_blocks:    u32,
+    /// how much the edge threshold and the number of ticks loaded grow on the
+    /// trending side
+    pub band_multiplier: u16
+}
+
+/// A batch of ticks to load past the loaded range of a pool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TickRequest {
+    pub(crate) zero_for_one: bool,
+    pub(crate) start_tick:   i32,
+    pub(crate) num_ticks:    u16,
+    pub(crate) threshold:    i32
+}
+
+#[derive(Debug, Default)]
+struct PoolTrend {
+    seen:         bool,
+    last_block:   Option<u64>,
+    last_tick:    i32,
+    zero_for_one: Option<bool>,
+    streak:       u32
+}
+
+/// Tracks which way each pool's tick has been moving to decide where to load
+/// ticks
+#[derive(Debug, Default)]
+pub(crate) struct TickTrends {
+    config: Option<PredictiveTickLoading>,
+    pools:  HashMap<PoolId, PoolTrend>
+}
+
+impl TickTrends {
+    pub(crate) fn set_config(&mut self, config: PredictiveTickLoading) {
+        self.config = Some(config);
+    }
+
+    pub(crate) fn remove(&mut self, pool_id: &PoolId) {
+        self.pools.remove(pool_id);
+    }
+
+    /// Records the pool's tick and returns the direction it is trending in, if
+    /// any. Only one observation per block counts towards a trend.
+    fn observe(&mut self, pool_id: PoolId, block: Option<u64>, tick: i32) -> Option<bool> {
+        let config = self.config?;
+        let trend = self.pools.entry(pool_id).or_default();
+
+        if !(trend.seen && block.is_some() && block == trend.last_block) {
+            let zero_for_one = match tick.cmp(&trend.last_tick) {
+                _ if !trend.seen => None,
+                std::cmp::Ordering::Less => Some(true),
+                std::cmp::Ordering::Greater => Some(false),
+                std::cmp::Ordering::Equal => None
+            };
+
+            if zero_for_one.is_some() && zero_for_one == trend.zero_for_one {
+                trend.streak += 1;
+            } else {
+                trend.streak = zero_for_one.map_or(0, |_| 1);
+            }
+            trend.zero_for_one = zero_for_one;
+            trend.seen = true;
+            trend.last_block = block;
+            trend.last_tick = tick;
+        }
+
+        trend
+            .zero_for_one
+            .filter(|_| trend.streak >= config.trend_blocks)
+    }
+
+    /// Works out which sides of the loaded `[min_tick, max_tick]` range need
+    /// more ticks. A side is loaded once the current tick is within
+    /// `edge_threshold` tick spacings of it, with both the threshold and the
+    /// amount loaded widened on the side the price is trending towards.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn plan_requests(
+        &mut self,
+        pool_id: PoolId,
+        block: Option<u64>,
+        current_tick: i32,
+        min_tick: i32,
+        max_tick: i32,
+        tick_spacing: i32,
+        edge_threshold: u16
+    ) -> Vec<TickRequest> {
+        let trend = self.observe(pool_id, block, current_tick);
+        let multiplier = self
+            .config
+            .map_or(1, |config| config.band_multiplier.max(1));
+        let num_ticks = |zero_for_one| {
+            if trend == Some(zero_for_one) {
+                edge_threshold.saturating_mul(multiplier)
+            } else {
+                edge_threshold
+            }
+        };
+
+        let mut requests = Vec::new();
+        for (zero_for_one, distance, start_tick) in [
+            (true, current_tick - min_tick, min_tick - tick_spacing),
+            (false, max_tick - current_tick, max_tick + tick_spacing)
+        ] {
+            let num_ticks = num_ticks(zero_for_one);
+            let threshold = (tick_spacing * num_ticks as i32).abs();
+            if distance < threshold {
+                requests.push(TickRequest { zero_for_one, start_tick, num_ticks, threshold });
+            }
+        }
+        requests
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POOL: PoolId = PoolId::repeat_byte(1);
+
+    #[test]
+    fn downtrend_widens_loading_below() {
+        let mut trends = TickTrends::default();
+        trends.set_config(PredictiveTickLoading { trend_blocks: 3, band_multiplier: 4 });
+
+        // loaded range [-6000, 6000], price walking down 600 per block. Without a
+        // trend nothing is close enough to an edge with a threshold of 10 * 60.
+        for (block, tick) in (1..=3).zip([0, -600, -1200]) {
+            assert!(
+                trends
+                    .plan_requests(POOL, Some(block), tick, -6000, 6000, 60, 10)
+                    .is_empty()
+            );
+        }
+
+        // the third move down makes it a trend, widening the threshold below to
+        // 40 spacings and loading 40 ticks
+        let requests = trends.plan_requests(POOL, Some(4), -3660, -6000, 6000, 60, 10);
+        assert_eq!(
+            requests,
+            vec![TickRequest {
+                zero_for_one: true,
+                start_tick:   -6060,
+                num_ticks:    40,
+                threshold:    2400
+            }]
+        );
+
+        // repeated observations within a block don't extend the trend, and a
+        // reversal resets it
+        trends.plan_requests(POOL, Some(4), -3660, -6000, 6000, 60, 10);
+        assert!(
+            trends
+                .plan_requests(POOL, Some(5), -3000, -6000, 6000, 60, 10)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn without_config_uses_the_edge_threshold() {
+        let mut trends = TickTrends::default();
+
+        let requests = trends.plan_requests(POOL, Some(1), 5700, -6000, 6000, 60, 10);
+        assert_eq!(
+            requests,
+            vec![TickRequest {
+                zero_for_one: false,
+                start_tick:   6060,
+                num_ticks:    10,
+                threshold:    600
+            }]
+        );
+    }
+}
@@ -0,0 +1,234 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    pin::Pin,
+    task::{Context, Poll}
+};
+
+use futures::Future;
+use tokio::sync::{
+    broadcast,
+    mpsc::{
+        self, OwnedPermit,
+        error::{SendError, TrySendError}
+    }
+};
+use uni_v4_common::{PoolUpdate, V4Network};
+use uni_v4_structure::PoolId;
+
+type ReserveFuture<T> = Pin<
+    Box<dyn Future<Output = Result<OwnedPermit<PoolUpdate<T>>, SendError<()>>> + Send + 'static>
+>;
+
+/// Where the service delivers updates when running in channel mode
+pub enum UpdateSender<T: V4Network> {
+    /// A single consumer. When the channel is full the service buffers updates
+    /// in order until the consumer catches up, so none are dropped.
+    Mpsc(mpsc::Sender<PoolUpdate<T>>),
+    /// Any number of subscribers, each receiving every update. A subscriber
+    /// that falls more than the channel capacity behind skips the oldest
+    /// updates and gets [`broadcast::error::RecvError::Lagged`] with the number
+    /// it missed; it should resync from the shared pools when that happens.
+    Broadcast(broadcast::Sender<PoolUpdate<T>>)
+}
+
+impl<T: V4Network> From<mpsc::Sender<PoolUpdate<T>>> for UpdateSender<T> {
+    fn from(sender: mpsc::Sender<PoolUpdate<T>>) -> Self {
+        Self::Mpsc(sender)
+    }
+}
+
+impl<T: V4Network> From<broadcast::Sender<PoolUpdate<T>>> for UpdateSender<T> {
+    fn from(sender: broadcast::Sender<PoolUpdate<T>>) -> Self {
+        Self::Broadcast(sender)
+    }
+}
+
+/// Channel-mode sender that never drops an update because an mpsc consumer is
+/// slow.
+///
+/// Updates go straight into the channel while it has capacity. Once it is full
+/// they are kept in an ordered backlog which is flushed from the service's
+/// `poll` as soon as the consumer frees up capacity. Updates are only discarded
+/// when the receiver has been dropped. Broadcast senders never block, so they
+/// have no backlog.
+pub(crate) struct UpdateChannel<T: V4Network> {
+    sender:  UpdateSender<T>,
+    // when set, only updates for these pools (plus block markers) are sent
+    pools:   Option<HashSet<PoolId>>,
+    backlog: VecDeque<PoolUpdate<T>>,
+    reserve: Option<ReserveFuture<T>>
+}
+
+impl<T: V4Network> UpdateChannel<T> {
+    pub(crate) fn new(sender: UpdateSender<T>) -> Self {
+        Self { sender, pools: None, backlog: VecDeque::new(), reserve: None }
+    }
+
+    pub(crate) fn filtered(sender: UpdateSender<T>, pools: HashSet<PoolId>) -> Self {
+        Self { pools: Some(pools), ..Self::new(sender) }
+    }
+
+    /// Number of updates waiting for the consumer to catch up
+    pub(crate) fn lagging(&self) -> usize {
+        self.backlog.len()
+    }
+
+    pub(crate) fn send(&mut self, update: PoolUpdate<T>) {
+        if let (Some(pools), Some(pool_id)) = (&self.pools, update.pool_id())
+            && !pools.contains(&pool_id)
+        {
+            return;
+        }
+
+        let sender = match &self.sender {
+            UpdateSender::Mpsc(sender) => sender,
+            UpdateSender::Broadcast(sender) => {
+                if sender.send(update).is_err() {
+                    tracing::debug!("no broadcast subscribers, update not delivered");
+                }
+                return;
+            }
+        };
+
+        // keep ordering: once anything is buffered everything after it is too
+        if !self.backlog.is_empty() {
+            self.backlog.push_back(update);
+            return;
+        }
+
+        match sender.try_send(update) {
+            Ok(()) => {}
+            Err(TrySendError::Full(update)) => {
+                tracing::warn!(
+                    "update channel is full, buffering updates until the consumer catches up"
+                );
+                self.backlog.push_back(update);
+            }
+            Err(TrySendError::Closed(_)) => {
+                tracing::error!("update channel receiver dropped, discarding update");
+            }
+        }
+    }
+
+    /// Moves buffered updates into the channel as capacity frees up. Registers
+    /// the waker with the channel while anything is left in the backlog.
+    pub(crate) fn poll_flush(&mut self, cx: &mut Context<'_>) {
+        let UpdateSender::Mpsc(sender) = &self.sender else { return };
+
+        while !self.backlog.is_empty() {
+            let reserve = self
+                .reserve
+                .get_or_insert_with(|| Box::pin(sender.clone().reserve_owned()));
+
+            match reserve.as_mut().poll(cx) {
+                Poll::Ready(Ok(permit)) => {
+                    self.reserve = None;
+                    permit.send(self.backlog.pop_front().unwrap());
+                    if self.backlog.is_empty() {
+                        tracing::info!("update channel consumer caught up");
+                    }
+                }
+                Poll::Ready(Err(_)) => {
+                    self.reserve = None;
+                    tracing::error!(
+                        "update channel receiver dropped, discarding {} buffered updates",
+                        self.backlog.len()
+                    );
+                    self.backlog.clear();
+                }
+                Poll::Pending => return
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_network::Ethereum;
+    use alloy_primitives::Address;
+    use uni_v4_common::SwapEventData;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn full_channel_buffers_instead_of_dropping() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let mut channel = UpdateChannel::<Ethereum>::new(tx.into());
+
+        for block in 0..5 {
+            channel.send(PoolUpdate::NewBlock(block));
+        }
+        assert_eq!(channel.lagging(), 4);
+
+        let mut received = Vec::new();
+        while received.len() < 5 {
+            futures::future::poll_fn(|cx| {
+                channel.poll_flush(cx);
+                Poll::Ready(())
+            })
+            .await;
+            match rx.recv().await {
+                Some(PoolUpdate::NewBlock(block)) => received.push(block),
+                other => panic!("unexpected update {other:?}")
+            }
+        }
+
+        assert_eq!(received, vec![0, 1, 2, 3, 4]);
+        assert_eq!(channel.lagging(), 0);
+    }
+
+    #[tokio::test]
+    async fn filtered_channel_skips_other_pools() {
+        let tracked = PoolId::with_last_byte(1);
+        let (tx, mut rx) = mpsc::channel(8);
+        let mut channel = UpdateChannel::<Ethereum>::filtered(tx.into(), HashSet::from([tracked]));
+
+        let swap = |pool_id| {
+            PoolUpdate::from_swap(
+                pool_id,
+                1,
+                0,
+                0,
+                SwapEventData {
+                    sender:         Address::ZERO,
+                    amount0:        0,
+                    amount1:        0,
+                    sqrt_price_x96: Default::default(),
+                    liquidity:      0,
+                    tick:           0,
+                    fee:            0
+                }
+            )
+        };
+        channel.send(PoolUpdate::NewBlock(1));
+        channel.send(swap(PoolId::with_last_byte(2)));
+        channel.send(swap(tracked));
+        drop(channel);
+
+        assert!(matches!(rx.recv().await, Some(PoolUpdate::NewBlock(1))));
+        assert!(
+            matches!(rx.recv().await, Some(PoolUpdate::SwapEvent { pool_id, .. }) if pool_id == tracked)
+        );
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn broadcast_reaches_every_subscriber() {
+        let (tx, mut first) = broadcast::channel(8);
+        let mut second = tx.subscribe();
+        let mut channel = UpdateChannel::<Ethereum>::new(tx.into());
+
+        for block in 0..3 {
+            channel.send(PoolUpdate::NewBlock(block));
+        }
+
+        for rx in [&mut first, &mut second] {
+            for expected in 0..3 {
+                assert!(
+                    matches!(rx.recv().await, Ok(PoolUpdate::NewBlock(block)) if block == expected)
+                );
+            }
+        }
+        assert_eq!(channel.lagging(), 0);
+    }
+}
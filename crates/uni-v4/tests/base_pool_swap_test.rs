@@ -90,3 +90,42 @@ async fn test_specific_pool_at_block() {
         }
     }
 }
+
+#[tokio::test]
+async fn test_base_address_book_loads_pools() {
+    let Some(base_url) = get_eth_url() else {
+        println!("No BASE_URL SET, returning");
+        return;
+    };
+
+    let pool_manager_address =
+        alloy::primitives::address!("0x498581ff718922c3f8e6a244956af099b2652b2b");
+
+    let provider = Arc::new(
+        ProviderBuilder::<_, _, Optimism>::default()
+            .with_recommended_fillers()
+            .connect(&base_url)
+            .await
+            .unwrap()
+    );
+
+    let service = PoolManagerServiceBuilder::new_with_noop_stream(
+        provider,
+        L2AddressBook::base(),
+        L2PoolRegistry::default(),
+        pool_manager_address,
+        42966000
+    )
+    .with_current_block(42977290)
+    .build()
+    .await
+    .expect("Failed to create service");
+
+    let pools = service.get_pools();
+    assert!(!pools.get_pools().is_empty(), "no Base pools loaded");
+    for entry in pools.get_pools().iter() {
+        let pool = entry.value();
+        assert!(pool.tick_spacing() > 0);
+        assert!(pool.liquidity().get_min_initialized_tick().is_some());
+    }
+}
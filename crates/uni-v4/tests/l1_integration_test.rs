@@ -8,6 +8,7 @@ use std::{
 
 use alloy::{
     eips::BlockId,
+    network::Ethereum,
     providers::{Provider, ProviderBuilder},
     rpc::types::Block
 };
@@ -20,6 +21,7 @@ use uni_v4::{
     tick_info::TickInfo
 };
 use uni_v4_upkeeper::{
+    pool_manager_service::PoolManagerServiceError,
     pool_manager_service_builder::PoolManagerServiceBuilder, slot0::NoOpSlot0Stream
 };
 
@@ -352,3 +354,235 @@ async fn test_pool_state_consistency() {
         assert_eq!(failures, 0, "Pool state comparison failed for {failures} pools");
     }
 }
+
+/// Needs `ETH_URL` to point at an archive node
+#[tokio::test]
+async fn test_pool_state_at_historical_block() {
+    let Some(eth_url) = get_eth_url() else {
+        println!("No ETH_URL SET, returning");
+        return;
+    };
+
+    let deploy_block = 22971782;
+    let historical_block = 23034200;
+    let current_block = historical_block + 10;
+
+    let angstrom_address =
+        alloy::primitives::address!("0x0000000aa232009084Bd71A5797d089AA4Edfad4");
+    let controller_address =
+        alloy::primitives::address!("0x1746484EA5e11C75e009252c102C8C33e0315fD4");
+    let pool_manager_address =
+        alloy::primitives::address!("0x000000000004444c5dc75cB358380D2e3dE08A90");
+
+    let address_book = L1AddressBook::new(controller_address, angstrom_address);
+
+    let provider = Arc::new(
+        ProviderBuilder::<_, _, Ethereum>::default()
+            .with_recommended_fillers()
+            .connect(&eth_url)
+            .await
+            .unwrap()
+    );
+
+    let build_service = |block| {
+        PoolManagerServiceBuilder::new_with_noop_stream(
+            provider.clone(),
+            address_book,
+            L1PoolRegistry::new(angstrom_address),
+            pool_manager_address,
+            deploy_block
+        )
+        .with_initial_tick_range_size(400)
+        .with_current_block(block)
+        .build()
+    };
+
+    let historical = build_service(historical_block)
+        .await
+        .expect("Failed to create historical service");
+    let service = build_service(current_block)
+        .await
+        .expect("Failed to create service");
+
+    // loading at an old block has to match a service started at that block
+    for entry in historical.get_pools().get_pools().iter() {
+        let expected = entry.value();
+        let loaded = service
+            .pool_state_at_block(*entry.key(), historical_block)
+            .await
+            .expect("Failed to load historical pool state");
+
+        assert_eq!(loaded.block_number(), historical_block);
+        assert_eq!(loaded.current_price(), expected.current_price());
+        assert_eq!(loaded.current_liquidity(), expected.current_liquidity());
+        assert_eq!(
+            loaded.liquidity().initialized_ticks(),
+            expected.liquidity().initialized_ticks()
+        );
+    }
+
+    assert!(matches!(
+        service
+            .pool_state_at_block(PoolId::ZERO, historical_block)
+            .await,
+        Err(PoolManagerServiceError::UnknownPool(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_quote_loading_ticks() {
+    let Some(eth_url) = get_eth_url() else {
+        println!("No ETH_URL SET, returning");
+        return;
+    };
+
+    let deploy_block = 22971782;
+    let angstrom_address =
+        alloy::primitives::address!("0x0000000aa232009084Bd71A5797d089AA4Edfad4");
+    let controller_address =
+        alloy::primitives::address!("0x1746484EA5e11C75e009252c102C8C33e0315fD4");
+    let pool_manager_address =
+        alloy::primitives::address!("0x000000000004444c5dc75cB358380D2e3dE08A90");
+
+    let provider = Arc::new(
+        ProviderBuilder::<_, _, Ethereum>::default()
+            .with_recommended_fillers()
+            .connect(&eth_url)
+            .await
+            .unwrap()
+    );
+
+    // load a narrow band so a modest swap runs past it
+    let mut service = PoolManagerServiceBuilder::new_with_noop_stream(
+        provider,
+        L1AddressBook::new(controller_address, angstrom_address),
+        L1PoolRegistry::new(angstrom_address),
+        pool_manager_address,
+        deploy_block
+    )
+    .with_initial_tick_range_size(5)
+    .build()
+    .await
+    .expect("Failed to create service");
+
+    let pools = service.get_pools();
+    let Some(pool_id) = pools.get_pools().iter().map(|entry| *entry.key()).next() else {
+        println!("No pools loaded, returning");
+        return;
+    };
+
+    // the smallest power of two past the loaded range
+    let (amount_in, min_tick) = {
+        let pool = pools.get_pool(&pool_id).unwrap();
+        let amount_in = (40..100)
+            .map(|exp| 1u128 << exp)
+            .find(|amount| pool.quote(*amount, true, false).is_err())
+            .expect("no amount left the loaded range");
+        (amount_in, pool.liquidity().get_min_initialized_tick().unwrap())
+    };
+
+    let quote = service
+        .quote_loading_ticks(pool_id, amount_in, true)
+        .await
+        .expect("quote failed after loading ticks");
+    assert_eq!(quote.amount_in, amount_in);
+    assert!(
+        pools
+            .get_pool(&pool_id)
+            .unwrap()
+            .liquidity()
+            .get_min_initialized_tick()
+            .unwrap()
+            < min_tick
+    );
+
+    assert!(matches!(
+        service
+            .quote_loading_ticks(PoolId::ZERO, amount_in, true)
+            .await,
+        Err(PoolManagerServiceError::UnknownPool(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_prewarm_ticks() {
+    let Some(eth_url) = get_eth_url() else {
+        println!("No ETH_URL SET, returning");
+        return;
+    };
+
+    let deploy_block = 22971782;
+    let angstrom_address =
+        alloy::primitives::address!("0x0000000aa232009084Bd71A5797d089AA4Edfad4");
+    let controller_address =
+        alloy::primitives::address!("0x1746484EA5e11C75e009252c102C8C33e0315fD4");
+    let pool_manager_address =
+        alloy::primitives::address!("0x000000000004444c5dc75cB358380D2e3dE08A90");
+    let address_book = L1AddressBook::new(controller_address, angstrom_address);
+
+    let provider = Arc::new(
+        ProviderBuilder::<_, _, Ethereum>::default()
+            .with_recommended_fillers()
+            .connect(&eth_url)
+            .await
+            .unwrap()
+    );
+    let current_block = provider.get_block_number().await.unwrap();
+
+    let build_service = |tick_range| {
+        PoolManagerServiceBuilder::new_with_noop_stream(
+            provider.clone(),
+            address_book,
+            L1PoolRegistry::new(angstrom_address),
+            pool_manager_address,
+            deploy_block
+        )
+        .with_initial_tick_range_size(tick_range)
+        .with_current_block(current_block)
+        .build()
+    };
+
+    // a narrow initial band to warm up, and a wide one to check against
+    let mut service = build_service(5).await.expect("Failed to create service");
+    let reference = build_service(400)
+        .await
+        .expect("Failed to create reference service");
+
+    let pools = service.get_pools();
+    let Some(pool_id) = pools.get_pools().iter().map(|entry| *entry.key()).next() else {
+        println!("No pools loaded, returning");
+        return;
+    };
+    let (lower_tick, upper_tick) = {
+        let pool = pools.get_pool(&pool_id).unwrap();
+        let band = 200 * pool.tick_spacing();
+        (pool.current_tick() - band, pool.current_tick() + band)
+    };
+
+    service
+        .prewarm_ticks(pool_id, lower_tick, upper_tick)
+        .await
+        .expect("Failed to prewarm ticks");
+
+    {
+        let warmed = pools.get_pool(&pool_id).unwrap();
+        let reference_pools = reference.get_pools();
+        let expected = reference_pools.get_pool(&pool_id).unwrap();
+        for (tick, info) in expected.liquidity().initialized_ticks() {
+            if (lower_tick..=upper_tick).contains(tick) {
+                assert_eq!(warmed.liquidity().initialized_ticks().get(tick), Some(info));
+            }
+        }
+    }
+
+    assert!(matches!(
+        service.prewarm_ticks(pool_id, upper_tick, lower_tick).await,
+        Err(PoolManagerServiceError::InvalidTickBand { .. })
+    ));
+    assert!(matches!(
+        service
+            .prewarm_ticks(PoolId::ZERO, lower_tick, upper_tick)
+            .await,
+        Err(PoolManagerServiceError::UnknownPool(_))
+    ));
+}
@@ -89,3 +89,51 @@ async fn test_specific_pool_at_block() {
         }
     }
 }
+
+#[tokio::test]
+async fn test_swap_after_wait_until_ready() {
+    let Some(eth_url) = get_eth_url() else {
+        println!("No ETH_URL SET, returning");
+        return;
+    };
+
+    let deploy_block = 22971782u64;
+    let angstrom_address = address!("0x0000000aa232009084Bd71A5797d089AA4Edfad4");
+    let controller_address = address!("0x1746484EA5e11C75e009252c102C8C33e0315fD4");
+    let pool_manager_address = address!("0x000000000004444c5dc75cB358380D2e3dE08A90");
+
+    let provider = Arc::new(
+        ProviderBuilder::<_, _, Ethereum>::default()
+            .with_recommended_fillers()
+            .connect(&eth_url)
+            .await
+            .unwrap()
+    );
+
+    let mut service = PoolManagerServiceBuilder::new_with_noop_stream(
+        provider,
+        L1AddressBook::new(controller_address, angstrom_address),
+        L1PoolRegistry::new(angstrom_address),
+        pool_manager_address,
+        deploy_block
+    )
+    .build()
+    .await
+    .expect("Failed to create service");
+
+    service.wait_until_ready().await;
+    assert!(service.is_initialized());
+
+    let pools = service.get_pools();
+    for entry in pools.get_pools().iter() {
+        let pool_state = entry.value();
+        if pool_state.current_liquidity() == 0 {
+            continue;
+        }
+
+        let amount = I256::from(U256::from(623754804));
+        pool_state
+            .swap_current_with_amount(amount, false, true)
+            .expect("swap failed on a ready pool");
+    }
+}
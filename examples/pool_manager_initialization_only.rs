@@ -93,7 +93,7 @@ async fn main() -> Result<()> {
                 PoolUpdate::NewBlock(block) => {
                     println!("📦 Block #{block}: Received NewBlock");
                 }
-                PoolUpdate::FeeUpdate { pool_id, block, update } => {
+                PoolUpdate::FeeUpdate { pool_id, block, update, .. } => {
                     println!(
                         "💰 Received FeeUpdate for pool {pool_id:?} at block {block} - update: \
                          {:?}",
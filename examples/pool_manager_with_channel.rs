@@ -75,37 +75,7 @@ async fn main() -> Result<()> {
         while let Some(msg) = rx.recv().await {
             message_count += 1;
 
-            // Log the message type
-            match &msg {
-                PoolUpdate::NewBlock(block) => {
-                    println!("📦 Block #{block}: Received NewBlock");
-                }
-                PoolUpdate::ChainSpecific { pool_id, update } => {
-                    println!("🔄 Received ChainSpecific update for pool {pool_id:?}: {:?}", update);
-                }
-                PoolUpdate::SwapEvent { pool_id, .. } => {
-                    println!("💱 Received SwapEvent for pool {pool_id:?}");
-                }
-                PoolUpdate::LiquidityEvent { pool_id, .. } => {
-                    println!("💧 Received LiquidityEvent for pool {pool_id:?}");
-                }
-                PoolUpdate::NewTicks { pool_id, ticks, .. } => {
-                    println!("📊 Received NewTicks for pool {:?} ({} ticks)", pool_id, ticks.len());
-                }
-                PoolUpdate::NewPoolState { pool_id, .. } => {
-                    println!("🆕 Received NewPoolState with state for pool {pool_id:?}");
-                }
-                PoolUpdate::UpdatedSlot0 { pool_id, data } => {
-                    println!(
-                        "🔄 Received UpdatedSlot0 for pool {:?} - tick: {}, sqrt_price: {}, \
-                         liquidity: {}",
-                        pool_id, data.tick, data.sqrt_price_x96, data.liquidity
-                    );
-                }
-                _ => {
-                    println!("📬 Received other message type");
-                }
-            }
+            println!("📬 {msg}");
 
             // Apply the update to our local pool instance
             local_pools.update_pools(vec![msg]);
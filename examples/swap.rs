@@ -50,7 +50,7 @@ async fn main() -> eyre::Result<()> {
 
     // Create pool manager service with both event stream and slot0 stream
     println!("🔨 Building pool manager service with slot0 stream...");
-    let service = PoolManagerServiceBuilder::<_, _, _, NoOpSlot0Stream>::new(
+    let mut service = PoolManagerServiceBuilder::<_, _, _, NoOpSlot0Stream>::new(
         provider.clone(),
         address_book,
         pool_registry,
@@ -62,6 +62,8 @@ async fn main() -> eyre::Result<()> {
     .build()
     .await?;
 
+    // make sure the ticks of the initial pools are loaded before quoting
+    service.wait_until_ready().await;
     println!("✅ Pool service initialized!");
     println!("📊 Found {} pools", service.get_pools().len());
     println!("🔗 Current block: {}", service.current_block());